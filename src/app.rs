@@ -1,50 +1,131 @@
+#[cfg(test)]
+use crate::services::api_keys::api_key_store::Scope;
 use crate::{
-    middlewares::auth::auth_middleware,
+    middlewares::{
+        auth::auth_middleware,
+        rate_limit::{rate_limit_middleware, RateLimitConfig, RateLimits},
+    },
     routes::apply_routes,
     services::{
+        announcements::{announcer::Announcer, clip_library::ClipLibrary},
+        api_keys::{api_key_service::ApiKeyService, api_key_store::ApiKeyStore},
+        departure_advice::{
+            departure_advisor::DepartureAdvisor, walking_time::WalkingTimeEstimator,
+        },
+        gtfs_static::gtfs_static_store::GtfsStaticStore,
+        jwt::jwt_verifier::JwtVerifier,
+        live_updates::live_poller::LivePoller,
         maps_client::maps_service::{MapsService, MapsServiceConfig},
-        transit_service::transit_service::{TransitService, TransitServiceConfig},
+        notifications::{
+            notification_scheduler::NotificationScheduler,
+            webhook_sender::{WebhookConfig, WebhookSender},
+        },
+        transit_service::{
+            stale_arrivals::{StaleArrivals, StaleArrivalsConfig},
+            transit_provider::TransitProvider,
+            transit_service::{TransitService, TransitServiceConfig},
+        },
+        user_data::user_data_store::UserDataStore,
     },
     types::app_state::AppState,
+    utils::upstream_client::UpstreamConfig,
 };
 use axum::{middleware, routing::get, Router};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 use tower_http::cors::CorsLayer;
 
 pub struct AppConfig {
-    pub mta_host: String,
-    pub mta_key: String,
+    pub transit_providers: Vec<Arc<dyn TransitProvider>>,
     pub google_maps_host: String,
     pub google_maps_key: String,
+    pub maps_upstream: UpstreamConfig,
+    /// Grants every scope. Leaving it unset turns auth off.
     pub auth_key: Option<String>,
+    /// The keys issued through `/admin/keys`.
+    pub api_keys: Arc<dyn ApiKeyStore>,
+    /// Verifies `Authorization: Bearer` tokens. Setting it also turns auth on.
+    pub jwt: Option<JwtVerifier>,
+    pub gtfs_static: GtfsStaticStore,
+    /// How long arrivals are served, and where they are kept, once fetching fresh ones fails.
+    pub stale_arrivals: StaleArrivalsConfig,
+    /// How often streamed arrivals are refreshed.
+    pub arrivals_poll_interval: Duration,
+    /// Where the clips spoken by `/audio/arrivals` are recorded. Without them it answers 503.
+    pub audio_clips_dir: Option<PathBuf>,
+    /// Estimates the walk from an origin to nearby stops for `/transit-departure-advice`.
+    pub walking_time: Arc<dyn WalkingTimeEstimator>,
+    /// Where favorites and commute profiles are saved for each client.
+    pub user_data: Arc<dyn UserDataStore>,
+    /// How often notification rules are checked. `None` leaves webhooks unsent.
+    pub notification_poll_interval: Option<Duration>,
+    /// Per client limits and daily quotas. `None` lets clients make as many requests as they like.
+    pub rate_limits: Option<RateLimitConfig>,
 }
 
 pub fn gen_app(
     AppConfig {
+        transit_providers,
         auth_key,
-        mta_host,
+        api_keys,
+        jwt,
         google_maps_host,
-        mta_key,
         google_maps_key,
+        maps_upstream,
+        gtfs_static,
+        stale_arrivals,
+        arrivals_poll_interval,
+        audio_clips_dir,
+        walking_time,
+        user_data,
+        notification_poll_interval,
+        rate_limits,
     }: AppConfig,
 ) -> Router {
     let cors_middleware = CorsLayer::new();
     let maps_service = MapsService::new(MapsServiceConfig {
         host: google_maps_host,
         api_key: google_maps_key,
+        upstream: maps_upstream,
     });
+    let transit_service = TransitService::new(TransitServiceConfig {
+        providers: transit_providers,
+        maps_service: maps_service.clone(),
+        stale_arrivals: StaleArrivals::new(stale_arrivals),
+    });
+    if let Some(interval) = notification_poll_interval {
+        NotificationScheduler::new(
+            transit_service.clone(),
+            user_data.clone(),
+            WebhookSender::new(WebhookConfig::default()),
+        )
+        .spawn(interval);
+    }
+
     let state = AppState {
-        transit_service: TransitService::new(TransitServiceConfig {
-            host: mta_host,
-            api_key: mta_key,
-            maps_service: maps_service.clone(),
-        }),
+        live_poller: LivePoller::new(transit_service.clone(), arrivals_poll_interval),
+        departure_advisor: DepartureAdvisor::new(transit_service.clone(), walking_time),
+        transit_service,
+        announcer: audio_clips_dir.map(|dir| Announcer::new(ClipLibrary::new(dir))),
         maps_service: maps_service.clone(),
+        gtfs_static,
+        user_data,
+        api_keys: ApiKeyService::new(api_keys),
         auth_key,
+        jwt,
     };
 
-    apply_routes(Router::new())
+    let mut router = apply_routes(Router::new())
         .route("/", get(root))
-        .layer(cors_middleware)
+        .layer(cors_middleware);
+    // route layers added later run first, so clients are identified before being limited
+    if let Some(config) = rate_limits {
+        router = router.route_layer(middleware::from_fn_with_state(
+            RateLimits::new(config),
+            rate_limit_middleware,
+        ));
+    }
+
+    router
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
@@ -56,48 +137,100 @@ pub fn gen_app(
 pub struct MockApp {
     pub mta_server: mockito::ServerGuard,
     pub google_server: mockito::ServerGuard,
+    /// An empty clip directory for tests to record into.
+    pub audio_clips: tempfile::TempDir,
+    /// Empty until a test loads a feed into it.
+    pub gtfs_static: GtfsStaticStore,
+    pub api_keys: ApiKeyService,
     pub app: Router,
 }
 
+#[cfg(test)]
+impl MockApp {
+    /// A stored key with the scopes anyone has while auth is off, for requests that need a
+    /// client of their own.
+    pub async fn issue_key(&self) -> String {
+        self.issue(Scope::public()).await
+    }
+
+    pub async fn issue_admin_key(&self) -> String {
+        self.issue(Scope::all()).await
+    }
+
+    async fn issue(&self, scopes: Vec<Scope>) -> String {
+        self.api_keys
+            .issue("test", scopes, None)
+            .await
+            .unwrap()
+            .token
+    }
+}
+
 #[cfg(test)]
 pub async fn gen_mock_app() -> MockApp {
+    gen_mock_app_with_auth(None, None).await
+}
+
+#[cfg(test)]
+pub async fn gen_mock_app_with_auth(auth_key: Option<&str>, jwt: Option<JwtVerifier>) -> MockApp {
+    use crate::services::{
+        api_keys::sqlite_api_key_store::SqliteApiKeyStore,
+        departure_advice::walking_time::StraightLineWalking,
+        transit_service::providers::{
+            gtfs_provider::{GtfsProvider, GtfsProviderConfig},
+            one_bus_away_provider::{
+                OneBusAwayProvider, OneBusAwayProviderConfig, TransitCacheConfig,
+            },
+        },
+        user_data::sqlite_user_data_store::SqliteUserDataStore,
+    };
+
     let mock_mta_server = mockito::Server::new_async().await;
     let mock_google_server = mockito::Server::new_async().await;
 
-    let app_config = AppConfig {
-        mta_host: mock_mta_server.url(),
-        mta_key: "key".to_string(),
+    let gtfs_static = GtfsStaticStore::default();
+    let audio_clips = tempfile::tempdir().unwrap();
+    let api_keys = Arc::new(SqliteApiKeyStore::open_in_memory().unwrap());
+
+    // the gtfs provider answers nothing until a test loads a feed into the store
+    let router = gen_app(AppConfig {
+        transit_providers: vec![
+            Arc::new(GtfsProvider::new(GtfsProviderConfig {
+                name: "gtfs".to_string(),
+                gtfs_static: gtfs_static.clone(),
+                realtime: None,
+            })),
+            Arc::new(OneBusAwayProvider::new(OneBusAwayProviderConfig {
+                name: "mta".to_string(),
+                host: mock_mta_server.url(),
+                api_key: "key".to_string(),
+                agency_ids: Some(vec!["MTA NYCT".to_string()]),
+                cache: TransitCacheConfig::default(),
+                upstream: UpstreamConfig::default(),
+            })),
+        ],
         google_maps_host: mock_google_server.url(),
         google_maps_key: "key".to_string(),
-        auth_key: None,
-    };
-    let cors_middleware = CorsLayer::new();
-    let maps_service = MapsService::new(MapsServiceConfig {
-        host: app_config.google_maps_host,
-        api_key: app_config.google_maps_key,
+        maps_upstream: UpstreamConfig::default(),
+        auth_key: auth_key.map(|k| k.to_string()),
+        api_keys: api_keys.clone(),
+        jwt,
+        gtfs_static: gtfs_static.clone(),
+        stale_arrivals: StaleArrivalsConfig::default(),
+        arrivals_poll_interval: Duration::from_millis(50),
+        audio_clips_dir: Some(audio_clips.path().to_path_buf()),
+        walking_time: Arc::new(StraightLineWalking::default()),
+        user_data: Arc::new(SqliteUserDataStore::open_in_memory().unwrap()),
+        notification_poll_interval: None,
+        rate_limits: None,
     });
-    let state = AppState {
-        transit_service: TransitService::new(TransitServiceConfig {
-            host: app_config.mta_host,
-            api_key: app_config.mta_key,
-            maps_service: maps_service.clone(),
-        }),
-        maps_service: maps_service.clone(),
-        auth_key: app_config.auth_key,
-    };
-
-    let router = apply_routes(Router::new())
-        .route("/", get(root))
-        .layer(cors_middleware)
-        .route_layer(middleware::from_fn_with_state(
-            state.clone(),
-            auth_middleware,
-        ))
-        .with_state(state);
 
     MockApp {
         mta_server: mock_mta_server,
         google_server: mock_google_server,
+        audio_clips,
+        gtfs_static,
+        api_keys: ApiKeyService::new(api_keys),
         app: router,
     }
 }
@@ -2,8 +2,29 @@ mod routes;
 mod types;
 mod utils;
 use app::AppConfig;
-use std::env;
-use tracing::info;
+use middlewares::rate_limit::RateLimitConfig;
+use services::{
+    announcements::{announcer::Announcer, clip_library::ClipLibrary},
+    api_keys::sqlite_api_key_store::SqliteApiKeyStore,
+    departure_advice::walking_time::StraightLineWalking,
+    gtfs_realtime::gtfs_realtime_feed::{GtfsRealtimeConfig, GtfsRealtimeFeed, GtfsRealtimeSource},
+    gtfs_static::gtfs_static_store::{GtfsStaticConfig, GtfsStaticStore},
+    jwt::jwt_verifier::{JwtConfig, JwtKeySource, JwtVerifier},
+    transit_service::{
+        providers::{
+            gtfs_provider::{GtfsProvider, GtfsProviderConfig},
+            one_bus_away_provider::{
+                OneBusAwayProvider, OneBusAwayProviderConfig, TransitCacheConfig,
+            },
+        },
+        stale_arrivals::StaleArrivalsConfig,
+        transit_provider::TransitProvider,
+    },
+    user_data::sqlite_user_data_store::SqliteUserDataStore,
+};
+use std::{env, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tracing::{info, warn};
+use utils::{rate_limiter::RateLimit, upstream_client::UpstreamConfig};
 mod app;
 mod middlewares;
 mod services;
@@ -17,17 +38,241 @@ async fn main() {
     // get all stops: https://bustime.mta.info/api/where/stops-for-route/MTA%20NYCT_{BUS}+.json?key={KEY}&includePolylines=false&version=2
     // get all buses at stop: https://bustime.mta.info/api/siri/stop-monitoring.json?key={KEY}&MonitoringRef={STOP_REF}
     // let resp = reqwest::get("https://bustime.mta.info/api/siri/stop-monitoring.json?key={KEY}&MonitoringRef={STOP_REF}").await?.error_for_status();
+    let gtfs_static = GtfsStaticStore::default();
+    if let Ok(path) = env::var("GTFS_STATIC_PATH") {
+        let summary = gtfs_static
+            .load(GtfsStaticConfig {
+                path: PathBuf::from(&path),
+                stop_id_prefix: env::var("GTFS_STOP_ID_PREFIX").ok(),
+            })
+            .await
+            .unwrap_or_else(|e| panic!("Failed to load GTFS static feed: {}", e));
+
+        info!(
+            "Loaded GTFS static feed from {} with {} routes and {} stops",
+            path, summary.routes, summary.stops
+        );
+    }
+
+    let upstream_seconds = |key: &str, default: u64| {
+        Duration::from_secs(
+            env::var(key)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default),
+        )
+    };
+    let upstream = UpstreamConfig {
+        connect_timeout: upstream_seconds("UPSTREAM_CONNECT_TIMEOUT_SECONDS", 3),
+        read_timeout: upstream_seconds("UPSTREAM_READ_TIMEOUT_SECONDS", 10),
+        timeout: upstream_seconds("UPSTREAM_TIMEOUT_SECONDS", 15),
+        deadline: upstream_seconds("UPSTREAM_DEADLINE_SECONDS", 30),
+        ..Default::default()
+    };
+
+    let transit_cache = TransitCacheConfig::default();
+    let gtfs_realtime = GtfsRealtimeConfig {
+        trip_updates: env::var("GTFS_RT_TRIP_UPDATES")
+            .ok()
+            .map(|s| GtfsRealtimeSource::parse(&s)),
+        vehicle_positions: env::var("GTFS_RT_VEHICLE_POSITIONS")
+            .ok()
+            .map(|s| GtfsRealtimeSource::parse(&s)),
+        cache_ttl: transit_cache.stop_monitoring_ttl,
+        upstream: upstream.clone(),
+    };
+    let has_gtfs_realtime =
+        gtfs_realtime.trip_updates.is_some() || gtfs_realtime.vehicle_positions.is_some();
+
+    // e.g. TRANSIT_PROVIDERS=gtfs,mta,onebusaway:PUGET_SOUND. Without a static feed, the gtfs
+    // provider can't tell which stops are its own, so it goes last.
+    let default_providers = match (gtfs_static.index().is_some(), has_gtfs_realtime) {
+        (true, _) => "gtfs,mta",
+        (false, true) => "mta,gtfs",
+        (false, false) => "mta",
+    };
+    let transit_providers: Vec<Arc<dyn TransitProvider>> = env::var("TRANSIT_PROVIDERS")
+        .unwrap_or(default_providers.to_string())
+        .split(',')
+        .map(|name| -> Arc<dyn TransitProvider> {
+            match name.trim() {
+                "mta" => Arc::new(OneBusAwayProvider::new(OneBusAwayProviderConfig {
+                    name: "mta".to_string(),
+                    host: "https://bustime.mta.info".to_string(),
+                    api_key: env::var("MTA_KEY").expect("MTA API key is expected"),
+                    agency_ids: parse_agency_ids("MTA_AGENCIES"),
+                    cache: transit_cache.clone(),
+                    upstream: upstream.clone(),
+                })),
+                "gtfs" => Arc::new(GtfsProvider::new(GtfsProviderConfig {
+                    name: "gtfs".to_string(),
+                    gtfs_static: gtfs_static.clone(),
+                    realtime: match has_gtfs_realtime {
+                        true => Some(GtfsRealtimeFeed::new(gtfs_realtime.clone())),
+                        false => None,
+                    },
+                })),
+                other => match other.strip_prefix("onebusaway:") {
+                    Some(prefix) => {
+                        let var = |suffix: &str| {
+                            let key = format!("{}_OBA_{}", prefix, suffix);
+                            env::var(&key).unwrap_or_else(|_| panic!("{} is expected", key))
+                        };
+
+                        Arc::new(OneBusAwayProvider::new(OneBusAwayProviderConfig {
+                            name: prefix.to_lowercase(),
+                            host: var("HOST"),
+                            api_key: var("KEY"),
+                            agency_ids: parse_agency_ids(&format!("{}_OBA_AGENCIES", prefix)),
+                            cache: transit_cache.clone(),
+                            upstream: upstream.clone(),
+                        }))
+                    }
+                    None => panic!(
+                        "Unknown transit provider {}, expected mta, gtfs or onebusaway:<NAME>",
+                        other
+                    ),
+                },
+            }
+        })
+        .collect();
+
+    // a shared secret takes precedence over a key set
+    let jwt_key = match (env::var("JWT_HS256_SECRET"), env::var("JWT_JWKS_PATH")) {
+        (Ok(secret), _) => Some(JwtKeySource::Hs256Secret(secret)),
+        (_, Ok(path)) => Some(JwtKeySource::JwksFile(PathBuf::from(path))),
+        _ => None,
+    };
+    let jwt = jwt_key.map(|key| {
+        JwtVerifier::new(JwtConfig {
+            key,
+            // tokens minted for other services of the same identity provider must not pass
+            audience: env::var("JWT_AUDIENCE")
+                .expect("JWT_AUDIENCE is expected when JWT verification is configured"),
+            issuer: env::var("JWT_ISSUER").ok(),
+            // comma separated `sub`s that may administer
+            admin_subjects: env::var("JWT_ADMIN_SUBJECTS")
+                .map(|subs| subs.split(',').map(|s| s.trim().to_string()).collect())
+                .unwrap_or_default(),
+        })
+        .unwrap_or_else(|e| panic!("Failed to set up JWT verification: {}", e))
+    });
+
+    // anonymous clients are limited by IP, so limits stay off until TRUST_FORWARDED_FOR says
+    // whether that is the peer's (false) or the one a proxy in front appends to
+    // X-Forwarded-For (true). Behind a proxy, the peer is always the proxy.
+    let rate_limits = match env::var("TRUST_FORWARDED_FOR") {
+        Ok(trust) => {
+            // limits are <per minute>:<burst>[:<daily quota>], e.g.
+            // RATE_LIMIT_ROUTES=/location-search-autocomplete=10:5:500,/transit-routes=60:20
+            let mut rate_limits = RateLimitConfig::default();
+            if let Ok(limit) = env::var("RATE_LIMIT") {
+                rate_limits.default = RateLimit::parse(&limit)
+                    .unwrap_or_else(|| panic!("Invalid RATE_LIMIT {}", limit));
+            }
+            if let Ok(routes) = env::var("RATE_LIMIT_ROUTES") {
+                for route in routes.split(',') {
+                    let limit = route
+                        .split_once('=')
+                        .and_then(|(path, limit)| {
+                            Some((path.trim().to_string(), RateLimit::parse(limit)?))
+                        })
+                        .unwrap_or_else(|| panic!("Invalid RATE_LIMIT_ROUTES entry {}", route));
+                    rate_limits.routes.insert(limit.0, limit.1);
+                }
+            }
+            rate_limits.trust_forwarded_for = match trust.as_str() {
+                "true" => true,
+                "false" => false,
+                other => panic!(
+                    "Invalid TRUST_FORWARDED_FOR {}, expected true or false",
+                    other
+                ),
+            };
+
+            Some(rate_limits)
+        }
+        Err(_) => {
+            warn!("TRUST_FORWARDED_FOR is not set, rate limits are off");
+            None
+        }
+    };
+
+    // no clips ship with the server, so /audio/arrivals is off until a deployment records its own
+    let audio_clips_dir = match env::var("AUDIO_CLIPS_DIR").map(PathBuf::from) {
+        Ok(dir) => match Announcer::new(ClipLibrary::new(dir.clone())).check().await {
+            Ok(()) => Some(dir),
+            Err(e) => {
+                warn!(
+                    "Announcement clips in {} are incomplete, /audio/arrivals is off: {}",
+                    dir.display(),
+                    e
+                );
+                None
+            }
+        },
+        Err(_) => {
+            warn!("AUDIO_CLIPS_DIR is not set, /audio/arrivals is off");
+            None
+        }
+    };
+
     let app = app::gen_app(AppConfig {
-        mta_host: "https://bustime.mta.info".to_string(),
+        transit_providers,
         google_maps_host: "https://maps.googleapis.com".to_string(),
-        mta_key: env::var("MTA_KEY").expect("MTA API key is expected"),
         google_maps_key: env::var("GOOGLE_MAPS_KEY").expect("Google Maps API key is expected"),
+        maps_upstream: upstream,
         auth_key: match &env::var("AUTH_KEY") {
             Ok(auth_key) => Some(auth_key.to_string()),
             Err(_) => None,
         },
+        api_keys: Arc::new(
+            SqliteApiKeyStore::open(&PathBuf::from(
+                env::var("API_KEYS_DB_PATH").unwrap_or("api_keys.db".to_string()),
+            ))
+            .unwrap_or_else(|e| panic!("Failed to open API key database: {}", e)),
+        ),
+        jwt,
+        gtfs_static,
+        stale_arrivals: StaleArrivalsConfig {
+            dir: env::var("STALE_ARRIVALS_DIR").ok().map(PathBuf::from),
+            ..Default::default()
+        },
+        arrivals_poll_interval: Duration::from_secs(
+            env::var("ARRIVALS_POLL_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(15),
+        ),
+        audio_clips_dir,
+        walking_time: Arc::new(StraightLineWalking::default()),
+        user_data: Arc::new(
+            SqliteUserDataStore::open(&PathBuf::from(
+                env::var("USER_DATA_DB_PATH").unwrap_or("user_data.db".to_string()),
+            ))
+            .unwrap_or_else(|e| panic!("Failed to open user data database: {}", e)),
+        ),
+        notification_poll_interval: Some(Duration::from_secs(
+            env::var("NOTIFICATION_POLL_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+        )),
+        rate_limits,
     });
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Reads a comma separated agency list, leaving agencies to be discovered when it is unset.
+fn parse_agency_ids(key: &str) -> Option<Vec<String>> {
+    env::var(key)
+        .ok()
+        .map(|ids| ids.split(',').map(|id| id.trim().to_string()).collect())
 }
@@ -1,26 +1,244 @@
 use axum::{
-    extract::{Request, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, UPGRADE},
+        HeaderMap, StatusCode,
+    },
     middleware::Next,
     response::Response,
 };
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tracing::{error, warn};
 
-use crate::{types::app_state::AppState, utils::app_error::AppError};
+use crate::{
+    services::api_keys::api_key_store::Scope,
+    types::app_state::AppState,
+    utils::{
+        app_error::AppError,
+        client_identity::{ClientIdentity, GrantedScopes},
+    },
+};
+
+/// Browsers can't set headers on a WebSocket, so its upgrade request may carry the credentials in
+/// its query instead.
+#[derive(Deserialize, Default)]
+struct QueryCredentials {
+    /// In place of `Authorization: Bearer`.
+    access_token: Option<String>,
+    /// In place of `Temp-Authorization`.
+    api_key: Option<String>,
+}
 
+/// Authenticates an `Authorization: Bearer` JWT, or the `Temp-Authorization` header against
+/// `AUTH_KEY` and the key store. With neither `AUTH_KEY` nor JWT verification configured auth is
+/// off: requests without a known key are anonymous and may only read, while stored keys keep
+/// their scopes so that admin routes stay closed.
 pub async fn auth_middleware(
     State(state): State<AppState>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    match state.auth_key {
-        Some(ref auth_key) => match headers.get("Temp-Authorization") {
-            Some(header) if header == auth_key => Ok(next.run(request).await),
-            _ => Err(AppError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
+    let is_websocket = headers
+        .get(UPGRADE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.eq_ignore_ascii_case("websocket"));
+    let query = match is_websocket {
+        true => Query::<QueryCredentials>::try_from_uri(request.uri())
+            .map(|Query(q)| q)
+            .unwrap_or_default(),
+        false => QueryCredentials::default(),
+    };
+
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .or(query.access_token.as_deref());
+    let key = headers
+        .get("Temp-Authorization")
+        .and_then(|h| h.to_str().ok())
+        .or(query.api_key.as_deref());
+    let auth_enabled = state.auth_key.is_some() || state.jwt.is_some();
+
+    let (identity, scopes) = match (bearer, &state.jwt) {
+        (Some(token), Some(jwt)) => {
+            let claims = jwt.verify(token.trim()).map_err(|e| {
+                warn!("Rejected bearer token: {}", e);
+                AppError::new(StatusCode::UNAUTHORIZED, "Unauthorized")
+            })?;
+            let authenticated = (
+                ClientIdentity::from_jwt_subject(&claims.sub),
+                jwt.scopes(&claims),
+            );
+
+            request.extensions_mut().insert(claims);
+            authenticated
+        }
+        _ => match key {
+            Some(key) => match authenticate(&state, key).await? {
+                Some(authenticated) => authenticated,
+                None if !auth_enabled => (ClientIdentity::anonymous(), Scope::public()),
+                None => return Err(AppError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
+            },
+            None if !auth_enabled => (ClientIdentity::anonymous(), Scope::public()),
+            None => return Err(AppError::new(StatusCode::UNAUTHORIZED, "Unauthorized")),
         },
-        None => {
-            let response = next.run(request).await;
-            return Ok(response);
+    };
+
+    request.extensions_mut().insert(identity);
+    request.extensions_mut().insert(GrantedScopes(scopes));
+    Ok(next.run(request).await)
+}
+
+async fn authenticate(
+    state: &AppState,
+    key: &str,
+) -> Result<Option<(ClientIdentity, Vec<Scope>)>, AppError> {
+    // AUTH_KEY can do everything, which is how the first stored keys get issued
+    if let Some(auth_key) = &state.auth_key {
+        if bool::from(key.as_bytes().ct_eq(auth_key.as_bytes())) {
+            return Ok(Some((
+                ClientIdentity::from_key(key.as_bytes()),
+                Scope::all(),
+            )));
+        }
+    }
+
+    match state.api_keys.authenticate(key).await {
+        Ok(key) => Ok(key.map(|key| (ClientIdentity::from_key_id(&key.id), key.scopes))),
+        Err(e) => {
+            error!("Failed to look up API key: {}", e);
+            Err(AppError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error",
+            ))
+        }
+    }
+}
+
+/// Rejects requests whose credential lacks the scope a group of routes is layered with.
+pub async fn require_scope(
+    State(scope): State<Scope>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    match request.extensions().get::<GrantedScopes>() {
+        Some(GrantedScopes(scopes)) if scopes.contains(&scope) => Ok(next.run(request).await),
+        _ => Err(AppError::new(StatusCode::FORBIDDEN, "Forbidden")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use tower::ServiceExt;
+
+    use crate::{
+        app::{gen_mock_app, gen_mock_app_with_auth},
+        services::jwt::jwt_verifier::{JwtConfig, JwtKeySource, JwtVerifier},
+    };
+
+    #[tokio::test]
+    async fn admin_fails_closed_with_auth_off() {
+        let mock_app = gen_mock_app().await;
+        let admin = mock_app.issue_admin_key().await;
+
+        let get = |uri: &str, key: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(key) = key {
+                request = request.header("Temp-Authorization", key);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        for key in [None, Some("made-up")] {
+            let response = mock_app
+                .app
+                .clone()
+                .oneshot(get("/transit-cache-stats", key))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+            // while reading stays open
+            let response = mock_app
+                .app
+                .clone()
+                .oneshot(get("/audio", key))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
         }
+
+        let response = mock_app
+            .app
+            .oneshot(get("/transit-cache-stats", Some(&admin)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn accepts_bearer_tokens() {
+        let jwt = JwtVerifier::new(JwtConfig {
+            key: JwtKeySource::Hs256Secret("shared".to_string()),
+            audience: "overwatch".to_string(),
+            issuer: None,
+            admin_subjects: Vec::new(),
+        })
+        .unwrap();
+        let app = gen_mock_app_with_auth(None, Some(jwt)).await.app;
+
+        let token = encode(
+            &Header::default(),
+            &serde_json::json!({
+                "sub": "rider-1",
+                "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+                "aud": "overwatch"
+            }),
+            &EncodingKey::from_secret(b"shared"),
+        )
+        .unwrap();
+
+        let get = |uri: &str, token: Option<&str>| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(token) = token {
+                request = request.header("Authorization", format!("Bearer {}", token));
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        // configuring JWTs turns auth on
+        let response = app
+            .clone()
+            .oneshot(get("/me/favorites", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .clone()
+            .oneshot(get("/me/favorites", Some(&token)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(get("/me/favorites", Some("not.a.jwt")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // tokens without a scope claim cannot administer
+        let response = app.oneshot(get("/admin/keys", Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 }
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::{
+    app_error::{header_seconds, AppError},
+    client_identity::ClientIdentity,
+    rate_limiter::{RateLimit, RateLimitOutcome, RateLimiter},
+};
+
+#[derive(Clone)]
+pub struct RateLimitConfig {
+    /// Shared by every route without a limit of its own.
+    pub default: RateLimit,
+    /// Limits by route path as declared in the router, each with its own bucket.
+    pub routes: HashMap<String, RateLimit>,
+    /// Takes anonymous clients' IPs from the last `X-Forwarded-For` entry, the one appended by
+    /// the proxy in front. Clients can send any of the others.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            default: RateLimit {
+                burst: 60,
+                per_minute: 120,
+                daily_quota: Some(20_000),
+            },
+            // every call is billed by Google Places
+            routes: HashMap::from([(
+                "/location-search-autocomplete".to_string(),
+                RateLimit {
+                    burst: 5,
+                    per_minute: 10,
+                    daily_quota: Some(500),
+                },
+            )]),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimits {
+    config: Arc<RateLimitConfig>,
+    limiter: RateLimiter,
+}
+
+impl RateLimits {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimits {
+            config: Arc::new(config),
+            limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Clients with a credential of their own are limited by it, everyone else by IP, since
+    /// shared keys are held by many clients and anonymous callers can send any key.
+    fn client_key(&self, request: &Request) -> String {
+        if let Some(identity) = request.extensions().get::<ClientIdentity>() {
+            if identity.is_per_client() {
+                return identity.0.clone();
+            }
+        }
+
+        let forwarded = self
+            .config
+            .trust_forwarded_for
+            .then(|| forwarded_for(request.headers()))
+            .flatten();
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+
+        format!("ip:{}", forwarded.or(peer).unwrap_or("unknown".to_string()))
+    }
+}
+
+fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.rsplit(',').next())
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty())
+}
+
+/// Takes a token for the client and route before the request is handled, so it must run after
+/// `auth_middleware` has identified the client.
+pub async fn rate_limit_middleware(
+    State(limits): State<RateLimits>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| limits.config.routes.get_key_value(path.as_str()));
+    let (bucket, limit) = match route {
+        Some((path, limit)) => (path.as_str(), *limit),
+        None => ("default", limits.config.default),
+    };
+    let key = format!("{}|{}", limits.client_key(&request), bucket);
+
+    let (mut response, remaining, reset_after) = match limits.limiter.check(&key, limit) {
+        RateLimitOutcome::Allowed {
+            remaining,
+            reset_after,
+            quota_remaining,
+        } => {
+            let mut response = next.run(request).await;
+            if let Some(quota_remaining) = quota_remaining {
+                response.headers_mut().insert(
+                    "X-Daily-Quota-Remaining",
+                    HeaderValue::from(quota_remaining),
+                );
+            }
+
+            (response, remaining, reset_after)
+        }
+        RateLimitOutcome::Limited { retry_after } => {
+            let mut response =
+                AppError::new(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests").into_response();
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, header_seconds(retry_after));
+
+            (response, 0, retry_after)
+        }
+        RateLimitOutcome::QuotaExceeded { retry_after } => {
+            let mut response = AppError::new(StatusCode::TOO_MANY_REQUESTS, "Daily quota exceeded")
+                .into_response();
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, header_seconds(retry_after));
+            response
+                .headers_mut()
+                .insert("X-Daily-Quota-Remaining", HeaderValue::from(0));
+
+            (response, 0, retry_after)
+        }
+    };
+
+    let headers = response.headers_mut();
+    headers.insert("RateLimit-Limit", HeaderValue::from(limit.burst));
+    headers.insert("RateLimit-Remaining", HeaderValue::from(remaining));
+    headers.insert("RateLimit-Reset", header_seconds(reset_after));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+        Extension, Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_clients_per_route() {
+        let limits = RateLimits::new(RateLimitConfig {
+            default: RateLimit {
+                burst: 2,
+                per_minute: 60,
+                daily_quota: None,
+            },
+            routes: HashMap::from([(
+                "/costly".to_string(),
+                RateLimit {
+                    burst: 1,
+                    per_minute: 1,
+                    daily_quota: Some(1),
+                },
+            )]),
+            trust_forwarded_for: true,
+        });
+        let app = Router::new()
+            .route("/cheap", get(|| async { "ok" }))
+            .route("/costly", get(|| async { "ok" }))
+            .route_layer(middleware::from_fn_with_state(
+                limits,
+                rate_limit_middleware,
+            ))
+            .layer(Extension(ClientIdentity::anonymous()));
+
+        // a fresh spoofed entry on every request doesn't make a new client
+        let spoofed = std::sync::atomic::AtomicUsize::new(0);
+        let get = |uri: &str, ip: &str| {
+            let n = spoofed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Request::builder()
+                .uri(uri)
+                .header("X-Forwarded-For", format!("10.0.0.{}, {}", n, ip))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(get("/costly", "1.1.1.1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["RateLimit-Limit"], "1");
+        assert_eq!(response.headers()["RateLimit-Remaining"], "0");
+        assert_eq!(response.headers()["X-Daily-Quota-Remaining"], "0");
+
+        let response = app
+            .clone()
+            .oneshot(get("/costly", "1.1.1.1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(RETRY_AFTER));
+
+        // the costly route has a bucket of its own, and other clients are unaffected
+        for _ in 0..2 {
+            let response = app.clone().oneshot(get("/cheap", "1.1.1.1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let response = app.clone().oneshot(get("/cheap", "1.1.1.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+
+        let response = app.oneshot(get("/costly", "2.2.2.2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
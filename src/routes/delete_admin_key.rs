@@ -0,0 +1,25 @@
+use crate::{
+    services::api_keys::api_key_store::ApiKeyError, types::app_state::AppState,
+    utils::app_error::AppError,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::error;
+
+/// Revokes the key. It stays listed so that who had access remains on record.
+pub async fn delete_admin_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    state.api_keys.revoke(&id).await.map_err(|e| match e {
+        ApiKeyError::NotFound => AppError::new(StatusCode::NOT_FOUND, "Key not found"),
+        _ => {
+            error!("Failed to revoke API key: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        }
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
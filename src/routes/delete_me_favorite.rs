@@ -0,0 +1,30 @@
+use crate::{
+    services::user_data::user_data_store::UserDataError,
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use tracing::error;
+
+pub async fn delete_me_favorite(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AppError> {
+    state
+        .user_data
+        .delete_favorite(&client_id, &name)
+        .await
+        .map_err(|e| match e {
+            UserDataError::NotFound => AppError::new(StatusCode::NOT_FOUND, "Favorite not found"),
+            _ => {
+                error!("Failed to delete favorite: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,66 @@
+use crate::{
+    services::api_keys::api_key_store::{ApiKey, Scope},
+    types::app_state::AppState,
+    utils::app_error::AppError,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub label: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub active: bool,
+}
+
+impl From<&ApiKey> for ApiKeyResponse {
+    fn from(k: &ApiKey) -> Self {
+        ApiKeyResponse {
+            id: k.id.clone(),
+            label: k.label.clone(),
+            scopes: k.scopes.clone(),
+            created_at: k.created_at.to_rfc3339(),
+            expires_at: k.expires_at.map(|t| t.to_rfc3339()),
+            revoked_at: k.revoked_at.map(|t| t.to_rfc3339()),
+            active: k.is_active(Utc::now()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAdminKeysResponseData {
+    pub keys: Vec<ApiKeyResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAdminKeysResponse {
+    pub data: GetAdminKeysResponseData,
+}
+
+pub async fn get_admin_keys(State(state): State<AppState>) -> Result<Response, AppError> {
+    let keys = state.api_keys.list().await.map_err(|e| {
+        error!("Failed to list API keys: {}", e);
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetAdminKeysResponse {
+            data: GetAdminKeysResponseData {
+                keys: keys.iter().map(ApiKeyResponse::from).collect(),
+            },
+        }),
+    )
+        .into_response())
+}
@@ -1,22 +1,171 @@
-use crate::utils::app_error::AppError;
+use std::{io::ErrorKind, time::SystemTime};
+
+use crate::{
+    services::announcements::wav::WavClip,
+    utils::{
+        app_error::AppError,
+        audio_format::{AcceptAudio, AudioFormat},
+        ranged_response::RangedResponse,
+    },
+};
 use axum::{
-    body::Body,
-    response::{AppendHeaders, IntoResponse, Response},
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    response::Response,
 };
-use tokio::fs::File;
+use tokio::sync::OnceCell;
+use tracing::error;
+
+const CLIP_PATH: &str = "src/media/two_minutes.wav";
+
+struct EncodedClip {
+    bytes: Bytes,
+    last_modified: Option<SystemTime>,
+}
+
+/// The clip is read and encoded once per format, the first time it is asked for.
+static WAV: OnceCell<EncodedClip> = OnceCell::const_new();
+static FLAC: OnceCell<EncodedClip> = OnceCell::const_new();
+
+pub async fn get_audio(
+    AcceptAudio(format): AcceptAudio,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let cell = match format {
+        AudioFormat::Wav => &WAV,
+        AudioFormat::Flac => &FLAC,
+    };
+    let clip = cell.get_or_try_init(|| encode_clip(format)).await?;
+
+    Ok(RangedResponse {
+        bytes: clip.bytes.clone(),
+        content_type: format.content_type(),
+        filename: format!("audio.{}", format.extension()),
+        last_modified: clip.last_modified,
+    }
+    .respond(&headers))
+}
+
+async fn encode_clip(format: AudioFormat) -> Result<EncodedClip, AppError> {
+    let (bytes, metadata) = match tokio::fs::read(CLIP_PATH).await {
+        Ok(bytes) => (bytes, tokio::fs::metadata(CLIP_PATH).await.ok()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(AppError::new(StatusCode::NOT_FOUND, "Audio clip not found"))
+        }
+        Err(e) => {
+            error!("Failed to read {}: {}", CLIP_PATH, e);
+            return Err(AppError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error",
+            ));
+        }
+    };
+
+    // FLAC encoding is CPU bound
+    let encoded = tokio::task::spawn_blocking(move || {
+        WavClip::parse(Bytes::from(bytes)).and_then(|clip| clip.encode(format))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|encoded| encoded.map_err(|e| e.to_string()))
+    .map_err(|e| {
+        error!("Failed to encode {}: {}", CLIP_PATH, e);
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+    })?;
+
+    Ok(EncodedClip {
+        bytes: encoded,
+        last_modified: metadata.and_then(|m| m.modified().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::Request,
+    };
+    use tower::ServiceExt;
+
+    use crate::app::gen_mock_app;
+
+    use super::*;
+
+    async fn get(headers: &[(&str, &str)], uri: &str) -> Response {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        gen_mock_app()
+            .await
+            .app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_ranges() {
+        let response = get(&[("range", "bytes=0-3")], "/audio").await;
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-type"], "audio/wav");
+        assert_eq!(response.headers()["accept-ranges"], "bytes");
+        assert!(response.headers()["content-range"]
+            .to_str()
+            .unwrap()
+            .starts_with("bytes 0-3/"));
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"RIFF");
+
+        let response = get(&[("range", "bytes=99999999-")], "/audio").await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[tokio::test]
+    async fn revalidates() {
+        let response = get(&[], "/audio").await;
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+        let last_modified = response.headers()["last-modified"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = get(&[("if-none-match", &etag)], "/audio").await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = get(&[("if-modified-since", &last_modified)], "/audio").await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // a stale If-Range sends the whole clip rather than the range
+        let response = get(
+            &[("range", "bytes=0-3"), ("if-range", "\"stale\"")],
+            "/audio",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn negotiates_format() {
+        let response = get(&[("accept", "audio/flac")], "/audio").await;
+        assert_eq!(response.headers()["content-type"], "audio/flac");
 
-pub async fn get_audio() -> Result<Response, AppError> {
-    let path = "src/media/two_minutes.wav";
-    let audio = File::open(path).await.unwrap();
+        let wav = get(&[], "/audio?format=wav").await;
+        let flac = get(&[("accept", "audio/flac")], "/audio?format=flac").await;
+        assert_eq!(wav.headers()["content-type"], "audio/wav");
 
-    let stream = tokio_util::io::ReaderStream::new(audio);
-    // let body = BodyDataStream(stream);
-    let response_body = Body::from_stream(stream);
+        let wav = to_bytes(wav.into_body(), usize::MAX).await.unwrap();
+        let flac = to_bytes(flac.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&flac[..4], b"fLaC");
+        assert!(flac.len() < wav.len());
 
-    let headers = AppendHeaders([
-        ("content-type", "audio/wav"),
-        ("content-disposition", "inline; filename=\"audio.wav\""),
-    ]);
+        let response = get(&[], "/audio?format=mp3").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    Ok((headers, response_body).into_response())
+        let response = get(&[("accept", "video/mp4")], "/audio").await;
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    }
 }
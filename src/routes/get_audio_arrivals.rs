@@ -0,0 +1,236 @@
+use crate::{
+    routes::get_transit_arrival_times::GetTransitStopPayload,
+    services::transit_service::transit_service::StopInformation,
+    types::app_state::AppState,
+    utils::{
+        app_error::AppError, audio_format::AcceptAudio, cache_control::CacheControl,
+        ranged_response::RangedResponse, validated_query::ValidatedQuery,
+    },
+};
+use axum::{
+    extract::State,
+    http::{header::CACHE_CONTROL, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use tracing::error;
+
+/// Reads out the same arrivals `/transit-arrival-times` returns for these parameters.
+pub async fn get_audio_arrivals(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    AcceptAudio(format): AcceptAudio,
+    headers: HeaderMap,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitStopPayload>,
+) -> Result<Response, AppError> {
+    let announcer = state.announcer.as_ref().ok_or(AppError::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Audio announcements are not available",
+    ))?;
+    let stop_ids = payload.stop_ids.split(",").collect::<Vec<&str>>();
+
+    let stop_count = stop_ids.len();
+    let result = state
+        .transit_service
+        .with_cache_policy(cache_policy)
+        .fetch_multiple_stop_arrivals(stop_ids, payload.limit_per_route.unwrap_or(1))
+        .await;
+
+    if result.errors.len() == stop_count {
+        error!("Failed to fetch stop info for all {} stops", stop_count);
+        return Err(AppError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal Server Error",
+        ));
+    }
+
+    let route_ids = payload.route_ids();
+    let arrivals = result
+        .arrivals
+        .into_iter()
+        .filter(|s| {
+            route_ids
+                .as_ref()
+                .map_or(true, |ids| ids.contains(&s.route_id))
+        })
+        .collect::<Vec<StopInformation>>();
+
+    let bytes = match announcer.announce(&arrivals).await {
+        // FLAC encoding is CPU bound
+        Ok(clip) => tokio::task::spawn_blocking(move || clip.encode(format))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|encoded| encoded.map_err(|e| e.to_string())),
+        Err(e) => Err(e.to_string()),
+    }
+    .map_err(|e| {
+        // clip names stay in the log, they are not the caller's to fix
+        error!("Failed to build arrivals announcement: {}", e);
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+    })?;
+
+    let mut response = RangedResponse {
+        bytes,
+        content_type: format.content_type(),
+        filename: format!("arrivals.{}", format.extension()),
+        last_modified: None,
+    }
+    .respond(&headers);
+
+    // arrivals change by the minute, only ranges of the same announcement are worth reusing
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        services::{
+            announcements::wav::{WavClip, WavFormat},
+            transit_service::types::mta_get_stop_response::{
+                GetStopInfoResponse, MonitoredCall, MonitoredStopVisit, MonitoredVehicleJourney,
+                ServiceDelivery, Siri, StopMonitoringDelivery,
+            },
+        },
+    };
+
+    const FORMAT: WavFormat = WavFormat {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+    };
+
+    /// Records a clip of a single sample so the announcement's order can be read back.
+    fn record(dir: &Path, name: &str, sample: u8) {
+        let path = dir.join(format!("{}.wav", name));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let mut bytes = FORMAT.header(2).to_vec();
+        bytes.extend_from_slice(&[sample, 0]);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[tokio::test]
+    async fn announces_arrivals() {
+        let mut mock_app = gen_mock_app().await;
+
+        let clips = mock_app.audio_clips.path();
+        record(clips, "letters/a", 1);
+        record(clips, "phrases/to", 2);
+        record(clips, "destinations/cobble_hill", 3);
+        record(clips, "phrases/arriving_in", 4);
+        record(clips, "phrases/minutes", 5);
+        for n in 0..=20 {
+            record(clips, &format!("numbers/{}", n), 9);
+        }
+
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::from([MonitoredStopVisit {
+                            MonitoredVehicleJourney: MonitoredVehicleJourney {
+                                MonitoredCall: MonitoredCall {
+                                    ExpectedArrivalTime: Some(
+                                        (Utc::now() + Duration::minutes(4)).to_rfc3339(),
+                                    ),
+                                    ..Default::default()
+                                },
+                                PublishedLineName: "A".to_string(),
+                                DirectionRef: "0".to_string(),
+                                LineRef: "A".to_string(),
+                                DestinationName: Some("Cobble Hill".to_string()),
+                                ..Default::default()
+                            },
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/audio/arrivals?stop_ids=123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "audio/wav");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let wav = WavClip::parse(body).unwrap();
+
+        assert_eq!(wav.format, FORMAT);
+        assert_eq!(&wav.data[..], &[1, 0, 2, 0, 3, 0, 4, 0, 9, 0, 5, 0]);
+    }
+
+    #[tokio::test]
+    async fn missing_clips_are_internal_errors() {
+        let mut mock_app = gen_mock_app().await;
+
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::new(),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/audio/arrivals?stop_ids=123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("no_arrivals"));
+    }
+}
@@ -1,5 +1,7 @@
 use crate::{
-    services::maps_client::maps_service::AutocompleteSearchInput,
+    services::maps_client::{
+        maps_service::AutocompleteSearchInput, types::maps_service_error::MapsServiceError,
+    },
     types::app_state::AppState,
     utils::{app_error::AppError, validated_query::ValidatedQuery},
 };
@@ -56,12 +58,15 @@ pub async fn get_location_search_autocomplete(
             lon,
         })
         .await
-        .map_err(|e| {
-            error!("Failed to fetch location search autocomplete: {}", e);
-            AppError::new(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to fetch location search autocomplete",
-            )
+        .map_err(|e| match e {
+            MapsServiceError::Unavailable(retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch location search autocomplete: {}", e);
+                AppError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to fetch location search autocomplete",
+                )
+            }
         })?;
 
     Ok(Json(GetLocationSearchAutocompleteResponse {
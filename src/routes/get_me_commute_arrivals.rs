@@ -0,0 +1,164 @@
+use crate::{
+    routes::get_transit_arrival_times::{stop_arrivals, GetTransitStopPayload},
+    services::user_data::user_data_store::UserDataError,
+    types::app_state::AppState,
+    utils::{
+        app_error::AppError, cache_control::CacheControl, client_identity::ClientIdentity,
+        validated_query::ValidatedQuery,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+};
+use serde::Deserialize;
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct GetMeCommuteArrivals {
+    /// Overrides the limit saved with the commute.
+    #[validate(range(min = 1, max = 10, message = "Must be between 1 and 10"))]
+    pub limit_per_route: Option<usize>,
+
+    pub group_by_route: Option<bool>,
+}
+
+/// Answers like `/transit-arrival-times` for the stops and routes saved under a commute.
+pub async fn get_me_commute_arrivals(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    ClientIdentity(client_id): ClientIdentity,
+    Path(name): Path<String>,
+    ValidatedQuery(payload): ValidatedQuery<GetMeCommuteArrivals>,
+) -> Result<Response, AppError> {
+    let commute = state
+        .user_data
+        .get_commute(&client_id, &name)
+        .await
+        .map_err(|e| match e {
+            UserDataError::NotFound => AppError::new(StatusCode::NOT_FOUND, "Commute not found"),
+            _ => {
+                error!("Failed to read commute: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    stop_arrivals(
+        &state.transit_service,
+        cache_policy,
+        &GetTransitStopPayload {
+            stop_ids: commute.stop_ids.join(","),
+            route_ids: match commute.route_ids.is_empty() {
+                true => None,
+                false => Some(commute.route_ids.join(",")),
+            },
+            limit_per_route: payload.limit_per_route.or(commute.limit_per_route),
+            group_by_route: payload.group_by_route,
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    use crate::{app::gen_mock_app, routes::get_transit_arrival_times::TransitArrivalsResponse};
+
+    #[tokio::test]
+    async fn serves_saved_commute_arrivals() {
+        let mut mock_app = gen_mock_app().await;
+        let (alice, bob) = (mock_app.issue_key().await, mock_app.issue_key().await);
+
+        let expected = (Utc::now() + Duration::minutes(3)).to_rfc3339();
+        let visit = |route_id: &str| {
+            serde_json::json!({ "MonitoredVehicleJourney": {
+                "LineRef": route_id,
+                "DirectionRef": "1",
+                "PublishedLineName": route_id,
+                "MonitoredCall": { "ExpectedArrivalTime": expected }
+            }})
+        };
+        let body = serde_json::json!({
+            "Siri": { "ServiceDelivery": {
+                "StopMonitoringDelivery": [{
+                    "MonitoredStopVisit": [visit("MTA NYCT_B63"), visit("MTA NYCT_B61")]
+                }]
+            }}
+        });
+
+        let mock = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .match_query(mockito::Matcher::Regex(
+                "MonitoringRef=MTA_308209".to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/me/commutes/Morning")
+                    .header("Temp-Authorization", &alice)
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"stop_ids":["MTA_308209"],"route_ids":["MTA NYCT_B63"]}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/me/commutes/Morning/arrivals")
+                    .header("Temp-Authorization", &alice)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        mock.assert();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.arrivals.len(), 1);
+        assert_eq!(body.data.arrivals[0].route_label, "MTA NYCT_B63");
+
+        // another key has no such commute
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/me/commutes/Morning/arrivals")
+                    .header("Temp-Authorization", &bob)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{
+    services::user_data::user_data_store::CommuteProfile,
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CommuteProfileResponse {
+    pub name: String,
+    pub stop_ids: Vec<String>,
+    pub route_ids: Vec<String>,
+    pub limit_per_route: Option<usize>,
+}
+
+impl From<&CommuteProfile> for CommuteProfileResponse {
+    fn from(c: &CommuteProfile) -> Self {
+        CommuteProfileResponse {
+            name: c.name.clone(),
+            stop_ids: c.stop_ids.clone(),
+            route_ids: c.route_ids.clone(),
+            limit_per_route: c.limit_per_route,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeCommutesResponseData {
+    pub commutes: Vec<CommuteProfileResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeCommutesResponse {
+    pub data: GetMeCommutesResponseData,
+}
+
+pub async fn get_me_commutes(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+) -> Result<Response, AppError> {
+    let commutes = state
+        .user_data
+        .list_commutes(&client_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to list commutes: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetMeCommutesResponse {
+            data: GetMeCommutesResponseData {
+                commutes: commutes.iter().map(CommuteProfileResponse::from).collect(),
+            },
+        }),
+    )
+        .into_response())
+}
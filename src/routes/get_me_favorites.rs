@@ -0,0 +1,64 @@
+use crate::{
+    services::user_data::user_data_store::{Favorite, FavoriteKind},
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FavoriteResponse {
+    pub name: String,
+    pub kind: FavoriteKind,
+    pub id: String,
+}
+
+impl From<&Favorite> for FavoriteResponse {
+    fn from(f: &Favorite) -> Self {
+        FavoriteResponse {
+            name: f.name.clone(),
+            kind: f.kind,
+            id: f.id.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeFavoritesResponseData {
+    pub favorites: Vec<FavoriteResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeFavoritesResponse {
+    pub data: GetMeFavoritesResponseData,
+}
+
+pub async fn get_me_favorites(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+) -> Result<Response, AppError> {
+    let favorites = state
+        .user_data
+        .list_favorites(&client_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to list favorites: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetMeFavoritesResponse {
+            data: GetMeFavoritesResponseData {
+                favorites: favorites.iter().map(FavoriteResponse::from).collect(),
+            },
+        }),
+    )
+        .into_response())
+}
@@ -0,0 +1,71 @@
+use crate::{
+    services::user_data::user_data_store::NotificationRule,
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// A rule as shown back to its owner, without the signing secret.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct NotificationRuleResponse {
+    pub name: String,
+    pub stop_id: String,
+    pub route_id: String,
+    pub minutes_before: i64,
+    pub webhook_url: String,
+    pub expires_at: String,
+}
+
+impl From<&NotificationRule> for NotificationRuleResponse {
+    fn from(r: &NotificationRule) -> Self {
+        NotificationRuleResponse {
+            name: r.name.clone(),
+            stop_id: r.stop_id.clone(),
+            route_id: r.route_id.clone(),
+            minutes_before: r.minutes_before,
+            webhook_url: r.webhook_url.clone(),
+            expires_at: r.expires_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeNotificationsResponseData {
+    pub rules: Vec<NotificationRuleResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMeNotificationsResponse {
+    pub data: GetMeNotificationsResponseData,
+}
+
+pub async fn get_me_notifications(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+) -> Result<Response, AppError> {
+    let rules = state
+        .user_data
+        .list_notification_rules(&client_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to list notification rules: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetMeNotificationsResponse {
+            data: GetMeNotificationsResponseData {
+                rules: rules.iter().map(NotificationRuleResponse::from).collect(),
+            },
+        }),
+    )
+        .into_response())
+}
@@ -0,0 +1,230 @@
+use crate::{
+    services::transit_service::transit_service::{AlertFilter, ServiceAlert, TransitClientError},
+    types::app_state::AppState,
+    utils::{
+        app_error::AppError,
+        cache_control::CacheControl,
+        id_list::{split_ids, validate_id_list},
+        validated_query::ValidatedQuery,
+    },
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct GetTransitAlerts {
+    /// Comma separated, as are `stop_ids`. At least one of the two is required.
+    #[validate(custom(function = "validate_id_list"))]
+    pub route_ids: Option<String>,
+    #[validate(custom(function = "validate_id_list"))]
+    pub stop_ids: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceAlertResponse {
+    pub id: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub severity: Option<String>,
+    pub valid_from: Option<String>,
+    pub valid_until: Option<String>,
+    pub route_ids: Vec<String>,
+    pub stop_ids: Vec<String>,
+}
+
+impl From<&ServiceAlert> for ServiceAlertResponse {
+    fn from(a: &ServiceAlert) -> Self {
+        ServiceAlertResponse {
+            id: a.id.clone(),
+            summary: a.summary.clone(),
+            description: a.description.clone(),
+            severity: a.severity.clone(),
+            valid_from: a.valid_from.clone(),
+            valid_until: a.valid_until.clone(),
+            route_ids: a.route_ids.clone(),
+            stop_ids: a.stop_ids.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitAlertsResponseData {
+    pub alerts: Vec<ServiceAlertResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitAlertsResponse {
+    pub data: GetTransitAlertsResponseData,
+}
+
+pub async fn get_transit_alerts(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitAlerts>,
+) -> Result<Response, AppError> {
+    let filters = split_ids(payload.route_ids.as_deref().unwrap_or_default())
+        .into_iter()
+        .map(AlertFilter::Route)
+        .chain(
+            split_ids(payload.stop_ids.as_deref().unwrap_or_default())
+                .into_iter()
+                .map(AlertFilter::Stop),
+        )
+        .collect::<Vec<AlertFilter>>();
+
+    if filters.is_empty() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "Must provide route_ids or stop_ids",
+        ));
+    }
+
+    let alerts = state
+        .transit_service
+        .with_cache_policy(cache_policy)
+        .fetch_alerts(&filters)
+        .await
+        .map_err(|e| match e {
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch alerts: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(GetTransitAlertsResponse {
+            data: GetTransitAlertsResponseData {
+                alerts: alerts.iter().map(ServiceAlertResponse::from).collect(),
+            },
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use chrono::{Duration, Utc};
+    use tower::ServiceExt;
+
+    use crate::app::gen_mock_app;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_ids_that_do_not_decode() {
+        let response = gen_mock_app()
+            .await
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-alerts?stop_ids=%25FF")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_active_alerts() {
+        let mut mock_app = gen_mock_app().await;
+
+        let started = (Utc::now() - Duration::hours(1)).to_rfc3339();
+        let ended = (Utc::now() - Duration::minutes(5)).to_rfc3339();
+
+        // trimmed from a real bus time response
+        let body = serde_json::json!({
+            "Siri": { "ServiceDelivery": {
+                "VehicleMonitoringDelivery": [{ "VehicleActivity": [] }],
+                "SituationExchangeDelivery": [{ "Situations": { "PtSituationElement": [
+                    {
+                        "SituationNumber": "MTA NYCT_lmm:planned_work:1",
+                        "Summary": "B63 detoured at 5 Av/Union St",
+                        "Description": "Buses skip 5 Av/Union St, use 4 Av/Union St instead",
+                        "Severity": "severe",
+                        "PublicationWindow": { "StartTime": started },
+                        "Affects": {
+                            "VehicleJourneys": { "AffectedVehicleJourney": [
+                                { "LineRef": "MTA NYCT_B63", "DirectionRef": "0" },
+                                { "LineRef": "MTA NYCT_B63", "DirectionRef": "1" }
+                            ]},
+                            "StopPoints": { "AffectedStopPoint": [
+                                { "StopPointRef": "MTA_308209" }
+                            ]}
+                        }
+                    },
+                    {
+                        "SituationNumber": "MTA NYCT_lmm:planned_work:2",
+                        "Summary": "Over",
+                        "PublicationWindow": { "StartTime": started, "EndTime": ended }
+                    }
+                ]}}]
+            }}
+        });
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/vehicle-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .match_query(mockito::Matcher::Regex(
+                "LineRef=MTA%20NYCT_B63".to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-alerts?route_ids=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitAlertsResponse = serde_json::from_slice(&body).unwrap();
+
+        // the expired alert is left out
+        assert_eq!(body.data.alerts.len(), 1);
+        let alert = &body.data.alerts[0];
+        assert_eq!(alert.id, "MTA NYCT_lmm:planned_work:1");
+        assert_eq!(alert.severity.as_deref(), Some("severe"));
+        assert_eq!(alert.valid_from.as_deref(), Some(started.as_str()));
+        assert_eq!(alert.route_ids, ["MTA NYCT_B63"]);
+        assert_eq!(alert.stop_ids, ["MTA_308209"]);
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-alerts")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
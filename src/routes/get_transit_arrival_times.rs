@@ -1,34 +1,110 @@
 use crate::{
+    routes::get_transit_alerts::ServiceAlertResponse,
+    services::transit_service::transit_service::{
+        AlertFilter, MultipleStopArrivals, StopInformation, TransitService,
+    },
     types::app_state::AppState,
-    utils::{app_error::AppError, validated_query::ValidatedQuery},
+    utils::{
+        app_error::{header_seconds, AppError},
+        cache_control::{CacheControl, CachePolicy},
+        id_list::{split_ids, validate_id_list},
+        validated_query::ValidatedQuery,
+    },
 };
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use chrono::Utc;
+use futures::future::join;
 use serde::{Deserialize, Serialize};
-use tracing::error;
-use urlencoding::decode;
-use validator::{Validate, ValidateLength};
+use tracing::{error, warn};
+use validator::Validate;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct StopResponseDataArrival {
     pub expected_arrival_time: String,
     pub minutes_until_arrival: i64,
     pub stop_id: String,
     pub route_label: String,
+    pub direction_id: String,
+    pub aimed_arrival_time: Option<String>,
+    pub destination_name: Option<String>,
+    pub vehicle_id: Option<String>,
+    pub progress_status: Option<String>,
+    pub occupancy: Option<String>,
+    pub passenger_count: Option<u32>,
+    pub passenger_capacity: Option<u32>,
+    pub presentable_distance: Option<String>,
+    pub stops_away: Option<u32>,
+    pub distance_from_stop_meters: Option<f64>,
+    /// Served from the last good response after fetching a fresh one failed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// How old a stale arrival's data is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_age_seconds: Option<i64>,
+}
+
+impl From<&StopInformation> for StopResponseDataArrival {
+    fn from(s: &StopInformation) -> Self {
+        StopResponseDataArrival {
+            stop_id: s.stop_id.clone(),
+            expected_arrival_time: s.expected_arrival_time.clone(),
+            minutes_until_arrival: s.minutes_until_arrival,
+            route_label: s.route_label.clone(),
+            direction_id: s.direction_id.clone(),
+            aimed_arrival_time: s.aimed_arrival_time.clone(),
+            destination_name: s.destination_name.clone(),
+            vehicle_id: s.vehicle_id.clone(),
+            progress_status: s.progress_status.clone(),
+            occupancy: s.occupancy.clone(),
+            passenger_count: s.passenger_count,
+            passenger_capacity: s.passenger_capacity,
+            presentable_distance: s.presentable_distance.clone(),
+            stops_away: s.stops_away,
+            distance_from_stop_meters: s.distance_from_stop_meters,
+            stale: s.stale_since.is_some(),
+            data_age_seconds: s
+                .stale_since
+                .map(|since| (Utc::now() - since).num_seconds()),
+        }
+    }
+}
+
+/// The arrivals of one route in one direction at one stop, soonest first.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct TransitArrivalsGroup {
+    pub stop_id: String,
+    pub route_id: String,
+    pub route_label: String,
+    pub direction_id: String,
+    pub arrivals: Vec<StopResponseDataArrival>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TransitArrivalsData {
     pub arrivals: Vec<StopResponseDataArrival>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<TransitArrivalsGroup>>,
+    /// Active alerts affecting the requested stops or their routes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<ServiceAlertResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransitArrivalsResponseError {
+    pub stop_id: String,
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TransitArrivalsResponse {
     pub data: TransitArrivalsData,
+    #[serde(default)]
+    pub errors: Vec<TransitArrivalsResponseError>,
 }
 
 #[derive(Validate, Deserialize)]
@@ -36,57 +112,178 @@ pub struct GetTransitStopPayload {
     #[validate(length(min = 1, message = "Must be at least 1 character"))]
     pub stop_ids: String,
 
+    #[validate(custom(function = "validate_id_list"))]
     pub route_ids: Option<String>,
+
+    /// How many upcoming arrivals to return for each route and direction at a stop.
+    #[validate(range(min = 1, max = 10, message = "Must be between 1 and 10"))]
+    pub limit_per_route: Option<usize>,
+
+    /// Also return the arrivals grouped by stop, route and direction.
+    pub group_by_route: Option<bool>,
+}
+
+impl GetTransitStopPayload {
+    pub fn route_ids(&self) -> Option<Vec<String>> {
+        self.route_ids.as_deref().map(split_ids)
+    }
 }
 
 pub async fn get_transit_arrival_times(
     State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
     ValidatedQuery(payload): ValidatedQuery<GetTransitStopPayload>,
+) -> Result<Response, AppError> {
+    stop_arrivals(&state.transit_service, cache_policy, &payload).await
+}
+
+/// Fetches the arrivals of every stop in `payload` along with their alerts.
+pub async fn stop_arrivals(
+    transit_service: &TransitService,
+    cache_policy: CachePolicy,
+    payload: &GetTransitStopPayload,
 ) -> Result<Response, AppError> {
     let stop_ids = payload.stop_ids.split(",").collect::<Vec<&str>>();
-    let route_ids = match payload.route_ids.as_deref() {
-        Some(s) => Some(
-            s.split(",")
-                .filter(|s| s.length() > Some(0))
-                .map(|s| decode(s.trim()).unwrap().to_string())
-                .collect::<Vec<String>>(),
-        ),
-        None => None,
+    let filters = stop_ids
+        .iter()
+        .map(|id| AlertFilter::Stop(id.to_string()))
+        .collect::<Vec<AlertFilter>>();
+
+    // alerts are read from the same stop monitoring responses, so concurrent lookups share the
+    // upstream call
+    let arrivals_service = transit_service.with_cache_policy(cache_policy);
+    let alerts_service = transit_service.with_cache_policy(cache_policy.alongside());
+    let (result, alerts) = join(
+        arrivals_service
+            .fetch_multiple_stop_arrivals(stop_ids.clone(), payload.limit_per_route.unwrap_or(1)),
+        alerts_service.fetch_alerts(&filters),
+    )
+    .await;
+
+    let stop_count = stop_ids.len();
+
+    // the errors say why each stop failed, so they are returned in the usual shape
+    if result.errors.len() == stop_count && result.errors.iter().all(|e| e.not_found) {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(arrivals_response(&result, payload)),
+        )
+            .into_response());
+    }
+
+    if result.errors.len() == stop_count {
+        error!("Failed to fetch stop info for all {} stops", stop_count);
+        let body = Json(arrivals_response(&result, payload));
+
+        return Ok(match result.unavailable_for() {
+            Some(retry_after) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, header_seconds(retry_after))],
+                body,
+            )
+                .into_response(),
+            None => (StatusCode::BAD_GATEWAY, body).into_response(),
+        });
+    }
+
+    // some stops failing is still a useful response, so signal the partial success instead
+    let status = match result.errors.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::MULTI_STATUS,
     };
 
-    match state
-        .transit_service
-        .fetch_multiple_stop_arrivals(stop_ids)
-        .await
-        .map_err(|e| {
-            error!("Failed to fetch stop info: {}", e);
-            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
-        }) {
-        Ok(v) => {
-            let response_json = TransitArrivalsResponse {
-                data: TransitArrivalsData {
-                    arrivals: v
-                        .iter()
-                        .filter(|s| {
-                            route_ids.is_none()
-                                || route_ids
-                                    .as_ref()
-                                    .is_some_and(|ids| ids.contains(&s.route_id))
-                        })
-                        .map(|s| StopResponseDataArrival {
-                            stop_id: s.stop_id.clone(),
-                            expected_arrival_time: s.expected_arrival_time.clone(),
-                            minutes_until_arrival: s.minutes_until_arrival,
-                            route_label: s.route_label.clone(),
-                        })
-                        .collect::<Vec<StopResponseDataArrival>>(),
-                },
-            };
+    let mut res = arrivals_response(&result, payload);
+
+    // arrivals are still worth returning when alerts fail
+    let route_ids = payload.route_ids();
+
+    match alerts {
+        Ok(alerts) => {
+            res.data.alerts = alerts
+                .iter()
+                .filter(|a| {
+                    a.route_ids.is_empty()
+                        || route_ids
+                            .as_ref()
+                            .map_or(true, |ids| a.route_ids.iter().any(|id| ids.contains(id)))
+                })
+                .map(ServiceAlertResponse::from)
+                .collect();
+        }
+        Err(e) => warn!("Failed to fetch alerts for arrivals: {}", e),
+    }
+
+    Ok((status, Json(res)).into_response())
+}
+
+/// Filters and optionally groups the arrivals of every requested stop as `payload` asks.
+pub fn arrivals_response(
+    result: &MultipleStopArrivals,
+    payload: &GetTransitStopPayload,
+) -> TransitArrivalsResponse {
+    let route_ids = payload.route_ids();
+
+    let arrivals = result
+        .arrivals
+        .iter()
+        .filter(|s| {
+            route_ids.is_none()
+                || route_ids
+                    .as_ref()
+                    .is_some_and(|ids| ids.contains(&s.route_id))
+        })
+        .collect::<Vec<&StopInformation>>();
+
+    let groups = match payload.group_by_route {
+        Some(true) => Some(group_arrivals(&arrivals)),
+        _ => None,
+    };
+
+    TransitArrivalsResponse {
+        data: TransitArrivalsData {
+            arrivals: arrivals
+                .into_iter()
+                .map(StopResponseDataArrival::from)
+                .collect(),
+            groups,
+            alerts: Vec::new(),
+        },
+        errors: result
+            .errors
+            .iter()
+            .map(|e| TransitArrivalsResponseError {
+                stop_id: e.stop_id.clone(),
+                reason: e.reason.clone(),
+            })
+            .collect(),
+    }
+}
 
-            Ok((StatusCode::OK, Json(response_json)).into_response())
+/// Groups arrivals that are already sorted soonest first, ordering the groups by their first
+/// arrival.
+pub fn group_arrivals(arrivals: &[&StopInformation]) -> Vec<TransitArrivalsGroup> {
+    let mut groups: Vec<TransitArrivalsGroup> = Vec::new();
+
+    for arrival in arrivals {
+        let existing = groups.iter_mut().find(|g| {
+            g.stop_id == arrival.stop_id
+                && g.route_id == arrival.route_id
+                && g.direction_id == arrival.direction_id
+        });
+
+        match existing {
+            Some(group) => group.arrivals.push(StopResponseDataArrival::from(*arrival)),
+            None => groups.push(TransitArrivalsGroup {
+                stop_id: arrival.stop_id.clone(),
+                route_id: arrival.route_id.clone(),
+                route_label: arrival.route_label.clone(),
+                direction_id: arrival.direction_id.clone(),
+                arrivals: vec![StopResponseDataArrival::from(*arrival)],
+            }),
         }
-        Err(e) => Err(e),
     }
+
+    groups
 }
 
 #[cfg(test)]
@@ -101,8 +298,8 @@ mod tests {
     use crate::{
         app::gen_mock_app,
         services::transit_service::types::mta_get_stop_response::{
-            GetStopInfoResponse, MonitoredCall, MonitoredStopVisit, MonitoredVehicleJourney,
-            ServiceDelivery, Siri, StopMonitoringDelivery,
+            ErrorCondition, GetStopInfoResponse, MonitoredCall, MonitoredStopVisit,
+            MonitoredVehicleJourney, ServiceDelivery, Siri, StopMonitoringDelivery,
         },
     };
 
@@ -116,17 +313,21 @@ mod tests {
         let mock_response = GetStopInfoResponse {
             Siri: Siri {
                 ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
                     StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
                         MonitoredStopVisit: Vec::from([MonitoredStopVisit {
                             MonitoredVehicleJourney: MonitoredVehicleJourney {
                                 MonitoredCall: MonitoredCall {
                                     ExpectedArrivalTime: Some(future_date.to_rfc3339()),
+                                    ..Default::default()
                                 },
                                 PublishedLineName: "A".to_string(),
                                 DirectionRef: "A".to_string(),
                                 LineRef: "A".to_string(),
+                                ..Default::default()
                             },
                         }]),
+                        ErrorCondition: None,
                     }]),
                 },
             },
@@ -146,17 +347,21 @@ mod tests {
         let mock_response2 = GetStopInfoResponse {
             Siri: Siri {
                 ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
                     StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
                         MonitoredStopVisit: Vec::from([MonitoredStopVisit {
                             MonitoredVehicleJourney: MonitoredVehicleJourney {
                                 MonitoredCall: MonitoredCall {
                                     ExpectedArrivalTime: Some(future_date2.to_rfc3339()),
+                                    ..Default::default()
                                 },
                                 PublishedLineName: "B".to_string(),
                                 DirectionRef: "B".to_string(),
                                 LineRef: "B".to_string(),
+                                ..Default::default()
                             },
                         }]),
+                        ErrorCondition: None,
                     }]),
                 },
             },
@@ -200,8 +405,8 @@ mod tests {
             body.data.arrivals[0].expected_arrival_time,
             future_date.to_rfc3339()
         );
-        assert_eq!(body.data.arrivals[0].minutes_until_arrival < 4, true);
-        assert_eq!(body.data.arrivals[0].minutes_until_arrival > 0, true);
+        assert!(body.data.arrivals[0].minutes_until_arrival < 4);
+        assert!(body.data.arrivals[0].minutes_until_arrival > 0);
 
         // test 1st element is abc
         assert_eq!(body.data.arrivals[1].stop_id, "abc");
@@ -210,8 +415,155 @@ mod tests {
             body.data.arrivals[1].expected_arrival_time,
             future_date2.to_rfc3339()
         );
-        assert_eq!(body.data.arrivals[1].minutes_until_arrival < 12, true);
-        assert_eq!(body.data.arrivals[1].minutes_until_arrival > 8, true);
+        assert!(body.data.arrivals[1].minutes_until_arrival < 12);
+        assert!(body.data.arrivals[1].minutes_until_arrival > 8);
+    }
+
+    #[tokio::test]
+    async fn vehicle_details() {
+        let mut mock_app = gen_mock_app().await;
+
+        let expected = (Utc::now() + Duration::minutes(3)).to_rfc3339();
+        let aimed = (Utc::now() + Duration::minutes(1)).to_rfc3339();
+
+        // trimmed from a real bus time response
+        let body = serde_json::json!({
+            "Siri": { "ServiceDelivery": { "StopMonitoringDelivery": [{
+                "MonitoredStopVisit": [{ "MonitoredVehicleJourney": {
+                    "LineRef": "MTA NYCT_B63",
+                    "DirectionRef": "1",
+                    "PublishedLineName": "B63",
+                    "DestinationName": "BAY RIDGE SHORE RD",
+                    "VehicleRef": "MTA NYCT_7560",
+                    "ProgressStatus": "prevTrip",
+                    "Occupancy": "standingAvailable",
+                    "MonitoredCall": {
+                        "AimedArrivalTime": aimed,
+                        "ExpectedArrivalTime": expected,
+                        "Extensions": {
+                            "Distances": {
+                                "PresentableDistance": "1 stop away",
+                                "DistanceFromCall": 412.5,
+                                "StopsFromCall": 1,
+                                "CallDistanceAlongRoute": 3108.22
+                            },
+                            "Capacities": {
+                                "EstimatedPassengerCount": 38,
+                                "EstimatedPassengerCapacity": 55
+                            }
+                        }
+                    }
+                }}]
+            }]}}
+        });
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=MTA_308209")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+        let arrival = &body.data.arrivals[0];
+
+        assert_eq!(arrival.aimed_arrival_time.as_deref(), Some(aimed.as_str()));
+        assert_eq!(
+            arrival.destination_name.as_deref(),
+            Some("BAY RIDGE SHORE RD")
+        );
+        assert_eq!(arrival.vehicle_id.as_deref(), Some("MTA NYCT_7560"));
+        assert_eq!(arrival.progress_status.as_deref(), Some("prevTrip"));
+        assert_eq!(arrival.occupancy.as_deref(), Some("standingAvailable"));
+        assert_eq!(arrival.passenger_count, Some(38));
+        assert_eq!(arrival.passenger_capacity, Some(55));
+        assert_eq!(arrival.presentable_distance.as_deref(), Some("1 stop away"));
+        assert_eq!(arrival.stops_away, Some(1));
+        assert_eq!(arrival.distance_from_stop_meters, Some(412.5));
+    }
+
+    #[tokio::test]
+    async fn attaches_alerts() {
+        let mut mock_app = gen_mock_app().await;
+
+        let expected = (Utc::now() + Duration::minutes(3)).to_rfc3339();
+        let visit = |route_id: &str| {
+            serde_json::json!({ "MonitoredVehicleJourney": {
+                "LineRef": route_id,
+                "DirectionRef": "1",
+                "PublishedLineName": route_id,
+                "MonitoredCall": { "ExpectedArrivalTime": expected }
+            }})
+        };
+        let situation = |id: &str, route_id: &str| {
+            serde_json::json!({
+                "SituationNumber": id,
+                "Summary": format!("{} detoured", route_id),
+                "Affects": { "VehicleJourneys": { "AffectedVehicleJourney": [
+                    { "LineRef": route_id }
+                ]}}
+            })
+        };
+        let body = serde_json::json!({
+            "Siri": { "ServiceDelivery": {
+                "StopMonitoringDelivery": [{
+                    "MonitoredStopVisit": [visit("MTA NYCT_B63"), visit("MTA NYCT_B61")]
+                }],
+                "SituationExchangeDelivery": [{ "Situations": { "PtSituationElement": [
+                    situation("b63-detour", "MTA NYCT_B63"),
+                    situation("b61-detour", "MTA NYCT_B61")
+                ]}}]
+            }}
+        });
+
+        // the alerts are read from the same cached response as the arrivals
+        let mock = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=MTA_308209&route_ids=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        mock.assert();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.arrivals.len(), 1);
+        assert_eq!(body.data.alerts.len(), 1);
+        assert_eq!(body.data.alerts[0].id, "b63-detour");
+        assert_eq!(body.data.alerts[0].summary, "MTA NYCT_B63 detoured");
     }
 
     #[tokio::test]
@@ -222,29 +574,35 @@ mod tests {
         let mock_response = GetStopInfoResponse {
             Siri: Siri {
                 ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
                     StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
                         MonitoredStopVisit: Vec::from([
                             MonitoredStopVisit {
                                 MonitoredVehicleJourney: MonitoredVehicleJourney {
                                     MonitoredCall: MonitoredCall {
                                         ExpectedArrivalTime: Some(future_date.to_rfc3339()),
+                                        ..Default::default()
                                     },
                                     PublishedLineName: "A".to_string(),
                                     DirectionRef: "A".to_string(),
                                     LineRef: "A".to_string(),
+                                    ..Default::default()
                                 },
                             },
                             MonitoredStopVisit {
                                 MonitoredVehicleJourney: MonitoredVehicleJourney {
                                     MonitoredCall: MonitoredCall {
                                         ExpectedArrivalTime: Some(future_date.to_rfc3339()),
+                                        ..Default::default()
                                     },
                                     PublishedLineName: "A".to_string(),
                                     DirectionRef: "A".to_string(),
                                     LineRef: "A".to_string(),
+                                    ..Default::default()
                                 },
                             },
                         ]),
+                        ErrorCondition: None,
                     }]),
                 },
             },
@@ -280,4 +638,344 @@ mod tests {
 
         assert_eq!(body.data.arrivals.len(), 1);
     }
+
+    #[tokio::test]
+    async fn limit_per_route_grouped() {
+        let mut mock_app = gen_mock_app().await;
+
+        let visit = |minutes: i64, direction: &str| MonitoredStopVisit {
+            MonitoredVehicleJourney: MonitoredVehicleJourney {
+                MonitoredCall: MonitoredCall {
+                    ExpectedArrivalTime: Some(
+                        (Utc::now() + Duration::minutes(minutes)).to_rfc3339(),
+                    ),
+                    ..Default::default()
+                },
+                PublishedLineName: "A".to_string(),
+                DirectionRef: direction.to_string(),
+                LineRef: "A".to_string(),
+                ..Default::default()
+            },
+        };
+
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::from([
+                            visit(2, "0"),
+                            visit(5, "1"),
+                            visit(9, "0"),
+                            visit(15, "0"),
+                        ]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri(
+                        "/transit-arrival-times?stop_ids=123&limit_per_route=2&group_by_route=true",
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.arrivals.len(), 3);
+
+        let groups = body.data.groups.unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].direction_id, "0");
+        assert_eq!(groups[0].arrivals.len(), 2);
+        assert!(groups[0].arrivals[1].minutes_until_arrival > 7);
+        assert_eq!(groups[1].direction_id, "1");
+        assert_eq!(groups[1].arrivals.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn partial_failure() {
+        let mut mock_app = gen_mock_app().await;
+
+        let future_date = Utc::now() + Duration::minutes(2);
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::from([MonitoredStopVisit {
+                            MonitoredVehicleJourney: MonitoredVehicleJourney {
+                                MonitoredCall: MonitoredCall {
+                                    ExpectedArrivalTime: Some(future_date.to_rfc3339()),
+                                    ..Default::default()
+                                },
+                                PublishedLineName: "A".to_string(),
+                                DirectionRef: "A".to_string(),
+                                LineRef: "A".to_string(),
+                                ..Default::default()
+                            },
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        let unknown_stop_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::new(),
+                        ErrorCondition: Some(ErrorCondition {
+                            Description: Some("No such stop: MTA_unknown".to_string()),
+                        }),
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*MonitoringRef=123.*".to_string()))
+            .create_async()
+            .await;
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&unknown_stop_response).unwrap())
+            .match_query(mockito::Matcher::Regex(
+                ".*MonitoringRef=unknown.*".to_string(),
+            ))
+            .create_async()
+            .await;
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_status(500)
+            .match_query(mockito::Matcher::Regex(
+                ".*MonitoringRef=broken.*".to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=123,unknown,broken")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.arrivals.len(), 1);
+        assert_eq!(body.data.arrivals[0].stop_id, "123");
+        assert_eq!(body.errors.len(), 2);
+        assert_eq!(body.errors[0].stop_id, "unknown");
+        assert_eq!(body.errors[0].reason, "Stop not found");
+        assert_eq!(body.errors[1].stop_id, "broken");
+        assert_eq!(body.errors[1].reason, "Failed to fetch arrivals");
+    }
+
+    #[tokio::test]
+    async fn all_stops_failed() {
+        let mut mock_app = gen_mock_app().await;
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_status(500)
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+        assert!(body.data.arrivals.is_empty());
+        assert_eq!(body.errors.len(), 1);
+        assert_eq!(body.errors[0].stop_id, "123");
+        assert_eq!(body.errors[0].reason, "Failed to fetch arrivals");
+    }
+
+    #[tokio::test]
+    async fn all_stops_unknown() {
+        let mut mock_app = gen_mock_app().await;
+
+        let unknown_stop_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::new(),
+                        ErrorCondition: Some(ErrorCondition {
+                            Description: Some("No such stop: MTA_unknown".to_string()),
+                        }),
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&unknown_stop_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=unknown")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.errors.len(), 1);
+        assert_eq!(body.errors[0].reason, "Stop not found");
+    }
+
+    #[tokio::test]
+    async fn rejects_route_ids_that_do_not_decode() {
+        let response = gen_mock_app()
+            .await
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times?stop_ids=123&route_ids=%25FF")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_stale_arrivals_when_upstream_fails() {
+        let mut mock_app = gen_mock_app().await;
+
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::from([MonitoredStopVisit {
+                            MonitoredVehicleJourney: MonitoredVehicleJourney {
+                                MonitoredCall: MonitoredCall {
+                                    ExpectedArrivalTime: Some(
+                                        (Utc::now() + Duration::minutes(5)).to_rfc3339(),
+                                    ),
+                                    ..Default::default()
+                                },
+                                PublishedLineName: "A".to_string(),
+                                DirectionRef: "A".to_string(),
+                                LineRef: "A".to_string(),
+                                ..Default::default()
+                            },
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        let up = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*MonitoringRef=123.*".to_string()))
+            .create_async()
+            .await;
+
+        let get = || {
+            Request::builder()
+                .uri("/transit-arrival-times?stop_ids=123")
+                .header("Cache-Control", "no-cache")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = mock_app.app.clone().oneshot(get()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!body.data.arrivals[0].stale);
+
+        up.remove_async().await;
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_status(500)
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app.app.oneshot(get()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitArrivalsResponse = serde_json::from_slice(&body).unwrap();
+        assert!(body.errors.is_empty());
+        assert_eq!(body.data.arrivals.len(), 1);
+        assert!(body.data.arrivals[0].stale);
+        assert!(body.data.arrivals[0].data_age_seconds.is_some());
+        assert_eq!(body.data.arrivals[0].minutes_until_arrival, 4);
+    }
 }
@@ -0,0 +1,163 @@
+use std::{convert::Infallible, time::Duration};
+
+use crate::{
+    routes::get_transit_arrival_times::{arrivals_response, GetTransitStopPayload},
+    types::app_state::AppState,
+    utils::validated_query::ValidatedQuery,
+};
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{stream, Stream};
+
+/// Streams arrivals as server-sent `arrivals` events, each carrying the same body as
+/// `/transit-arrival-times`. An event is only sent when that body changes.
+pub async fn get_transit_arrival_times_stream(
+    State(state): State<AppState>,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitStopPayload>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stop_ids = payload
+        .stop_ids
+        .split(",")
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+
+    let mut receiver = state
+        .live_poller
+        .subscribe_arrivals(&stop_ids, payload.limit_per_route.unwrap_or(1));
+
+    // the poll may already have a result from another subscriber, so send it straight away
+    receiver.mark_changed();
+
+    // dropping the stream when the client disconnects drops the receiver, which ends the poll
+    // once nobody else is watching the same stops
+    let events = stream::unfold(
+        (receiver, payload, None::<String>),
+        |(mut receiver, payload, last_sent)| async move {
+            loop {
+                receiver.changed().await.ok()?;
+
+                let result = match receiver.borrow_and_update().clone() {
+                    Some(result) => result,
+                    None => continue,
+                };
+
+                let body = serde_json::to_string(&arrivals_response(&result, &payload)).ok()?;
+
+                // route filters can hide a change to the underlying arrivals
+                if last_sent.as_ref() == Some(&body) {
+                    continue;
+                }
+
+                let event = Event::default().event("arrivals").data(&body);
+
+                return Some((Ok(event), (receiver, payload, Some(body))));
+            }
+        },
+    );
+
+    Sse::new(events).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("heartbeat"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use chrono::Utc;
+    use futures::StreamExt;
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        routes::get_transit_arrival_times::TransitArrivalsResponse,
+        services::transit_service::types::mta_get_stop_response::{
+            GetStopInfoResponse, MonitoredCall, MonitoredStopVisit, MonitoredVehicleJourney,
+            ServiceDelivery, Siri, StopMonitoringDelivery,
+        },
+    };
+
+    #[tokio::test]
+    async fn streams_arrivals() {
+        let mut mock_app = gen_mock_app().await;
+
+        let mock_response = GetStopInfoResponse {
+            Siri: Siri {
+                ServiceDelivery: ServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    StopMonitoringDelivery: Vec::from([StopMonitoringDelivery {
+                        MonitoredStopVisit: Vec::from([MonitoredStopVisit {
+                            MonitoredVehicleJourney: MonitoredVehicleJourney {
+                                MonitoredCall: MonitoredCall {
+                                    ExpectedArrivalTime: Some(
+                                        (Utc::now() + chrono::Duration::minutes(5)).to_rfc3339(),
+                                    ),
+                                    ..Default::default()
+                                },
+                                PublishedLineName: "A".to_string(),
+                                DirectionRef: "0".to_string(),
+                                LineRef: "A".to_string(),
+                                ..Default::default()
+                            },
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        // later polls are answered from the cache
+        let mock = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-arrival-times/stream?stop_ids=123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"].to_str().unwrap(),
+            "text/event-stream"
+        );
+
+        let mut body = response.into_body().into_data_stream();
+        let chunk = body.next().await.unwrap().unwrap();
+        let chunk = std::str::from_utf8(&chunk).unwrap();
+
+        assert!(chunk.starts_with("event: arrivals\n"));
+
+        let data = chunk
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap();
+        let data: TransitArrivalsResponse = serde_json::from_str(data).unwrap();
+        assert_eq!(data.data.arrivals.len(), 1);
+        assert_eq!(data.data.arrivals[0].stop_id, "123");
+
+        // unchanged arrivals are not sent again
+        let next = tokio::time::timeout(std::time::Duration::from_millis(200), body.next()).await;
+        assert!(next.is_err());
+
+        mock.assert();
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{types::app_state::AppState, utils::app_error::AppError};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitCacheStatsResponseData {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitCacheStatsResponse {
+    pub data: GetTransitCacheStatsResponseData,
+}
+
+pub async fn get_transit_cache_stats(State(state): State<AppState>) -> Result<Response, AppError> {
+    let stats = state.transit_service.cache_stats();
+
+    Ok((
+        StatusCode::OK,
+        Json(GetTransitCacheStatsResponse {
+            data: GetTransitCacheStatsResponseData {
+                hits: stats.hits,
+                misses: stats.misses,
+                entries: stats.entries,
+            },
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        services::transit_service::types::mta_get_routes_response::{
+            GetRoutesResponse, GetRoutesResponseData, GetRoutesResponseRoute,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn counts_cached_upstream_calls() {
+        let mut mock_app = gen_mock_app().await;
+        let admin = mock_app.issue_admin_key().await;
+
+        let mock_response = GetRoutesResponse {
+            data: GetRoutesResponseData {
+                list: vec![GetRoutesResponseRoute {
+                    id: "1".to_string(),
+                    shortName: "A".to_string(),
+                }],
+            },
+        };
+
+        let mock_server = mock_app
+            .mta_server
+            .mock("GET", "/api/where/routes-for-agency/MTA%20NYCT.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .expect(2)
+            .create_async()
+            .await;
+
+        for cache_control in ["", "", "no-cache"] {
+            let response = mock_app
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/transit-routes?search=A")
+                        .header("cache-control", cache_control)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // the first request misses, the second hits and the third bypasses the cache entirely
+        mock_server.assert();
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-cache-stats")
+                    .header("Temp-Authorization", &admin)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitCacheStatsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.hits, 1);
+        assert_eq!(body.data.misses, 1);
+        assert_eq!(body.data.entries, 1);
+    }
+}
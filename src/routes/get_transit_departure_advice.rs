@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+use crate::{
+    routes::get_transit_arrival_times::TransitArrivalsResponseError,
+    services::{
+        departure_advice::departure_advisor::{DepartureAdviceOptions, DepartureOption},
+        transit_service::transit_service::TransitClientError,
+    },
+    types::{app_state::AppState, lat_long_location::GetStopsAtLocationInput},
+    utils::{
+        app_error::AppError,
+        cache_control::CacheControl,
+        id_list::{split_ids, validate_id_list},
+        validated_query::ValidatedQuery,
+    },
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct GetTransitDepartureAdvicePayload {
+    pub coordinates: Option<String>,
+    pub place_id: Option<String>,
+
+    /// Only consider these routes, comma separated.
+    #[validate(custom(function = "validate_id_list"))]
+    pub route_ids: Option<String>,
+
+    /// How far the rider is willing to walk. Defaults to 400 meters.
+    #[validate(range(min = 1.0, max = 2000.0, message = "Must be between 1 and 2000"))]
+    pub radius_meters: Option<f64>,
+
+    /// Minutes to spare at the stop before the bus arrives. Defaults to 1.
+    #[validate(range(min = 0, max = 15, message = "Must be between 0 and 15"))]
+    pub buffer_minutes: Option<u64>,
+}
+
+impl GetTransitDepartureAdvicePayload {
+    fn route_ids(&self) -> Option<Vec<String>> {
+        self.route_ids.as_deref().map(split_ids)
+    }
+}
+
+/// A bus the rider can still catch and when to leave for it.
+#[derive(Serialize, Deserialize)]
+pub struct TransitDepartureOption {
+    pub route_id: String,
+    pub route_label: String,
+    pub direction_id: String,
+    pub destination_name: Option<String>,
+    pub stop_id: String,
+    pub stop_name: String,
+    pub walking_distance_meters: f64,
+    pub walking_minutes: i64,
+    pub expected_arrival_time: String,
+    pub minutes_until_arrival: i64,
+    pub leave_at: String,
+    /// Zero means leave now.
+    pub leave_in_minutes: i64,
+}
+
+impl From<&DepartureOption> for TransitDepartureOption {
+    fn from(o: &DepartureOption) -> Self {
+        TransitDepartureOption {
+            route_id: o.arrival.route_id.clone(),
+            route_label: o.arrival.route_label.clone(),
+            direction_id: o.arrival.direction_id.clone(),
+            destination_name: o.arrival.destination_name.clone(),
+            stop_id: o.arrival.stop_id.clone(),
+            stop_name: o.stop_name.clone(),
+            walking_distance_meters: o.walking_distance_meters,
+            walking_minutes: o.walking_time.as_secs().div_ceil(60) as i64,
+            expected_arrival_time: o.arrival.expected_arrival_time.clone(),
+            minutes_until_arrival: o.arrival.minutes_until_arrival,
+            leave_at: o.leave_at.to_rfc3339(),
+            leave_in_minutes: (o.leave_at - Utc::now()).num_minutes().max(0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransitDepartureAdviceData {
+    /// The soonest bus that can still be caught, if any.
+    pub recommendation: Option<TransitDepartureOption>,
+    /// The soonest catchable bus on each route and direction, soonest first.
+    pub options: Vec<TransitDepartureOption>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransitDepartureAdviceResponse {
+    pub data: TransitDepartureAdviceData,
+    #[serde(default)]
+    pub errors: Vec<TransitArrivalsResponseError>,
+}
+
+pub async fn get_transit_departure_advice(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitDepartureAdvicePayload>,
+) -> Result<Response, AppError> {
+    let route_ids = payload.route_ids();
+    let location = GetStopsAtLocationInput::from_query(payload.coordinates, payload.place_id)
+        .map_err(|message| AppError::new(StatusCode::BAD_REQUEST, message))?;
+
+    let advice = state
+        .departure_advisor
+        .with_cache_policy(cache_policy)
+        .advise(
+            location,
+            DepartureAdviceOptions {
+                radius_meters: payload.radius_meters.unwrap_or(400.0),
+                route_ids,
+                buffer: Duration::from_secs(payload.buffer_minutes.unwrap_or(1) * 60),
+            },
+        )
+        .await
+        .map_err(|e| match e {
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to advise departures: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    let options = advice
+        .options
+        .iter()
+        .map(TransitDepartureOption::from)
+        .collect::<Vec<TransitDepartureOption>>();
+
+    let status = match advice.errors.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::MULTI_STATUS,
+    };
+
+    let res = TransitDepartureAdviceResponse {
+        data: TransitDepartureAdviceData {
+            recommendation: advice.options.first().map(TransitDepartureOption::from),
+            options,
+        },
+        errors: advice
+            .errors
+            .iter()
+            .map(|e| TransitArrivalsResponseError {
+                stop_id: e.stop_id.clone(),
+                reason: e.reason.clone(),
+            })
+            .collect(),
+    };
+
+    Ok((status, Json(res)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use chrono::Duration;
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        services::transit_service::types::mta_get_stops_at_location_response::{
+            GetStopsAtLocationResponse, GetStopsAtLocationResponseStops, StopAtLocation,
+            StopAtLocationRoute,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn advises_when_to_leave() {
+        let mut mock_app = gen_mock_app().await;
+
+        let stops_for_location_response = GetStopsAtLocationResponse {
+            data: GetStopsAtLocationResponseStops {
+                stops: vec![StopAtLocation {
+                    id: "MTA_308209".to_string(),
+                    name: "5 Av/Union St".to_string(),
+                    // about 110m north
+                    lat: 40.681,
+                    lon: -73.98,
+                    direction: None,
+                    routes: vec![StopAtLocationRoute {
+                        id: "MTA NYCT_B63".to_string(),
+                    }],
+                }],
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/where/stops-for-location.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&stops_for_location_response).unwrap())
+            .match_query(mockito::Matcher::Regex("radius=400".to_string()))
+            .create_async()
+            .await;
+
+        let soon = (Utc::now() + Duration::seconds(90)).to_rfc3339();
+        let later = (Utc::now() + Duration::minutes(10)).to_rfc3339();
+        let visit = |expected: &str| {
+            serde_json::json!({ "MonitoredVehicleJourney": {
+                "LineRef": "MTA NYCT_B63",
+                "DirectionRef": "1",
+                "PublishedLineName": "B63",
+                "DestinationName": "BAY RIDGE SHORE RD",
+                "MonitoredCall": { "ExpectedArrivalTime": expected }
+            }})
+        };
+        let body = serde_json::json!({
+            "Siri": { "ServiceDelivery": { "StopMonitoringDelivery": [{
+                "MonitoredStopVisit": [visit(&soon), visit(&later)]
+            }]}}
+        });
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/stop-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+            .match_query(mockito::Matcher::Regex(".*MTA_308209.*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-departure-advice?coordinates=40.68,-73.98&route_ids=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: TransitDepartureAdviceResponse = serde_json::from_slice(&body).unwrap();
+
+        // a two minute walk and a minute to spare rules out the bus in 90 seconds
+        let recommendation = body.data.recommendation.unwrap();
+        assert_eq!(recommendation.stop_id, "MTA_308209");
+        assert_eq!(recommendation.stop_name, "5 Av/Union St");
+        assert_eq!(recommendation.expected_arrival_time, later);
+        assert_eq!(recommendation.walking_minutes, 2);
+        assert!((6..=7).contains(&recommendation.leave_in_minutes));
+        assert_eq!(body.data.options.len(), 1);
+    }
+}
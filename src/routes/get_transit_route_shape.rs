@@ -0,0 +1,306 @@
+use crate::{
+    services::transit_service::transit_service::{GetStopsForRouteResultGroup, TransitClientError},
+    types::{
+        app_state::AppState,
+        geojson::{Feature, FeatureCollection, ResponseFormat},
+    },
+    utils::{
+        app_error::AppError, cache_control::CacheControl, polyline::decode_polyline,
+        validated_query::ValidatedQuery,
+    },
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, warn};
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct GetTransitRouteShape {
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub route_id: String,
+
+    /// `geojson` returns a FeatureCollection of LineStrings and stop Points instead.
+    #[serde(default)]
+    pub format: ResponseFormat,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitRouteShapeResponseStop {
+    pub id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitRouteShapeResponseDirection {
+    pub id: String,
+    pub name: String,
+    /// Encoded polylines, as several pieces when the route branches.
+    pub polylines: Vec<String>,
+    /// In the order they are served.
+    pub stops: Vec<GetTransitRouteShapeResponseStop>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitRouteShapeResponseData {
+    pub route_id: String,
+    pub route_name: String,
+    pub directions: Vec<GetTransitRouteShapeResponseDirection>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitRouteShapeResponse {
+    pub data: GetTransitRouteShapeResponseData,
+}
+
+pub async fn get_transit_route_shape(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitRouteShape>,
+) -> Result<Response, AppError> {
+    let groups = state
+        .transit_service
+        .with_cache_policy(cache_policy)
+        .get_stops_for_route(payload.route_id.clone(), true)
+        .await
+        .map_err(|e| match e {
+            TransitClientError::ResourceNotFound => {
+                AppError::new(StatusCode::NOT_FOUND, "Route does not exist")
+            }
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch route shape: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?
+        .groups;
+
+    if payload.format == ResponseFormat::GeoJson {
+        return Ok(route_features(&groups).into_response());
+    }
+
+    let res = GetTransitRouteShapeResponse {
+        data: GetTransitRouteShapeResponseData {
+            route_name: groups
+                .first()
+                .map(|g| g.route_name.clone())
+                .unwrap_or_default(),
+            route_id: payload.route_id,
+            directions: groups
+                .iter()
+                .map(|g| GetTransitRouteShapeResponseDirection {
+                    id: g.id.clone(),
+                    name: g.name.clone(),
+                    polylines: g.polylines.clone(),
+                    // stops without a location can't be placed on a map, so they are left out
+                    stops: g
+                        .stops
+                        .iter()
+                        .filter_map(|s| {
+                            Some(GetTransitRouteShapeResponseStop {
+                                id: s.id.clone(),
+                                name: s.name.clone(),
+                                lat: s.lat?,
+                                lon: s.lon?,
+                            })
+                        })
+                        .collect(),
+                })
+                .collect(),
+        },
+    };
+
+    Ok((StatusCode::OK, Json(res)).into_response())
+}
+
+/// A LineString for every polyline followed by a Point for every stop, each tagged with the
+/// direction it belongs to.
+fn route_features(groups: &[GetStopsForRouteResultGroup]) -> FeatureCollection {
+    let mut features = Vec::new();
+
+    for g in groups {
+        let properties = json!({
+            "route_id": g.route_id,
+            "route_name": g.route_name,
+            "direction_id": g.id,
+            "direction_name": g.name,
+        });
+
+        for polyline in &g.polylines {
+            match decode_polyline(polyline) {
+                Some(points) => features.push(Feature::line_string(&points, properties.clone())),
+                None => warn!("Skipping malformed polyline for route {}", g.route_id),
+            }
+        }
+
+        for s in &g.stops {
+            let (Some(lat), Some(lon)) = (s.lat, s.lon) else {
+                continue;
+            };
+            let mut properties = properties.clone();
+            properties["stop_id"] = json!(s.id);
+            properties["stop_name"] = json!(s.name);
+
+            features.push(Feature::point((lat, lon), properties));
+        }
+    }
+
+    FeatureCollection { features }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CONTENT_TYPE, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        services::transit_service::types::mta_get_stops_for_route_response::{
+            GetStopsForRouteResponse, GetStopsForRouteResponseData,
+            GetStopsForRouteResponseDataEntry, GetStopsForRouteResponseDataEntryStopGrouping,
+            GetStopsForRouteResponseDataEntryStopGroupingStopGroup,
+            GetStopsForRouteResponseDataEntryStopGroupingStopGroupName,
+            GetStopsForRouteResponseDataEntryStopGroupingType,
+            GetStopsForRouteResponseDataReferences, GetStopsForRouteResponseDataReferencesRoute,
+            GetStopsForRouteResponseDataReferencesStop, GetStopsForRouteResponsePolyline,
+        },
+        types::geojson::Geometry,
+        utils::polyline::encode_polyline,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_polylines_and_geojson() {
+        let mut mock_app = gen_mock_app().await;
+
+        let polyline = encode_polyline(&[(40.68, -73.98), (40.67, -73.988)]);
+        let mock_response = GetStopsForRouteResponse {
+            data: GetStopsForRouteResponseData {
+                entry: GetStopsForRouteResponseDataEntry {
+                    stopGroupings: vec![GetStopsForRouteResponseDataEntryStopGrouping {
+                        r#type: GetStopsForRouteResponseDataEntryStopGroupingType::Direction,
+                        stopGroups: vec![GetStopsForRouteResponseDataEntryStopGroupingStopGroup {
+                            id: "1".to_string(),
+                            name: GetStopsForRouteResponseDataEntryStopGroupingStopGroupName {
+                                name: "BAY RIDGE".to_string(),
+                            },
+                            stopIds: vec![
+                                "MTA_1".to_string(),
+                                "MTA_2".to_string(),
+                                "MTA_3".to_string(),
+                            ],
+                            polylines: vec![GetStopsForRouteResponsePolyline {
+                                points: polyline.clone(),
+                            }],
+                        }],
+                    }],
+                },
+                references: GetStopsForRouteResponseDataReferences {
+                    stops: vec![
+                        GetStopsForRouteResponseDataReferencesStop {
+                            id: "MTA_1".to_string(),
+                            name: "5 AV/UNION ST".to_string(),
+                            lat: Some(40.68),
+                            lon: Some(-73.98),
+                        },
+                        GetStopsForRouteResponseDataReferencesStop {
+                            id: "MTA_2".to_string(),
+                            name: "5 AV/3 ST".to_string(),
+                            lat: None,
+                            lon: None,
+                        },
+                        GetStopsForRouteResponseDataReferencesStop {
+                            id: "MTA_3".to_string(),
+                            name: "5 AV/9 ST".to_string(),
+                            lat: Some(40.67),
+                            lon: Some(-73.988),
+                        },
+                    ],
+                    routes: vec![GetStopsForRouteResponseDataReferencesRoute {
+                        id: "MTA NYCT_B63".to_string(),
+                        shortName: "B63".to_string(),
+                    }],
+                },
+            },
+        };
+
+        let mock_server = mock_app
+            .mta_server
+            .mock("GET", "/api/where/stops-for-route/MTA%20NYCT_B63.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex("includePolylines=true".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-route-shape?route_id=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        mock_server.assert();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitRouteShapeResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.route_name, "B63");
+        assert_eq!(body.data.directions.len(), 1);
+        assert_eq!(body.data.directions[0].polylines, [polyline]);
+        // MTA_2 has no coordinates, so it can't be placed on the map
+        assert_eq!(body.data.directions[0].stops.len(), 2);
+        assert_eq!(body.data.directions[0].stops[1].id, "MTA_3");
+        assert_eq!(body.data.directions[0].stops[1].lat, 40.67);
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-route-shape?route_id=MTA%20NYCT_B63&format=geojson")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/geo+json");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: FeatureCollection = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.features.len(), 3);
+        assert_eq!(
+            body.features[0].geometry,
+            Geometry::LineString {
+                coordinates: vec![[-73.98, 40.68], [-73.988, 40.67]]
+            }
+        );
+        assert_eq!(body.features[0].properties["direction_name"], "BAY RIDGE");
+        assert_eq!(
+            body.features[1].geometry,
+            Geometry::Point {
+                coordinates: [-73.98, 40.68]
+            }
+        );
+        assert_eq!(body.features[1].properties["stop_id"], "MTA_1");
+    }
+}
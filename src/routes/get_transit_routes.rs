@@ -1,6 +1,7 @@
 use crate::{
+    services::transit_service::transit_service::TransitClientError,
     types::app_state::AppState,
-    utils::{app_error::AppError, validated_query::ValidatedQuery},
+    utils::{app_error::AppError, cache_control::CacheControl, validated_query::ValidatedQuery},
 };
 use axum::{
     extract::State,
@@ -18,11 +19,14 @@ use validator::Validate;
 pub struct GetTransitRoutesPayload {
     #[validate(length(min = 1, message = "Must be at least 1 character"))]
     pub search: String,
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub agency_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GetTransitRoutesResponseDataRoute {
     pub id: String,
+    pub agency_id: String,
     pub name: String,
 }
 
@@ -39,20 +43,26 @@ pub struct GetTransitRoutesResponse {
 #[cfg_attr(test, debug_handler)]
 pub async fn get_transit_routes(
     State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
     ValidatedQuery(payload): ValidatedQuery<GetTransitRoutesPayload>,
 ) -> Result<Response, AppError> {
     let routes = state
         .transit_service
-        .get_routes(&payload.search)
+        .with_cache_policy(cache_policy)
+        .get_routes(&payload.search, payload.agency_id.as_deref())
         .await
-        .map_err(|e| {
-            error!("Failed to fetch transit routes: {}", e);
-            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        .map_err(|e| match e {
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch transit routes: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
         })?
         .routes
         .iter()
         .map(|r| GetTransitRoutesResponseDataRoute {
             id: r.id.clone(),
+            agency_id: r.agency_id.clone(),
             name: r.name.clone(),
         })
         .collect::<Vec<GetTransitRoutesResponseDataRoute>>();
@@ -131,6 +141,7 @@ mod tests {
 
         assert_eq!(body.data.routes.len(), 1);
         assert_eq!(body.data.routes[0].id, "1");
+        assert_eq!(body.data.routes[0].agency_id, "MTA NYCT");
         assert_eq!(body.data.routes[0].name, "A");
     }
 }
@@ -1,8 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
+    services::transit_service::transit_service::{StopNearLocation, TransitClientError},
     types::{app_state::AppState, lat_long_location::GetStopsAtLocationInput},
-    utils::{app_error::AppError, validated_query::ValidatedQuery},
+    utils::{
+        app_error::AppError, cache_control::CacheControl, geo::compass_point,
+        validated_query::ValidatedQuery,
+    },
 };
 use axum::{
     extract::State,
@@ -18,12 +22,55 @@ use validator::Validate;
 pub struct GetTransitStopsAtLocation {
     pub coordinates: Option<String>,
     pub place_id: Option<String>,
+
+    /// How far from the location to look for stops. Defaults to 250 meters.
+    #[validate(range(min = 1.0, max = 2000.0, message = "Must be between 1 and 2000"))]
+    pub radius_meters: Option<f64>,
+
+    /// Keeps only this many of the nearest stops.
+    #[validate(range(min = 1, max = 100, message = "Must be between 1 and 100"))]
+    pub limit: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GetTransitStopsAtLocationResponseRouteStop {
     pub id: String,
     pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub distance_meters: f64,
+    pub compass_direction: String,
+}
+
+/// A nearby stop. `distance_meters` and `compass_direction` are measured from the searched
+/// location, while `direction` is the way traffic at the stop heads.
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitStopsAtLocationResponseStop {
+    pub id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub direction: Option<String>,
+    pub distance_meters: f64,
+    pub bearing_degrees: f64,
+    pub compass_direction: String,
+    pub route_ids: Vec<String>,
+}
+
+impl From<&StopNearLocation> for GetTransitStopsAtLocationResponseStop {
+    fn from(s: &StopNearLocation) -> Self {
+        GetTransitStopsAtLocationResponseStop {
+            id: s.id.clone(),
+            name: s.name.clone(),
+            lat: s.lat,
+            lon: s.lon,
+            direction: s.direction.clone(),
+            distance_meters: s.distance_meters,
+            bearing_degrees: s.bearing_degrees,
+            compass_direction: compass_point(s.bearing_degrees).to_string(),
+            route_ids: s.route_ids.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,7 +88,10 @@ pub struct GetTransitStopsAtLocationResponseRoute {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetTransitStopsAtLocationResponseData {
+    /// Ordered by their nearest stop.
     pub routes: Vec<GetTransitStopsAtLocationResponseRoute>,
+    /// Nearest first.
+    pub stops: Vec<GetTransitStopsAtLocationResponseStop>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -51,50 +101,44 @@ pub struct GetTransitStopsAtLocationResponse {
 
 pub async fn get_transit_stops_at_location(
     State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
     ValidatedQuery(payload): ValidatedQuery<GetTransitStopsAtLocation>,
 ) -> Result<Response, AppError> {
-    let groups = state
+    let location = GetStopsAtLocationInput::from_query(payload.coordinates, payload.place_id)
+        .map_err(|message| AppError::new(StatusCode::BAD_REQUEST, message))?;
+
+    let result = state
         .transit_service
-        .get_stops_at_location(match payload {
-            GetTransitStopsAtLocation {
-                coordinates: Some(coordinates),
-                place_id: None,
-            } => {
-                let coordinates: Vec<&str> = coordinates.split(',').collect();
-                let lat = coordinates.get(0);
-                let lon = coordinates.get(1);
-
-                match (lat, lon) {
-                    (Some(lat), Some(lon)) => {
-                        GetStopsAtLocationInput::LatLong(lat.to_string(), lon.to_string())
-                    }
-                    _ => {
-                        return Err(AppError::new(
-                            StatusCode::BAD_REQUEST,
-                            "Invalid coordinates. Must be in the form of coordinates=lat,lon",
-                        ))
-                    }
-                }
-            }
-            GetTransitStopsAtLocation {
-                coordinates: None,
-                place_id: Some(place_id),
-            } => GetStopsAtLocationInput::GooglePlaceId(place_id),
-            _ => return Err(AppError::new(
-                StatusCode::BAD_REQUEST,
-                "Must provide either coordinates in the form of coordinates=lat,lon or place_id",
-            )),
-        })
+        .with_cache_policy(cache_policy)
+        .get_stops_at_location(
+            location,
+            payload.radius_meters.unwrap_or(250.0),
+            payload.limit,
+        )
         .await
-        .map_err(|e| {
-            error!("Failed to fetch stops at location: {}", e);
-            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
-        })?
-        .groups;
+        .map_err(|e| match e {
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch stops at location: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    let groups = result.groups;
+    let stops_by_id = result
+        .stops
+        .iter()
+        .map(|s| (s.id.as_str(), s))
+        .collect::<HashMap<&str, &StopNearLocation>>();
 
     let mut res = GetTransitStopsAtLocationResponse {
         data: GetTransitStopsAtLocationResponseData {
             routes: Vec::<GetTransitStopsAtLocationResponseRoute>::new(),
+            stops: result
+                .stops
+                .iter()
+                .map(GetTransitStopsAtLocationResponseStop::from)
+                .collect(),
         },
     };
 
@@ -124,14 +168,27 @@ pub async fn get_transit_stops_at_location(
             };
 
             for stop in &g.stops {
+                let nearby = match stops_by_id.get(stop.id.as_str()) {
+                    Some(nearby) => nearby,
+                    None => continue,
+                };
+
                 grouping
                     .stops
                     .push(GetTransitStopsAtLocationResponseRouteStop {
                         id: stop.id.clone(),
                         name: stop.name.clone(),
+                        lat: nearby.lat,
+                        lon: nearby.lon,
+                        distance_meters: nearby.distance_meters,
+                        compass_direction: compass_point(nearby.bearing_degrees).to_string(),
                     });
             }
 
+            grouping
+                .stops
+                .sort_by(|a, b| a.distance_meters.total_cmp(&b.distance_meters));
+
             new_route.groupings.push(grouping);
         }
 
@@ -144,7 +201,7 @@ pub async fn get_transit_stops_at_location(
 #[cfg(test)]
 mod tests {
     use axum::{
-        body::Body,
+        body::{to_bytes, Body},
         http::{Request, StatusCode},
     };
     use tower::ServiceExt;
@@ -164,6 +221,21 @@ mod tests {
         },
     };
 
+    use super::*;
+
+    fn stop_at_location(id: &str, lat: f64, lon: f64) -> StopAtLocation {
+        StopAtLocation {
+            id: id.to_string(),
+            name: format!("Stop {}", id),
+            lat,
+            lon,
+            direction: Some("N".to_string()),
+            routes: vec![StopAtLocationRoute {
+                id: "1".to_string(),
+            }],
+        }
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn test_response_latlon() {
@@ -171,12 +243,14 @@ mod tests {
 
         let stops_for_location_response = GetStopsAtLocationResponse {
             data: GetStopsAtLocationResponseStops {
-                stops: vec![StopAtLocation {
-                    id: "1".to_string(),
-                    routes: vec![StopAtLocationRoute {
-                        id: "1".to_string(),
-                    }],
-                }],
+                stops: vec![
+                    // about 220m north east
+                    stop_at_location("far", 40.6815, -73.9785),
+                    // about 110m south
+                    stop_at_location("near", 40.679, -73.98),
+                    // outside the radius even though the upstream returned it
+                    stop_at_location("outside", 40.69, -73.98),
+                ],
             },
         };
 
@@ -192,12 +266,12 @@ mod tests {
             },
         };
 
-        mock_app
+        let location_mock = mock_app
             .mta_server
             .mock("GET", "/api/where/stops-for-location.json")
             .with_header("content-type", "application/json")
             .with_body(serde_json::to_string(&stops_for_location_response).unwrap())
-            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .match_query(mockito::Matcher::Regex("radius=300".to_string()))
             .create_async()
             .await;
 
@@ -220,8 +294,8 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .uri(format!(
-                        "/transit-stops-at-location?coordinates={},{}",
-                        1.0, 1.0
+                        "/transit-stops-at-location?coordinates={},{}&radius_meters=300",
+                        40.68, -73.98
                     ))
                     .header("content-type", "application/json")
                     .body(Body::empty())
@@ -231,5 +305,99 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
+        location_mock.assert();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitStopsAtLocationResponse = serde_json::from_slice(&body).unwrap();
+        let stops = body.data.stops;
+
+        assert_eq!(stops.len(), 2);
+        assert_eq!(stops[0].id, "near");
+        assert_eq!(stops[0].compass_direction, "S");
+        assert!((stops[0].distance_meters - 111.0).abs() < 5.0);
+        assert_eq!(stops[0].direction.as_deref(), Some("N"));
+        assert_eq!(stops[1].id, "far");
+        assert_eq!(stops[1].compass_direction, "NE");
+    }
+
+    #[tokio::test]
+    async fn limits_stops() {
+        let mut mock_app = gen_mock_app().await;
+
+        let stops_for_location_response = GetStopsAtLocationResponse {
+            data: GetStopsAtLocationResponseStops {
+                stops: vec![
+                    stop_at_location("far", 40.6815, -73.9785),
+                    stop_at_location("near", 40.679, -73.98),
+                ],
+            },
+        };
+
+        let stops_for_route_response = GetStopsForRouteResponse {
+            data: GetStopsForRouteResponseData {
+                entry: GetStopsForRouteResponseDataEntry {
+                    stopGroupings: vec![],
+                },
+                references: GetStopsForRouteResponseDataReferences {
+                    stops: vec![],
+                    routes: vec![],
+                },
+            },
+        };
+
+        // the radius defaults to 250 meters
+        mock_app
+            .mta_server
+            .mock("GET", "/api/where/stops-for-location.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&stops_for_location_response).unwrap())
+            .match_query(mockito::Matcher::Regex("radius=250".to_string()))
+            .create_async()
+            .await;
+
+        mock_app
+            .mta_server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("/api/where/stops-for-route/1.json".to_string()),
+            )
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&stops_for_route_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-stops-at-location?coordinates=40.68,-73.98&limit=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitStopsAtLocationResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body.data.stops.len(), 1);
+        assert_eq!(body.data.stops[0].id, "near");
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-stops-at-location?coordinates=40.68,-73.98&radius_meters=5000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 }
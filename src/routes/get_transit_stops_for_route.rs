@@ -1,7 +1,8 @@
 use crate::{
-    services::transit_service::transit_service::TransitClientError,
+    routes::get_transit_alerts::ServiceAlertResponse,
+    services::transit_service::transit_service::{AlertFilter, TransitClientError},
     types::app_state::AppState,
-    utils::{app_error::AppError, validated_query::ValidatedQuery},
+    utils::{app_error::AppError, cache_control::CacheControl, validated_query::ValidatedQuery},
 };
 use axum::{
     extract::State,
@@ -9,8 +10,9 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use futures::future::join;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, warn};
 use validator::Validate;
 
 #[derive(Validate, Deserialize)]
@@ -20,7 +22,6 @@ pub struct GetTransitStopsForRoute {
 }
 
 #[derive(Serialize, Deserialize)]
-
 pub struct GetTransitStopsForRouteResponseGroupStop {
     pub id: String,
     pub name: String,
@@ -37,6 +38,9 @@ pub struct GetTransitStopsForRouteResponseGroup {
 #[derive(Serialize, Deserialize)]
 pub struct GetTransitStopsForRouteResponseData {
     pub groups: Vec<GetTransitStopsForRouteResponseGroup>,
+    /// Active alerts affecting the route.
+    #[serde(default)]
+    pub alerts: Vec<ServiceAlertResponse>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -46,16 +50,27 @@ pub struct GetTransitStopsForRouteResponse {
 
 pub async fn get_transit_stops_for_route(
     State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
     ValidatedQuery(payload): ValidatedQuery<GetTransitStopsForRoute>,
 ) -> Result<Response, AppError> {
-    let groups = state
+    let alerts_service = state
         .transit_service
-        .get_stops_for_route(payload.route_id)
-        .await
+        .with_cache_policy(cache_policy.alongside());
+    let (groups, alerts) = join(
+        state
+            .transit_service
+            .with_cache_policy(cache_policy)
+            .get_stops_for_route(payload.route_id.clone(), false),
+        alerts_service.fetch_alerts(&[AlertFilter::Route(payload.route_id.clone())]),
+    )
+    .await;
+
+    let groups = groups
         .map_err(|e| match e {
             TransitClientError::ResourceNotFound => {
                 AppError::new(StatusCode::NOT_FOUND, "Route does not exist")
             }
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
             _ => {
                 error!("Failed to fetch stops for route: {}", e);
                 AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
@@ -78,10 +93,19 @@ pub async fn get_transit_stops_for_route(
         })
         .collect::<Vec<GetTransitStopsForRouteResponseGroup>>();
 
+    // the stops are still worth returning without their alerts
+    let alerts = alerts.unwrap_or_else(|e| {
+        warn!("Failed to fetch alerts for route: {}", e);
+        Vec::new()
+    });
+
     Ok((
         StatusCode::OK,
         Json(GetTransitStopsForRouteResponse {
-            data: GetTransitStopsForRouteResponseData { groups },
+            data: GetTransitStopsForRouteResponseData {
+                groups,
+                alerts: alerts.iter().map(ServiceAlertResponse::from).collect(),
+            },
         }),
     )
         .into_response())
@@ -127,6 +151,7 @@ mod tests {
                                 name: "group name".to_string(),
                             },
                             stopIds: vec!["s1".to_string(), "s2".to_string()],
+                            polylines: vec![],
                         }],
                     }],
                 },
@@ -135,10 +160,14 @@ mod tests {
                         GetStopsForRouteResponseDataReferencesStop {
                             id: "s1".to_string(),
                             name: "stop 1".to_string(),
+                            lat: Some(40.68),
+                            lon: Some(-73.98),
                         },
                         GetStopsForRouteResponseDataReferencesStop {
                             id: "s2".to_string(),
                             name: "stop 2".to_string(),
+                            lat: Some(40.68),
+                            lon: Some(-73.98),
                         },
                     ],
                     routes: vec![],
@@ -152,7 +181,9 @@ mod tests {
             .mock("GET", "/api/where/stops-for-route/B1%2B.json")
             .with_header("content-type", "application/json")
             .with_body(serde_json::to_string(&mock_response).unwrap())
-            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .match_query(mockito::Matcher::Regex(
+                "includePolylines=false".to_string(),
+            ))
             .create_async()
             .await;
 
@@ -184,6 +215,51 @@ mod tests {
         assert_eq!(body.data.groups[0].stops[1].name, "stop 2");
     }
 
+    #[tokio::test]
+    async fn bypassing_the_cache_reads_alerts_from_it_only() {
+        let mut mock_app = gen_mock_app().await;
+
+        let stops_server = mock_app
+            .mta_server
+            .mock("GET", "/api/where/stops-for-route/B1.json")
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({
+                    "data": {
+                        "references": { "stops": [], "routes": [] },
+                        "entry": { "stopGroupings": [] }
+                    }
+                })
+                .to_string(),
+            )
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+        let alerts_server = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/vehicle-monitoring.json")
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-stops-for-route?route_id=B1")
+                    .header("cache-control", "no-cache")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        stops_server.assert();
+        alerts_server.assert();
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn test_not_found() {
@@ -0,0 +1,261 @@
+use crate::{
+    services::transit_service::transit_service::{
+        TransitClientError, VehicleFilter, VehiclePosition,
+    },
+    types::{
+        app_state::AppState,
+        geojson::{Feature, FeatureCollection, ResponseFormat},
+    },
+    utils::{app_error::AppError, cache_control::CacheControl, validated_query::ValidatedQuery},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct GetTransitVehicles {
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub route_id: Option<String>,
+
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub vehicle_id: Option<String>,
+
+    /// `geojson` returns a FeatureCollection of Points instead.
+    #[serde(default)]
+    pub format: ResponseFormat,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct VehiclePositionResponse {
+    pub vehicle_id: String,
+    pub route_id: String,
+    pub route_label: String,
+    pub direction_id: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    pub bearing: Option<f64>,
+    pub destination_name: Option<String>,
+    pub next_stop_id: Option<String>,
+    pub progress_status: Option<String>,
+    pub recorded_at: Option<String>,
+}
+
+impl From<&VehiclePosition> for VehiclePositionResponse {
+    fn from(v: &VehiclePosition) -> Self {
+        VehiclePositionResponse {
+            vehicle_id: v.vehicle_id.clone(),
+            route_id: v.route_id.clone(),
+            route_label: v.route_label.clone(),
+            direction_id: v.direction_id.clone(),
+            lat: v.lat,
+            lon: v.lon,
+            bearing: v.bearing,
+            destination_name: v.destination_name.clone(),
+            next_stop_id: v.next_stop_id.clone(),
+            progress_status: v.progress_status.clone(),
+            recorded_at: v.recorded_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitVehiclesResponseData {
+    pub vehicles: Vec<VehiclePositionResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetTransitVehiclesResponse {
+    pub data: GetTransitVehiclesResponseData,
+}
+
+pub async fn get_transit_vehicles(
+    State(state): State<AppState>,
+    CacheControl(cache_policy): CacheControl,
+    ValidatedQuery(payload): ValidatedQuery<GetTransitVehicles>,
+) -> Result<Response, AppError> {
+    let filter = match (payload.route_id, payload.vehicle_id) {
+        (Some(route_id), None) => VehicleFilter::Route(route_id),
+        (None, Some(vehicle_id)) => VehicleFilter::Vehicle(vehicle_id),
+        _ => {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                "Must provide either route_id or vehicle_id",
+            ))
+        }
+    };
+
+    let vehicles = state
+        .transit_service
+        .with_cache_policy(cache_policy)
+        .fetch_vehicle_positions(&filter)
+        .await
+        .map_err(|e| match e {
+            TransitClientError::ResourceNotFound => {
+                AppError::new(StatusCode::NOT_FOUND, "Route or vehicle does not exist")
+            }
+            TransitClientError::Unavailable(_, retry_after) => AppError::unavailable(retry_after),
+            _ => {
+                error!("Failed to fetch vehicle positions: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?
+        .iter()
+        .map(VehiclePositionResponse::from)
+        .collect::<Vec<VehiclePositionResponse>>();
+
+    if payload.format == ResponseFormat::GeoJson {
+        return Ok(FeatureCollection {
+            features: vehicles
+                .iter()
+                .map(|v| Feature::point((v.lat, v.lon), serde_json::to_value(v).unwrap()))
+                .collect(),
+        }
+        .into_response());
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(GetTransitVehiclesResponse {
+            data: GetTransitVehiclesResponseData { vehicles },
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        services::transit_service::types::mta_get_vehicle_monitoring_response::{
+            GetVehicleMonitoringResponse, VehicleActivity, VehicleJourney, VehicleLocation,
+            VehicleMonitoredCall, VehicleMonitoringDelivery, VehicleServiceDelivery, VehicleSiri,
+        },
+        types::geojson::Geometry,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_vehicle_positions() {
+        let mut mock_app = gen_mock_app().await;
+
+        let mock_response = GetVehicleMonitoringResponse {
+            Siri: VehicleSiri {
+                ServiceDelivery: VehicleServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    VehicleMonitoringDelivery: Vec::from([VehicleMonitoringDelivery {
+                        VehicleActivity: Vec::from([VehicleActivity {
+                            MonitoredVehicleJourney: VehicleJourney {
+                                LineRef: "MTA NYCT_B63".to_string(),
+                                DirectionRef: Some("1".to_string()),
+                                PublishedLineName: "B63".to_string(),
+                                DestinationName: Some("BAY RIDGE SHORE RD".to_string()),
+                                VehicleRef: "MTA NYCT_7582".to_string(),
+                                VehicleLocation: VehicleLocation {
+                                    Latitude: 40.67,
+                                    Longitude: -73.98,
+                                },
+                                Bearing: Some(235.5),
+                                ProgressStatus: Some("layover".to_string()),
+                                MonitoredCall: Some(VehicleMonitoredCall {
+                                    StopPointRef: Some("MTA_308209".to_string()),
+                                }),
+                            },
+                            RecordedAtTime: None,
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        let mock_server = mock_app
+            .mta_server
+            .mock("GET", "/api/siri/vehicle-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(
+                "LineRef=MTA%20NYCT_B63".to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-vehicles?route_id=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        mock_server.assert();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitVehiclesResponse = serde_json::from_slice(&body).unwrap();
+        let vehicle = &body.data.vehicles[0];
+
+        assert_eq!(vehicle.vehicle_id, "MTA NYCT_7582");
+        assert_eq!(vehicle.bearing, Some(235.5));
+        assert_eq!(vehicle.next_stop_id.as_deref(), Some("MTA_308209"));
+        assert_eq!(vehicle.progress_status.as_deref(), Some("layover"));
+        assert_eq!(
+            vehicle.destination_name.as_deref(),
+            Some("BAY RIDGE SHORE RD")
+        );
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-vehicles?route_id=MTA%20NYCT_B63&format=geojson")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: FeatureCollection = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body.features[0].geometry,
+            Geometry::Point {
+                coordinates: [-73.98, 40.67]
+            }
+        );
+        assert_eq!(body.features[0].properties["vehicle_id"], "MTA NYCT_7582");
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-vehicles")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,591 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Duration,
+};
+
+use crate::{
+    routes::{
+        get_transit_arrival_times::{group_arrivals, TransitArrivalsGroup},
+        get_transit_vehicles::VehiclePositionResponse,
+    },
+    services::{
+        live_updates::live_poller::LivePoller,
+        transit_service::transit_service::{
+            MultipleStopArrivals, TransitClientError, VehicleFilter, VehiclePosition,
+        },
+    },
+    types::app_state::AppState,
+    utils::shared_poll::PollUpdate,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+
+/// Subscriptions a single connection may hold at once.
+const MAX_SUBSCRIPTIONS: usize = 200;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct WsTopics {
+    #[serde(default)]
+    pub stop_ids: Vec<String>,
+    #[serde(default)]
+    pub route_ids: Vec<String>,
+    #[serde(default)]
+    pub vehicle_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    Subscribe {
+        #[serde(flatten)]
+        topics: WsTopics,
+        /// Applies to the stops in this message, defaulting to 1.
+        limit_per_route: Option<usize>,
+    },
+    Unsubscribe {
+        #[serde(flatten)]
+        topics: WsTopics,
+    },
+}
+
+/// Identifies a group of arrivals at a stop.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct WsRouteDirection {
+    pub route_id: String,
+    pub direction_id: String,
+}
+
+/// The first message for a subscription carries everything, later ones only what changed since.
+/// A failed lookup is reported in `error`, leaving what was last sent in place, and retried on the
+/// next poll.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    /// Acknowledges a subscribe or unsubscribe with everything the connection is now subscribed to.
+    Subscribed(WsTopics),
+    Arrivals {
+        stop_id: String,
+        /// Route and direction groups that are new or whose arrivals changed.
+        changed: Vec<TransitArrivalsGroup>,
+        /// Groups that no longer have arrivals.
+        removed: Vec<WsRouteDirection>,
+        error: Option<String>,
+    },
+    Positions {
+        route_id: Option<String>,
+        vehicle_id: Option<String>,
+        /// Vehicles that are new or have moved.
+        changed: Vec<VehiclePositionResponse>,
+        /// IDs of vehicles no longer reported.
+        removed: Vec<String>,
+        error: Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// What one subscription has sent, so that later messages only carry changes.
+struct Sent<K, V> {
+    items: HashMap<K, V>,
+    error: Option<String>,
+    started: bool,
+}
+
+impl<K, V> Default for Sent<K, V> {
+    fn default() -> Self {
+        Sent {
+            items: HashMap::new(),
+            error: None,
+            started: false,
+        }
+    }
+}
+
+impl<K, V> Sent<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + PartialEq,
+{
+    /// Records what is sent next, returning the changed items and removed keys, or `None` when
+    /// there is nothing to send. Items are left as they were when the lookup failed.
+    fn update(
+        &mut self,
+        items: Option<Vec<(K, V)>>,
+        error: Option<String>,
+    ) -> Option<(Vec<V>, Vec<K>)> {
+        let mut changed = Vec::new();
+        let mut removed = Vec::new();
+
+        if let Some(items) = items {
+            let mut current = HashMap::new();
+            for (key, value) in items {
+                if self.items.get(&key) != Some(&value) {
+                    changed.push(value.clone());
+                }
+                current.insert(key, value);
+            }
+
+            removed = self
+                .items
+                .keys()
+                .filter(|key| !current.contains_key(*key))
+                .cloned()
+                .collect();
+            self.items = current;
+        }
+
+        let first = !self.started;
+        let error_changed = self.error != error;
+        self.started = true;
+        self.error = error;
+
+        (first || error_changed || !changed.is_empty() || !removed.is_empty())
+            .then_some((changed, removed))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Topic {
+    Stop(String),
+    Route(String),
+    Vehicle(String),
+}
+
+pub async fn get_ws(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    // auth_middleware has already checked the upgrade request, including credentials in its query
+    ws.on_upgrade(move |socket| handle_socket(socket, state.live_poller))
+}
+
+async fn handle_socket(socket: WebSocket, poller: LivePoller) {
+    let (mut sink, mut stream) = socket.split();
+    let (sender, mut outgoing) = mpsc::channel::<WsServerMessage>(64);
+    let mut subscriptions: HashMap<Topic, JoinHandle<()>> = HashMap::new();
+
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(30));
+
+    loop {
+        let reply = tokio::select! {
+            incoming = stream.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    handle_message(&text, &poller, &sender, &mut subscriptions)
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            Some(message) = outgoing.recv() => message,
+            _ = heartbeat.tick() => {
+                if sink.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let text = match serde_json::to_string(&reply) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+
+        if sink.send(Message::Text(text)).await.is_err() {
+            break;
+        }
+    }
+
+    // dropping the forwarding tasks' receivers lets their polls stop
+    subscriptions.values().for_each(|handle| handle.abort());
+}
+
+fn handle_message(
+    text: &str,
+    poller: &LivePoller,
+    sender: &mpsc::Sender<WsServerMessage>,
+    subscriptions: &mut HashMap<Topic, JoinHandle<()>>,
+) -> WsServerMessage {
+    let message = match serde_json::from_str::<WsClientMessage>(text) {
+        Ok(m) => m,
+        Err(e) => {
+            return WsServerMessage::Error {
+                message: format!("Invalid message: {}", e),
+            }
+        }
+    };
+
+    match message {
+        WsClientMessage::Subscribe {
+            topics,
+            limit_per_route,
+        } => {
+            let limit_per_route = limit_per_route.unwrap_or(1);
+            if !(1..=10).contains(&limit_per_route) {
+                return WsServerMessage::Error {
+                    message: "limit_per_route must be between 1 and 10".to_string(),
+                };
+            }
+
+            let new_topics = to_topics(&topics)
+                .into_iter()
+                .filter(|t| !subscriptions.contains_key(t))
+                .collect::<HashSet<Topic>>();
+
+            if subscriptions.len() + new_topics.len() > MAX_SUBSCRIPTIONS {
+                return WsServerMessage::Error {
+                    message: format!("At most {} subscriptions are allowed", MAX_SUBSCRIPTIONS),
+                };
+            }
+
+            for topic in new_topics {
+                let handle = subscribe(&topic, poller, sender.clone(), limit_per_route);
+                subscriptions.insert(topic, handle);
+            }
+        }
+        WsClientMessage::Unsubscribe { topics } => {
+            for topic in to_topics(&topics) {
+                if let Some(handle) = subscriptions.remove(&topic) {
+                    handle.abort();
+                }
+            }
+        }
+    }
+
+    let mut current = WsTopics::default();
+    for topic in subscriptions.keys() {
+        match topic {
+            Topic::Stop(id) => current.stop_ids.push(id.clone()),
+            Topic::Route(id) => current.route_ids.push(id.clone()),
+            Topic::Vehicle(id) => current.vehicle_ids.push(id.clone()),
+        }
+    }
+    current.stop_ids.sort();
+    current.route_ids.sort();
+    current.vehicle_ids.sort();
+
+    WsServerMessage::Subscribed(current)
+}
+
+fn to_topics(topics: &WsTopics) -> Vec<Topic> {
+    let stops = topics.stop_ids.iter().map(|id| Topic::Stop(id.clone()));
+    let routes = topics.route_ids.iter().map(|id| Topic::Route(id.clone()));
+    let vehicles = topics
+        .vehicle_ids
+        .iter()
+        .map(|id| Topic::Vehicle(id.clone()));
+
+    stops.chain(routes).chain(vehicles).collect()
+}
+
+fn subscribe(
+    topic: &Topic,
+    poller: &LivePoller,
+    sender: mpsc::Sender<WsServerMessage>,
+    limit_per_route: usize,
+) -> JoinHandle<()> {
+    match topic {
+        Topic::Stop(stop_id) => {
+            let stop_id = stop_id.clone();
+            let receiver =
+                poller.subscribe_arrivals(std::slice::from_ref(&stop_id), limit_per_route);
+            let mut sent = Sent::default();
+
+            forward(receiver, sender, move |result| {
+                arrivals_message(&stop_id, &mut sent, result)
+            })
+        }
+        Topic::Route(route_id) => {
+            let route_id = route_id.clone();
+            let receiver = poller.subscribe_vehicles(VehicleFilter::Route(route_id.clone()));
+            let mut sent = Sent::default();
+
+            forward(receiver, sender, move |result| {
+                positions_message(Some(route_id.clone()), None, &mut sent, result)
+            })
+        }
+        Topic::Vehicle(vehicle_id) => {
+            let vehicle_id = vehicle_id.clone();
+            let receiver = poller.subscribe_vehicles(VehicleFilter::Vehicle(vehicle_id.clone()));
+            let mut sent = Sent::default();
+
+            forward(receiver, sender, move |result| {
+                positions_message(None, Some(vehicle_id.clone()), &mut sent, result)
+            })
+        }
+    }
+}
+
+fn arrivals_message(
+    stop_id: &str,
+    sent: &mut Sent<WsRouteDirection, TransitArrivalsGroup>,
+    result: &MultipleStopArrivals,
+) -> Option<WsServerMessage> {
+    let (groups, error) = match result.errors.first() {
+        Some(e) => (None, Some(e.reason.clone())),
+        None => {
+            let arrivals = result.arrivals.iter().collect::<Vec<_>>();
+            let groups = group_arrivals(&arrivals)
+                .into_iter()
+                .map(|g| {
+                    let key = WsRouteDirection {
+                        route_id: g.route_id.clone(),
+                        direction_id: g.direction_id.clone(),
+                    };
+                    (key, g)
+                })
+                .collect();
+
+            (Some(groups), None)
+        }
+    };
+
+    let (changed, removed) = sent.update(groups, error.clone())?;
+
+    Some(WsServerMessage::Arrivals {
+        stop_id: stop_id.to_string(),
+        changed,
+        removed,
+        error,
+    })
+}
+
+fn positions_message(
+    route_id: Option<String>,
+    vehicle_id: Option<String>,
+    sent: &mut Sent<String, VehiclePositionResponse>,
+    result: &Result<Vec<VehiclePosition>, TransitClientError>,
+) -> Option<WsServerMessage> {
+    let (vehicles, error) = match result {
+        Ok(vehicles) => (
+            Some(
+                vehicles
+                    .iter()
+                    .map(|v| (v.vehicle_id.clone(), VehiclePositionResponse::from(v)))
+                    .collect(),
+            ),
+            None,
+        ),
+        Err(TransitClientError::ResourceNotFound) => (None, Some("Not found".to_string())),
+        Err(TransitClientError::Internal(_) | TransitClientError::Unavailable(..)) => {
+            (None, Some("Failed to fetch vehicle positions".to_string()))
+        }
+    };
+
+    let (changed, removed) = sent.update(vehicles, error.clone())?;
+
+    Some(WsServerMessage::Positions {
+        route_id,
+        vehicle_id,
+        changed,
+        removed,
+        error,
+    })
+}
+
+/// Sends a message for the current value of a poll, then one for every change to it that
+/// `to_message` finds worth sending.
+fn forward<T, F>(
+    mut receiver: watch::Receiver<PollUpdate<T>>,
+    sender: mpsc::Sender<WsServerMessage>,
+    mut to_message: F,
+) -> JoinHandle<()>
+where
+    T: Send + Sync + 'static,
+    F: FnMut(&T) -> Option<WsServerMessage> + Send + 'static,
+{
+    receiver.mark_changed();
+
+    tokio::spawn(async move {
+        while receiver.changed().await.is_ok() {
+            let value = match receiver.borrow_and_update().clone() {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let message = match to_message(&value) {
+                Some(message) => message,
+                None => continue,
+            };
+
+            if sender.send(message).await.is_err() {
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::{gen_mock_app, gen_mock_app_with_auth},
+        services::transit_service::types::mta_get_vehicle_monitoring_response::{
+            GetVehicleMonitoringResponse, VehicleActivity, VehicleJourney, VehicleLocation,
+            VehicleMonitoringDelivery, VehicleServiceDelivery, VehicleSiri,
+        },
+    };
+    use tokio_tungstenite::{connect_async, tungstenite};
+
+    type Socket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn next_message(socket: &mut Socket) -> WsServerMessage {
+        loop {
+            if let tungstenite::Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn takes_credentials_from_the_query() {
+        let mock_app = gen_mock_app_with_auth(Some("secret"), None).await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, mock_app.app).await });
+
+        assert!(connect_async(format!("ws://{}/ws", address)).await.is_err());
+        assert!(connect_async(format!("ws://{}/ws?api_key=wrong", address))
+            .await
+            .is_err());
+        assert!(connect_async(format!("ws://{}/ws?api_key=secret", address))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn sends_only_changes() {
+        let mut sent = Sent::<&str, i32>::default();
+
+        let (changed, removed) = sent.update(Some(vec![("a", 1), ("b", 2)]), None).unwrap();
+        assert_eq!(changed.len(), 2);
+        assert!(removed.is_empty());
+        assert!(sent.update(Some(vec![("a", 1), ("b", 2)]), None).is_none());
+
+        assert_eq!(
+            sent.update(Some(vec![("a", 1), ("b", 3)]), None),
+            Some((vec![3], vec![]))
+        );
+        assert_eq!(
+            sent.update(Some(vec![("a", 1)]), None),
+            Some((vec![], vec!["b"]))
+        );
+
+        // a failure is reported once, and keeps what was sent for when the lookup recovers
+        let error = Some("Failed".to_string());
+        assert_eq!(sent.update(None, error.clone()), Some((vec![], vec![])));
+        assert!(sent.update(None, error).is_none());
+        assert_eq!(
+            sent.update(Some(vec![("a", 2)]), None),
+            Some((vec![2], vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribes_to_vehicle_positions() {
+        let mut mock_app = gen_mock_app().await;
+
+        let mock_response = GetVehicleMonitoringResponse {
+            Siri: VehicleSiri {
+                ServiceDelivery: VehicleServiceDelivery {
+                    SituationExchangeDelivery: Vec::new(),
+                    VehicleMonitoringDelivery: Vec::from([VehicleMonitoringDelivery {
+                        VehicleActivity: Vec::from([VehicleActivity {
+                            MonitoredVehicleJourney: VehicleJourney {
+                                LineRef: "MTA NYCT_B63".to_string(),
+                                PublishedLineName: "B63".to_string(),
+                                VehicleRef: "MTA NYCT_7582".to_string(),
+                                VehicleLocation: VehicleLocation {
+                                    Latitude: 40.67,
+                                    Longitude: -73.98,
+                                },
+                                ..Default::default()
+                            },
+                            RecordedAtTime: None,
+                        }]),
+                        ErrorCondition: None,
+                    }]),
+                },
+            },
+        };
+
+        mock_app
+            .mta_server
+            .mock("GET", "/api/siri/vehicle-monitoring.json")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&mock_response).unwrap())
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .create_async()
+            .await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, mock_app.app).await });
+
+        let (mut socket, _) = connect_async(format!("ws://{}/ws", address)).await.unwrap();
+
+        socket
+            .send(tungstenite::Message::Text(
+                r#"{"type":"subscribe","route_ids":["MTA NYCT_B63"]}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+
+        match next_message(&mut socket).await {
+            WsServerMessage::Subscribed(topics) => {
+                assert_eq!(topics.route_ids, vec!["MTA NYCT_B63"])
+            }
+            _ => panic!("expected a subscribed message"),
+        }
+
+        match next_message(&mut socket).await {
+            WsServerMessage::Positions {
+                route_id,
+                changed,
+                removed,
+                error,
+                ..
+            } => {
+                assert_eq!(route_id.as_deref(), Some("MTA NYCT_B63"));
+                assert_eq!(changed.len(), 1);
+                assert_eq!(changed[0].vehicle_id, "MTA NYCT_7582");
+                assert!(removed.is_empty());
+                assert_eq!(error, None);
+            }
+            _ => panic!("expected a positions message"),
+        }
+
+        socket
+            .send(tungstenite::Message::Text(
+                r#"{"type":"unsubscribe","route_ids":["MTA NYCT_B63"]}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+
+        match next_message(&mut socket).await {
+            WsServerMessage::Subscribed(topics) => assert!(topics.route_ids.is_empty()),
+            _ => panic!("expected a subscribed message"),
+        }
+
+        socket
+            .send(tungstenite::Message::Text("{}".to_string()))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            next_message(&mut socket).await,
+            WsServerMessage::Error { .. }
+        ));
+    }
+}
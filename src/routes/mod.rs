@@ -1,34 +1,155 @@
-use axum::{routing::get, Router};
+use axum::{
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
 
-use crate::types::app_state::AppState;
+use crate::{
+    middlewares::auth::require_scope, services::api_keys::api_key_store::Scope,
+    types::app_state::AppState,
+};
 
+mod delete_admin_key;
+mod delete_me_commute;
+mod delete_me_favorite;
+mod delete_me_notification;
+mod get_admin_keys;
 mod get_audio;
+mod get_audio_arrivals;
 mod get_location_search_autocomplete;
+mod get_me_commute_arrivals;
+mod get_me_commutes;
+mod get_me_favorites;
+mod get_me_notifications;
+mod get_transit_alerts;
 mod get_transit_arrival_times;
+mod get_transit_arrival_times_stream;
+mod get_transit_cache_stats;
+mod get_transit_departure_advice;
+mod get_transit_route_shape;
 mod get_transit_routes;
 mod get_transit_stops_at_location;
 mod get_transit_stops_for_route;
+mod get_transit_vehicles;
+mod get_ws;
+mod post_admin_key_rotate;
+mod post_admin_keys;
+mod post_gtfs_static_reload;
+mod put_me_commute;
+mod put_me_favorite;
+mod put_me_notification;
 
 pub fn apply_routes(app: Router<AppState>) -> Router<AppState> {
-    app.route(
-        "/transit-arrival-times",
-        get(get_transit_arrival_times::get_transit_arrival_times),
+    app.merge(
+        arrivals_routes().route_layer(middleware::from_fn_with_state(
+            Scope::ArrivalsRead,
+            require_scope,
+        )),
     )
-    .route(
-        "/transit-routes",
-        get(get_transit_routes::get_transit_routes),
-    )
-    .route(
-        "/transit-stops-for-route",
-        get(get_transit_stops_for_route::get_transit_stops_for_route),
-    )
-    .route(
-        "/transit-stops-at-location",
-        get(get_transit_stops_at_location::get_transit_stops_at_location),
-    )
-    .route(
-        "/location-search-autocomplete",
-        get(get_location_search_autocomplete::get_location_search_autocomplete),
-    )
-    .route("/audio", get(get_audio::get_audio))
+    .merge(audio_routes().route_layer(middleware::from_fn_with_state(
+        Scope::AudioRead,
+        require_scope,
+    )))
+    .merge(admin_routes().route_layer(middleware::from_fn_with_state(Scope::Admin, require_scope)))
+}
+
+fn arrivals_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/transit-arrival-times",
+            get(get_transit_arrival_times::get_transit_arrival_times),
+        )
+        .route(
+            "/transit-arrival-times/stream",
+            get(get_transit_arrival_times_stream::get_transit_arrival_times_stream),
+        )
+        .route(
+            "/transit-alerts",
+            get(get_transit_alerts::get_transit_alerts),
+        )
+        .route(
+            "/transit-routes",
+            get(get_transit_routes::get_transit_routes),
+        )
+        .route(
+            "/transit-stops-for-route",
+            get(get_transit_stops_for_route::get_transit_stops_for_route),
+        )
+        .route(
+            "/transit-route-shape",
+            get(get_transit_route_shape::get_transit_route_shape),
+        )
+        .route(
+            "/transit-stops-at-location",
+            get(get_transit_stops_at_location::get_transit_stops_at_location),
+        )
+        .route(
+            "/transit-departure-advice",
+            get(get_transit_departure_advice::get_transit_departure_advice),
+        )
+        .route(
+            "/transit-vehicles",
+            get(get_transit_vehicles::get_transit_vehicles),
+        )
+        .route(
+            "/location-search-autocomplete",
+            get(get_location_search_autocomplete::get_location_search_autocomplete),
+        )
+        .route("/me/favorites", get(get_me_favorites::get_me_favorites))
+        .route(
+            "/me/favorites/:name",
+            put(put_me_favorite::put_me_favorite).delete(delete_me_favorite::delete_me_favorite),
+        )
+        .route("/me/commutes", get(get_me_commutes::get_me_commutes))
+        .route(
+            "/me/commutes/:name",
+            put(put_me_commute::put_me_commute).delete(delete_me_commute::delete_me_commute),
+        )
+        .route(
+            "/me/commutes/:name/arrivals",
+            get(get_me_commute_arrivals::get_me_commute_arrivals),
+        )
+        .route(
+            "/me/notifications",
+            get(get_me_notifications::get_me_notifications),
+        )
+        .route(
+            "/me/notifications/:name",
+            put(put_me_notification::put_me_notification)
+                .delete(delete_me_notification::delete_me_notification),
+        )
+        .route("/ws", get(get_ws::get_ws))
+}
+
+fn audio_routes() -> Router<AppState> {
+    Router::new()
+        .route("/audio", get(get_audio::get_audio))
+        .route(
+            "/audio/arrivals",
+            get(get_audio_arrivals::get_audio_arrivals),
+        )
+}
+
+fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/transit-cache-stats",
+            get(get_transit_cache_stats::get_transit_cache_stats),
+        )
+        .route(
+            "/gtfs-static/reload",
+            post(post_gtfs_static_reload::post_gtfs_static_reload),
+        )
+        .route(
+            "/admin/keys",
+            get(get_admin_keys::get_admin_keys).post(post_admin_keys::post_admin_keys),
+        )
+        .route(
+            "/admin/keys/:id",
+            delete(delete_admin_key::delete_admin_key),
+        )
+        .route(
+            "/admin/keys/:id/rotate",
+            post(post_admin_key_rotate::post_admin_key_rotate),
+        )
 }
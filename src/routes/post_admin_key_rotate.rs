@@ -0,0 +1,34 @@
+use crate::{
+    routes::post_admin_keys::{IssuedKeyResponse, PostAdminKeysResponse},
+    services::api_keys::api_key_store::ApiKeyError,
+    types::app_state::AppState,
+    utils::app_error::AppError,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::error;
+
+pub async fn post_admin_key_rotate(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let issued = state.api_keys.rotate(&id).await.map_err(|e| match e {
+        ApiKeyError::NotFound => AppError::new(StatusCode::NOT_FOUND, "Key not found or inactive"),
+        _ => {
+            error!("Failed to rotate API key: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        }
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PostAdminKeysResponse {
+            data: IssuedKeyResponse::from(&issued),
+        }),
+    )
+        .into_response())
+}
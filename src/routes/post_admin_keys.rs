@@ -0,0 +1,166 @@
+use crate::{
+    routes::get_admin_keys::ApiKeyResponse,
+    services::api_keys::{api_key_service::IssuedKey, api_key_store::Scope},
+    types::app_state::AppState,
+    utils::{app_error::AppError, validated_json::ValidatedJson},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct PostAdminKeys {
+    /// e.g. the name of the client app the key is for.
+    #[validate(length(min = 1, max = 100, message = "Must be between 1 and 100 characters"))]
+    pub label: String,
+
+    #[validate(length(min = 1, message = "Must have at least 1 scope"))]
+    pub scopes: Vec<Scope>,
+
+    /// Leave unset for a key that lasts until revoked.
+    #[validate(range(min = 1, max = 3650, message = "Must be between 1 and 3650"))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// The token is only ever returned here and by rotation.
+#[derive(Serialize, Deserialize)]
+pub struct IssuedKeyResponse {
+    pub token: String,
+    pub key: ApiKeyResponse,
+}
+
+impl From<&IssuedKey> for IssuedKeyResponse {
+    fn from(i: &IssuedKey) -> Self {
+        IssuedKeyResponse {
+            token: i.token.clone(),
+            key: ApiKeyResponse::from(&i.key),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PostAdminKeysResponse {
+    pub data: IssuedKeyResponse,
+}
+
+pub async fn post_admin_keys(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PostAdminKeys>,
+) -> Result<Response, AppError> {
+    let issued = state
+        .api_keys
+        .issue(
+            &payload.label,
+            payload.scopes,
+            payload
+                .expires_in_days
+                .map(|days| Utc::now() + Duration::days(days)),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to issue API key: {}", e);
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(PostAdminKeysResponse {
+            data: IssuedKeyResponse::from(&issued),
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::app::gen_mock_app_with_auth;
+
+    use super::*;
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        key: Option<&str>,
+        body: &str,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(key) = key {
+            request = request.header("Temp-Authorization", key);
+        }
+
+        app.clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn issued(response: Response) -> IssuedKeyResponse {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<PostAdminKeysResponse>(&body)
+            .unwrap()
+            .data
+    }
+
+    #[tokio::test]
+    async fn manages_scoped_keys() {
+        let app = gen_mock_app_with_auth(Some("root"), None).await.app;
+
+        let response = send(&app, "GET", "/me/favorites", None, "").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = send(
+            &app,
+            "POST",
+            "/admin/keys",
+            Some("root"),
+            r#"{"label":"kiosk","scopes":["arrivals:read"]}"#,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let kiosk = issued(response).await;
+        assert_eq!(kiosk.key.scopes, [Scope::ArrivalsRead]);
+
+        let token = Some(kiosk.token.as_str());
+        let response = send(&app, "GET", "/me/favorites", token, "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(&app, "GET", "/audio?text=hi", token, "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send(&app, "GET", "/admin/keys", token, "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let uri = format!("/admin/keys/{}/rotate", kiosk.key.id);
+        let response = send(&app, "POST", &uri, Some("root"), "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let rotated = issued(response).await;
+
+        let response = send(&app, "GET", "/me/favorites", token, "").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let token = Some(rotated.token.as_str());
+        let response = send(&app, "GET", "/me/favorites", token, "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let uri = format!("/admin/keys/{}", kiosk.key.id);
+        let response = send(&app, "DELETE", &uri, Some("root"), "").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&app, "GET", "/me/favorites", token, "").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
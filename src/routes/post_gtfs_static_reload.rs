@@ -0,0 +1,227 @@
+use crate::{
+    services::gtfs_static::gtfs_static_store::{GtfsStaticConfig, GtfsStaticError},
+    types::app_state::AppState,
+    utils::{app_error::AppError, validated_json::ValidatedJson},
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use validator::Validate;
+
+/// Reloads the feed from the path it was loaded from at startup, `GTFS_STATIC_PATH`.
+#[derive(Validate, Deserialize)]
+pub struct PostGtfsStaticReloadPayload {
+    pub stop_id_prefix: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PostGtfsStaticReloadResponseData {
+    pub path: String,
+    pub agencies: usize,
+    pub routes: usize,
+    pub stops: usize,
+    pub trips: usize,
+    pub shapes: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PostGtfsStaticReloadResponse {
+    pub data: PostGtfsStaticReloadResponseData,
+}
+
+pub async fn post_gtfs_static_reload(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PostGtfsStaticReloadPayload>,
+) -> Result<Response, AppError> {
+    let config = match state.gtfs_static.config() {
+        Some(current) => GtfsStaticConfig {
+            path: current.path,
+            stop_id_prefix: payload.stop_id_prefix.or(current.stop_id_prefix),
+        },
+        None => {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                &GtfsStaticError::NotLoaded.to_string(),
+            ))
+        }
+    };
+
+    let path = config.path.display().to_string();
+    let summary = state.gtfs_static.load(config).await.map_err(|e| {
+        error!("Failed to reload GTFS static feed from {}: {}", path, e);
+        AppError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Failed to load GTFS static feed",
+        )
+    })?;
+
+    info!(
+        "Loaded GTFS static feed from {} with {} routes and {} stops",
+        path, summary.routes, summary.stops
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(PostGtfsStaticReloadResponse {
+            data: PostGtfsStaticReloadResponseData {
+                path,
+                agencies: summary.agencies,
+                routes: summary.routes,
+                stops: summary.stops,
+                trips: summary.trips,
+                shapes: summary.shapes,
+            },
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use crate::{
+        app::gen_mock_app,
+        routes::{
+            get_transit_routes::GetTransitRoutesResponse,
+            get_transit_stops_for_route::GetTransitStopsForRouteResponse,
+        },
+        services::gtfs_static::gtfs_static_store::tests::write_test_feed,
+    };
+
+    use super::*;
+
+    fn reload(key: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/gtfs-static/reload")
+            .header("Temp-Authorization", key)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_routes_and_stops_from_feed() {
+        let mock_app = gen_mock_app().await;
+        let admin = mock_app.issue_admin_key().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_test_feed(dir.path());
+
+        // as loaded at startup, prefixed with the agency ID
+        mock_app
+            .gtfs_static
+            .load(GtfsStaticConfig {
+                path,
+                stop_id_prefix: None,
+            })
+            .await
+            .unwrap();
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(reload(&admin, json!({ "stop_id_prefix": "MTA" })))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: PostGtfsStaticReloadResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.data.routes, 1);
+        assert_eq!(body.data.stops, 3);
+
+        // no mta mocks exist, so these can only be answered from the feed
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-routes?search=b6")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitRoutesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.data.routes.len(), 1);
+        assert_eq!(body.data.routes[0].id, "MTA NYCT_B63");
+
+        let response = mock_app
+            .app
+            .oneshot(
+                Request::builder()
+                    .uri("/transit-stops-for-route?route_id=MTA%20NYCT_B63")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetTransitStopsForRouteResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.data.groups.len(), 2);
+        assert_eq!(body.data.groups[0].name, "COBBLE HILL");
+        assert_eq!(body.data.groups[0].stops[0].id, "MTA_3");
+        assert_eq!(body.data.groups[0].stops[0].name, "5 AV/9 ST");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_feed() {
+        let mock_app = gen_mock_app().await;
+        let admin = mock_app.issue_admin_key().await;
+
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(reload(&admin, json!({})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_test_feed(dir.path());
+        mock_app
+            .gtfs_static
+            .load(GtfsStaticConfig {
+                path: path.clone(),
+                stop_id_prefix: Some("MTA".to_string()),
+            })
+            .await
+            .unwrap();
+        std::fs::write(&path, "not a zip").unwrap();
+
+        // the reason is logged rather than shown, and the last good feed keeps serving
+        let response = mock_app
+            .app
+            .clone()
+            .oneshot(reload(&admin, json!({})))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            json!({ "message": "Failed to load GTFS static feed" })
+        );
+        assert!(mock_app.gtfs_static.index().is_some());
+    }
+}
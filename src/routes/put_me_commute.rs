@@ -0,0 +1,90 @@
+use crate::{
+    routes::get_me_commutes::CommuteProfileResponse,
+    services::user_data::user_data_store::{CommuteProfile, UserDataError, MAX_NAME_LENGTH},
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity, validated_json::ValidatedJson},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct PutMeCommute {
+    #[validate(length(min = 1, max = 20, message = "Must have between 1 and 20 stops"))]
+    pub stop_ids: Vec<String>,
+
+    /// Only arrivals of these routes are returned. Empty keeps every route.
+    #[serde(default)]
+    #[validate(length(max = 20, message = "Must have at most 20 routes"))]
+    pub route_ids: Vec<String>,
+
+    #[validate(range(min = 1, max = 10, message = "Must be between 1 and 10"))]
+    pub limit_per_route: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PutMeCommuteResponse {
+    pub data: CommuteProfileResponse,
+}
+
+pub async fn put_me_commute(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+    Path(name): Path<String>,
+    ValidatedJson(payload): ValidatedJson<PutMeCommute>,
+) -> Result<Response, AppError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Name must be between 1 and {} characters", MAX_NAME_LENGTH).as_str(),
+        ));
+    }
+
+    // the IDs are joined back into the comma lists that arrivals are fetched with
+    if payload
+        .stop_ids
+        .iter()
+        .chain(payload.route_ids.iter())
+        .any(|id| id.trim().is_empty() || id.contains(','))
+    {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "IDs must not be empty or contain commas",
+        ));
+    }
+
+    let commute = CommuteProfile {
+        name,
+        stop_ids: payload.stop_ids,
+        route_ids: payload.route_ids,
+        limit_per_route: payload.limit_per_route,
+    };
+
+    state
+        .user_data
+        .save_commute(&client_id, commute.clone())
+        .await
+        .map_err(|e| match e {
+            UserDataError::LimitReached(_) => {
+                AppError::new(StatusCode::CONFLICT, e.to_string().as_str())
+            }
+            _ => {
+                error!("Failed to save commute: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PutMeCommuteResponse {
+            data: CommuteProfileResponse::from(&commute),
+        }),
+    )
+        .into_response())
+}
@@ -0,0 +1,160 @@
+use crate::{
+    routes::get_me_favorites::FavoriteResponse,
+    services::user_data::user_data_store::{
+        Favorite, FavoriteKind, UserDataError, MAX_NAME_LENGTH,
+    },
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity, validated_json::ValidatedJson},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct PutMeFavorite {
+    pub kind: FavoriteKind,
+
+    /// A stop or route ID, depending on `kind`.
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PutMeFavoriteResponse {
+    pub data: FavoriteResponse,
+}
+
+pub async fn put_me_favorite(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+    Path(name): Path<String>,
+    ValidatedJson(payload): ValidatedJson<PutMeFavorite>,
+) -> Result<Response, AppError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Name must be between 1 and {} characters", MAX_NAME_LENGTH).as_str(),
+        ));
+    }
+
+    let favorite = Favorite {
+        name,
+        kind: payload.kind,
+        id: payload.id,
+    };
+
+    state
+        .user_data
+        .save_favorite(&client_id, favorite.clone())
+        .await
+        .map_err(|e| match e {
+            UserDataError::LimitReached(_) => {
+                AppError::new(StatusCode::CONFLICT, e.to_string().as_str())
+            }
+            _ => {
+                error!("Failed to save favorite: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PutMeFavoriteResponse {
+            data: FavoriteResponse::from(&favorite),
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::{app::gen_mock_app, routes::get_me_favorites::GetMeFavoritesResponse};
+
+    use super::*;
+
+    async fn send(app: &Router, method: &str, uri: &str, key: &str, body: Body) -> Response {
+        app.clone()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Temp-Authorization", key)
+                    .header("content-type", "application/json")
+                    .body(body)
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    async fn favorites(app: &Router, key: &str) -> Vec<FavoriteResponse> {
+        let response = send(app, "GET", "/me/favorites", key, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: GetMeFavoritesResponse = serde_json::from_slice(&body).unwrap();
+        body.data.favorites
+    }
+
+    #[tokio::test]
+    async fn saves_favorites_per_key() {
+        let mock_app = gen_mock_app().await;
+        let (alice, bob) = (mock_app.issue_key().await, mock_app.issue_key().await);
+        let (alice, bob) = (alice.as_str(), bob.as_str());
+        let app = mock_app.app;
+
+        // a key of your own is required even with auth off
+        let response = send(&app, "GET", "/me/favorites", "unknown", Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = send(
+            &app,
+            "PUT",
+            "/me/favorites/Home",
+            alice,
+            Body::from(r#"{"kind":"stop","id":"MTA_308209"}"#),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(
+            favorites(&app, alice).await,
+            [FavoriteResponse {
+                name: "Home".to_string(),
+                kind: FavoriteKind::Stop,
+                id: "MTA_308209".to_string(),
+            }]
+        );
+        assert!(favorites(&app, bob).await.is_empty());
+
+        let response = send(
+            &app,
+            "PUT",
+            "/me/favorites/Home",
+            alice,
+            Body::from(r#"{"kind":"bus","id":"MTA_308209"}"#),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = send(&app, "DELETE", "/me/favorites/Home", bob, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send(&app, "DELETE", "/me/favorites/Home", alice, Body::empty()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(favorites(&app, alice).await.is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{
+    routes::get_me_notifications::NotificationRuleResponse,
+    services::{
+        notifications::webhook_sender::resolve_webhook_url,
+        user_data::user_data_store::{NotificationRule, UserDataError, MAX_NAME_LENGTH},
+    },
+    types::app_state::AppState,
+    utils::{app_error::AppError, client_identity::ClientIdentity, validated_json::ValidatedJson},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use validator::Validate;
+
+#[derive(Validate, Deserialize)]
+pub struct PutMeNotification {
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub stop_id: String,
+
+    #[validate(length(min = 1, message = "Must be at least 1 character"))]
+    pub route_id: String,
+
+    /// Fires once a bus is this many minutes or less from the stop.
+    #[validate(range(min = 1, max = 60, message = "Must be between 1 and 60"))]
+    pub minutes_before: i64,
+
+    #[validate(url(message = "Must be a URL"))]
+    pub webhook_url: String,
+
+    /// Keys the HMAC-SHA256 signature sent with every call.
+    #[validate(length(min = 16, message = "Must be at least 16 characters"))]
+    pub secret: String,
+
+    /// Defaults to a day.
+    #[validate(range(min = 1, max = 10080, message = "Must be between 1 and 10080"))]
+    pub expires_in_minutes: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PutMeNotificationResponse {
+    pub data: NotificationRuleResponse,
+}
+
+pub async fn put_me_notification(
+    State(state): State<AppState>,
+    ClientIdentity(client_id): ClientIdentity,
+    Path(name): Path<String>,
+    ValidatedJson(payload): ValidatedJson<PutMeNotification>,
+) -> Result<Response, AppError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Name must be between 1 and {} characters", MAX_NAME_LENGTH).as_str(),
+        ));
+    }
+
+    // checked again on every call, in case the host moves into a private network later
+    if let Err(e) = resolve_webhook_url(&payload.webhook_url, false).await {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            e.to_string().as_str(),
+        ));
+    }
+
+    let rule = NotificationRule {
+        name,
+        stop_id: payload.stop_id,
+        route_id: payload.route_id,
+        minutes_before: payload.minutes_before,
+        webhook_url: payload.webhook_url,
+        secret: payload.secret,
+        expires_at: Utc::now() + Duration::minutes(payload.expires_in_minutes.unwrap_or(1440)),
+    };
+
+    state
+        .user_data
+        .save_notification_rule(&client_id, rule.clone())
+        .await
+        .map_err(|e| match e {
+            UserDataError::LimitReached(_) => {
+                AppError::new(StatusCode::CONFLICT, e.to_string().as_str())
+            }
+            _ => {
+                error!("Failed to save notification rule: {}", e);
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error")
+            }
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PutMeNotificationResponse {
+            data: NotificationRuleResponse::from(&rule),
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    use crate::{app::gen_mock_app, routes::get_me_notifications::GetMeNotificationsResponse};
+
+    fn put(key: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri("/me/notifications/Morning")
+            .header("Temp-Authorization", key)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn registers_rules() {
+        let mock_app = gen_mock_app().await;
+        let alice = mock_app.issue_key().await;
+        let app = mock_app.app;
+
+        let response = app
+            .clone()
+            .oneshot(put(
+                &alice,
+                r#"{"stop_id":"MTA_308209","route_id":"MTA NYCT_B63","minutes_before":5,"webhook_url":"ftp://example.com/hook","secret":"0123456789abcdef"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // nor may webhooks reach into the server's own network
+        let response = app
+            .clone()
+            .oneshot(put(
+                &alice,
+                r#"{"stop_id":"MTA_308209","route_id":"MTA NYCT_B63","minutes_before":5,"webhook_url":"http://169.254.169.254/latest","secret":"0123456789abcdef"}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(put(
+                &alice,
+                r#"{"stop_id":"MTA_308209","route_id":"MTA NYCT_B63","minutes_before":5,"webhook_url":"https://93.184.215.14/hook","secret":"0123456789abcdef","expires_in_minutes":60}"#,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/me/notifications")
+                    .header("Temp-Authorization", &alice)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("0123456789abcdef"));
+
+        let body: GetMeNotificationsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.data.rules.len(), 1);
+        assert_eq!(body.data.rules[0].minutes_before, 5);
+        assert_eq!(body.data.rules[0].webhook_url, "https://93.184.215.14/hook");
+    }
+}
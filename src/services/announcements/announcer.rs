@@ -0,0 +1,297 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::services::transit_service::transit_service::StopInformation;
+
+use super::{
+    clip_library::{ClipError, ClipLibrary},
+    wav::{WavBuilder, WavClip, WavError},
+};
+
+const PAUSE_BETWEEN_ARRIVALS: Duration = Duration::from_millis(600);
+
+const PHRASES: [&str; 7] = [
+    "to",
+    "arriving_in",
+    "arriving_now",
+    "minute",
+    "minutes",
+    "hundred",
+    "no_arrivals",
+];
+
+#[derive(Debug)]
+pub enum AnnouncementError {
+    Clip(ClipError),
+    Wav(WavError),
+    /// There was nothing to join, which a complete library never leads to.
+    Empty,
+}
+
+impl fmt::Display for AnnouncementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnnouncementError::Clip(e) => write!(f, "{}", e),
+            AnnouncementError::Wav(e) => write!(f, "{}", e),
+            AnnouncementError::Empty => write!(f, "Announcement has no clips"),
+        }
+    }
+}
+
+impl From<ClipError> for AnnouncementError {
+    fn from(e: ClipError) -> Self {
+        AnnouncementError::Clip(e)
+    }
+}
+
+impl From<WavError> for AnnouncementError {
+    fn from(e: WavError) -> Self {
+        AnnouncementError::Wav(e)
+    }
+}
+
+/// Speaks arrivals by joining clips from a `ClipLibrary`, e.g. "B63 to Cobble Hill arriving in
+/// 4 minutes".
+///
+/// Besides `numbers/<0-20, 30, 40 .. 90>`, `letters/<a-z>` and the `phrases` `to`,
+/// `arriving_in`, `arriving_now`, `minute`, `minutes`, `hundred` and `no_arrivals`, the library may
+/// hold `routes/<label>` and `destinations/<name>` clips. Routes without a clip are spelled out
+/// and destinations without one are left out.
+#[derive(Clone)]
+pub struct Announcer {
+    library: ClipLibrary,
+}
+
+impl Announcer {
+    pub fn new(library: ClipLibrary) -> Self {
+        Announcer { library }
+    }
+
+    /// Fails on the first clip every announcement may need that is missing, or recorded in a
+    /// different format from the others.
+    pub async fn check(&self) -> Result<(), AnnouncementError> {
+        let numbers = (0..=20).chain((30..=90).step_by(10)).map(|n| n.to_string());
+        let letters = ('a'..='z').map(|c| c.to_string());
+        let mut builder = WavBuilder::default();
+
+        for (category, word) in numbers
+            .map(|n| ("numbers", n))
+            .chain(letters.map(|l| ("letters", l)))
+            .chain(PHRASES.iter().map(|p| ("phrases", p.to_string())))
+        {
+            builder.push(&*self.library.require(category, &word).await?)?;
+        }
+
+        Ok(())
+    }
+
+    /// The samples announcing `arrivals` in order.
+    pub async fn announce(
+        &self,
+        arrivals: &[StopInformation],
+    ) -> Result<WavClip, AnnouncementError> {
+        let mut builder = WavBuilder::default();
+
+        if arrivals.is_empty() {
+            builder.push(&*self.library.require("phrases", "no_arrivals").await?)?;
+        }
+
+        for arrival in arrivals {
+            builder.pause(PAUSE_BETWEEN_ARRIVALS);
+
+            for clip in self.arrival_clips(arrival).await? {
+                builder.push(&clip)?;
+            }
+        }
+
+        builder.finish().ok_or(AnnouncementError::Empty)
+    }
+
+    async fn arrival_clips(
+        &self,
+        arrival: &StopInformation,
+    ) -> Result<Vec<Arc<WavClip>>, AnnouncementError> {
+        let mut clips = self.route_clips(&arrival.route_label).await?;
+
+        if let Some(destination) = &arrival.destination_name {
+            if let Some(clip) = self.library.get("destinations", destination).await? {
+                clips.push(self.library.require("phrases", "to").await?);
+                clips.push(clip);
+            }
+        }
+
+        match arrival.minutes_until_arrival {
+            minutes if minutes <= 0 => {
+                clips.push(self.library.require("phrases", "arriving_now").await?);
+            }
+            minutes => {
+                clips.push(self.library.require("phrases", "arriving_in").await?);
+                clips.extend(self.number_clips(minutes as u64).await?);
+                clips.push(
+                    self.library
+                        .require("phrases", if minutes == 1 { "minute" } else { "minutes" })
+                        .await?,
+                );
+            }
+        }
+
+        Ok(clips)
+    }
+
+    /// A recorded route name when there is one, otherwise the letters and numbers of the label
+    /// so "B63" is read as "B sixty three".
+    async fn route_clips(&self, label: &str) -> Result<Vec<Arc<WavClip>>, AnnouncementError> {
+        if let Some(clip) = self.library.get("routes", label).await? {
+            return Ok(vec![clip]);
+        }
+
+        let mut clips = Vec::new();
+        let mut digits = String::new();
+
+        for c in label.chars().chain(std::iter::once(' ')) {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+
+            if !digits.is_empty() {
+                clips.extend(self.digit_run_clips(&digits).await?);
+                digits.clear();
+            }
+
+            if c.is_ascii_alphabetic() {
+                clips.push(self.library.require("letters", &c.to_string()).await?);
+            }
+        }
+
+        Ok(clips)
+    }
+
+    /// Short runs are read as a number, longer ones digit by digit.
+    async fn digit_run_clips(&self, digits: &str) -> Result<Vec<Arc<WavClip>>, AnnouncementError> {
+        match digits.len() <= 3 && !digits.starts_with('0') {
+            true => self.number_clips(digits.parse().unwrap()).await,
+            false => {
+                let mut clips = Vec::new();
+                for d in digits.chars() {
+                    clips.push(self.library.require("numbers", &d.to_string()).await?);
+                }
+                Ok(clips)
+            }
+        }
+    }
+
+    async fn number_clips(&self, n: u64) -> Result<Vec<Arc<WavClip>>, AnnouncementError> {
+        let mut clips = Vec::new();
+
+        for word in number_words(n) {
+            clips.push(match word.parse::<u64>() {
+                Ok(_) => self.library.require("numbers", &word).await?,
+                Err(_) => self.library.require("phrases", &word).await?,
+            });
+        }
+
+        Ok(clips)
+    }
+}
+
+/// The clips that read out `n`: numbers up to 20 and multiples of ten have their own, the rest
+/// are built from those. Anything from a thousand up is read digit by digit.
+fn number_words(n: u64) -> Vec<String> {
+    if n >= 1000 {
+        return n.to_string().chars().map(|d| d.to_string()).collect();
+    }
+
+    let mut words = Vec::new();
+    let (hundreds, rest) = (n / 100, n % 100);
+
+    if hundreds > 0 {
+        words.push(hundreds.to_string());
+        words.push("hundred".to_string());
+
+        if rest == 0 {
+            return words;
+        }
+    }
+
+    match rest {
+        0..=20 => words.push(rest.to_string()),
+        _ if rest % 10 == 0 => words.push(rest.to_string()),
+        _ => {
+            words.push((rest / 10 * 10).to_string());
+            words.push((rest % 10).to_string());
+        }
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::services::announcements::wav::WavFormat;
+
+    use super::*;
+
+    fn record(dir: &Path, name: &str, format: WavFormat) {
+        let path = dir.join(format!("{}.wav", name));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let mut bytes = format.header(2).to_vec();
+        bytes.extend_from_slice(&[0, 0]);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[tokio::test]
+    async fn checks_for_every_required_clip() {
+        let dir = tempfile::tempdir().unwrap();
+        let format = WavFormat {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+        };
+
+        for n in (0..=20).chain((30..=90).step_by(10)) {
+            record(dir.path(), &format!("numbers/{}", n), format);
+        }
+        for c in 'a'..='z' {
+            record(dir.path(), &format!("letters/{}", c), format);
+        }
+        for phrase in PHRASES {
+            record(dir.path(), &format!("phrases/{}", phrase), format);
+        }
+
+        let announcer = || Announcer::new(ClipLibrary::new(dir.path().to_path_buf()));
+        assert!(announcer().check().await.is_ok());
+
+        std::fs::remove_file(dir.path().join("phrases/arriving_now.wav")).unwrap();
+        assert!(matches!(
+            announcer().check().await,
+            Err(AnnouncementError::Clip(ClipError::Missing(name))) if name == "phrases/arriving_now"
+        ));
+
+        record(
+            dir.path(),
+            "phrases/arriving_now",
+            WavFormat {
+                sample_rate: 16000,
+                ..format
+            },
+        );
+        assert!(matches!(
+            announcer().check().await,
+            Err(AnnouncementError::Wav(_))
+        ));
+    }
+
+    #[test]
+    fn reads_numbers() {
+        assert_eq!(number_words(0), vec!["0"]);
+        assert_eq!(number_words(14), vec!["14"]);
+        assert_eq!(number_words(40), vec!["40"]);
+        assert_eq!(number_words(63), vec!["60", "3"]);
+        assert_eq!(number_words(300), vec!["3", "hundred"]);
+        assert_eq!(number_words(105), vec!["1", "hundred", "5"]);
+        assert_eq!(number_words(2024), vec!["2", "0", "2", "4"]);
+    }
+}
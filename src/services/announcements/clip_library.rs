@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use axum::body::Bytes;
+
+use super::wav::{WavClip, WavError};
+
+#[derive(Debug)]
+pub enum ClipError {
+    Missing(String),
+    Invalid(String, WavError),
+    Io(String, String),
+}
+
+impl fmt::Display for ClipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipError::Missing(name) => write!(f, "Missing clip {}", name),
+            ClipError::Invalid(name, e) => write!(f, "Clip {}: {}", name, e),
+            ClipError::Io(name, e) => write!(f, "Failed to read clip {}: {}", name, e),
+        }
+    }
+}
+
+/// Pre-recorded WAV clips named `<category>/<word>.wav` under one directory, e.g.
+/// `numbers/4.wav`, `routes/b63.wav` or `phrases/arriving_in.wav`. Clips are read on first use
+/// and kept in memory.
+#[derive(Clone)]
+pub struct ClipLibrary {
+    dir: PathBuf,
+    clips: Arc<RwLock<HashMap<String, Arc<WavClip>>>>,
+}
+
+impl ClipLibrary {
+    pub fn new(dir: PathBuf) -> Self {
+        ClipLibrary {
+            dir,
+            clips: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// The clip for `word`, or `None` when it hasn't been recorded.
+    pub async fn get(&self, category: &str, word: &str) -> Result<Option<Arc<WavClip>>, ClipError> {
+        let name = clip_name(category, word);
+
+        if let Some(clip) = self.clips.read().unwrap().get(&name) {
+            return Ok(Some(clip.clone()));
+        }
+
+        let bytes = match tokio::fs::read(self.dir.join(format!("{}.wav", name))).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ClipError::Io(name, e.to_string())),
+        };
+
+        let clip = Arc::new(
+            WavClip::parse(Bytes::from(bytes)).map_err(|e| ClipError::Invalid(name.clone(), e))?,
+        );
+        self.clips.write().unwrap().insert(name, clip.clone());
+
+        Ok(Some(clip))
+    }
+
+    pub async fn require(&self, category: &str, word: &str) -> Result<Arc<WavClip>, ClipError> {
+        self.get(category, word)
+            .await?
+            .ok_or_else(|| ClipError::Missing(clip_name(category, word)))
+    }
+}
+
+/// Lowercases `word` and collapses everything but letters and digits into `_`, which also keeps
+/// names from escaping the clip directory.
+pub fn clip_name(category: &str, word: &str) -> String {
+    let mut slug = String::new();
+
+    for c in word.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+
+    format!("{}/{}", category, slug.trim_end_matches('_'))
+}
@@ -0,0 +1,493 @@
+use axum::body::Bytes;
+
+use super::wav::{WavClip, WavError};
+
+const BLOCK_SIZE: usize = 4096;
+const MAX_FIXED_ORDER: usize = 4;
+/// Rice parameters take 4 bits and 15 is reserved as an escape code.
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Encodes PCM as FLAC: lossless, and far smaller than WAV for speech.
+///
+/// Each channel is coded independently with the best of the fixed predictors, falling back to
+/// verbatim samples when prediction doesn't help. The STREAMINFO MD5 is left unset, which
+/// decoders treat as unknown.
+pub fn encode_flac(clip: &WavClip) -> Result<Bytes, WavError> {
+    let format = clip.format;
+    let bits_per_sample = format.bits_per_sample as u32;
+
+    if !matches!(bits_per_sample, 8 | 16 | 24) || !(1..=8).contains(&format.channels) {
+        return Err(WavError::Unsupported(format!("{} as FLAC", format)));
+    }
+
+    let channels = format.channels as usize;
+    let bytes_per_sample = bits_per_sample as usize / 8;
+    let frame_len = channels * bytes_per_sample;
+    let total_samples = clip.data.len() / frame_len;
+
+    let mut writer = BitWriter::default();
+    writer.write_bytes(b"fLaC");
+
+    // STREAMINFO is the only, and so last, metadata block
+    writer.write(1, 1);
+    writer.write(0, 7);
+    writer.write(34, 24);
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(0, 24);
+    writer.write(0, 24);
+    writer.write(format.sample_rate as u64, 20);
+    writer.write(channels as u64 - 1, 3);
+    writer.write(bits_per_sample as u64 - 1, 5);
+    writer.write(total_samples as u64, 36);
+    writer.write_bytes(&[0; 16]);
+
+    let mut output = writer.finish();
+
+    for (frame_number, block) in clip.data[..total_samples * frame_len]
+        .chunks(BLOCK_SIZE * frame_len)
+        .enumerate()
+    {
+        let block_size = block.len() / frame_len;
+        let samples = (0..channels)
+            .map(|channel| {
+                (0..block_size)
+                    .map(|i| {
+                        let offset = i * frame_len + channel * bytes_per_sample;
+                        read_sample(&block[offset..offset + bytes_per_sample])
+                    })
+                    .collect::<Vec<i64>>()
+            })
+            .collect::<Vec<Vec<i64>>>();
+
+        output.extend(encode_frame(
+            frame_number as u64,
+            &samples,
+            channels,
+            bits_per_sample,
+        ));
+    }
+
+    Ok(Bytes::from(output))
+}
+
+/// Little endian PCM as a signed sample. 8 bit WAV is unsigned, FLAC is always signed.
+fn read_sample(bytes: &[u8]) -> i64 {
+    match bytes.len() {
+        1 => bytes[0] as i64 - 128,
+        2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+        _ => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as i64,
+    }
+}
+
+fn encode_frame(
+    frame_number: u64,
+    samples: &[Vec<i64>],
+    channels: usize,
+    bits_per_sample: u32,
+) -> Vec<u8> {
+    let block_size = samples[0].len();
+    let mut writer = BitWriter::default();
+
+    writer.write(0b11111111111110, 14);
+    writer.write(0, 1);
+    // fixed block size
+    writer.write(0, 1);
+    // block size - 1 follows as 16 bits
+    writer.write(0b0111, 4);
+    // sample rate and sample size come from STREAMINFO
+    writer.write(0, 4);
+    writer.write(channels as u64 - 1, 4);
+    writer.write(0, 3);
+    writer.write(0, 1);
+    writer.write_bytes(&utf8_number(frame_number));
+    writer.write(block_size as u64 - 1, 16);
+
+    let header = writer.finish();
+    let crc = crc8(&header);
+
+    let mut writer = BitWriter::default();
+    writer.write_bytes(&header);
+    writer.write(crc as u64, 8);
+
+    for channel in samples {
+        write_subframe(&mut writer, channel, bits_per_sample);
+    }
+
+    let mut frame = writer.finish();
+    let crc = crc16(&frame);
+    frame.extend_from_slice(&crc.to_be_bytes());
+
+    frame
+}
+
+fn write_subframe(writer: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    let verbatim_bits = samples.len() as u64 * bits_per_sample as u64;
+
+    if samples.iter().all(|s| *s == samples[0]) {
+        writer.write(0, 1);
+        writer.write(0b000000, 6);
+        writer.write(0, 1);
+        writer.write_signed(samples[0], bits_per_sample);
+        return;
+    }
+
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .filter_map(|order| {
+            let residuals = fixed_residuals(samples, order);
+            let (parameter, bits) = rice_parameter(&residuals)?;
+            let bits = bits + order as u64 * bits_per_sample as u64 + 10;
+
+            Some((order, residuals, parameter, bits))
+        })
+        .min_by_key(|(_, _, _, bits)| *bits);
+
+    match best {
+        Some((order, residuals, parameter, bits)) if bits < verbatim_bits => {
+            writer.write(0, 1);
+            writer.write(0b001000 | order as u64, 6);
+            writer.write(0, 1);
+
+            for sample in &samples[..order] {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+
+            // Rice coding with 4 bit parameters in a single partition
+            writer.write(0, 2);
+            writer.write(0, 4);
+            writer.write(parameter as u64, 4);
+
+            for residual in residuals {
+                let folded = fold(residual);
+                writer.write_unary(folded >> parameter);
+                writer.write(folded & ((1 << parameter) - 1), parameter);
+            }
+        }
+        _ => {
+            writer.write(0, 1);
+            writer.write(0b000001, 6);
+            writer.write(0, 1);
+
+            for sample in samples {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+        }
+    }
+}
+
+/// The residuals left by the fixed polynomial predictor of `order`, which is that many rounds of
+/// differencing.
+fn fixed_residuals(samples: &[i64], order: usize) -> Vec<i64> {
+    let mut residuals = samples.to_vec();
+
+    for _ in 0..order {
+        residuals = residuals.windows(2).map(|w| w[1] - w[0]).collect();
+    }
+
+    residuals
+}
+
+fn fold(residual: i64) -> u64 {
+    match residual >= 0 {
+        true => (residual as u64) << 1,
+        false => ((-residual as u64) << 1) - 1,
+    }
+}
+
+/// The cheapest Rice parameter for `residuals` and the bits they take with it, or `None` when
+/// they are too large to code.
+fn rice_parameter(residuals: &[i64]) -> Option<(u32, u64)> {
+    let folded = residuals.iter().map(|r| fold(*r)).collect::<Vec<u64>>();
+
+    (0..=MAX_RICE_PARAMETER)
+        .map(|parameter| {
+            let bits = folded
+                .iter()
+                .map(|f| (f >> parameter) + 1 + parameter as u64)
+                .sum::<u64>();
+
+            (parameter, bits)
+        })
+        .min_by_key(|(_, bits)| *bits)
+        // keep the unary part of any one residual to a sane length
+        .filter(|(parameter, _)| folded.iter().all(|f| f >> parameter < 1 << 16))
+}
+
+/// Frame numbers use the same variable length coding as UTF-8.
+fn utf8_number(n: u64) -> Vec<u8> {
+    if n < 0x80 {
+        return vec![n as u8];
+    }
+
+    let continuation_bytes = match n {
+        _ if n < 0x800 => 1,
+        _ if n < 0x10000 => 2,
+        _ if n < 0x200000 => 3,
+        _ if n < 0x4000000 => 4,
+        _ => 5,
+    };
+
+    let mut bytes = vec![0u8; continuation_bytes + 1];
+    for i in (1..=continuation_bytes).rev() {
+        bytes[i] = 0x80 | ((n >> (6 * (continuation_bytes - i))) & 0x3f) as u8;
+    }
+
+    let lead_marker = (0xff00u16 >> (continuation_bytes + 1)) as u8;
+    bytes[0] = lead_marker | (n >> (6 * continuation_bytes)) as u8;
+
+    bytes
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.accumulator = (self.accumulator << 1) | ((value >> i) & 1);
+            self.bits += 1;
+
+            if self.bits == 8 {
+                self.bytes.push(self.accumulator as u8);
+                self.accumulator = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn write_unary(&mut self, zeros: u64) {
+        for _ in 0..zeros {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write(*byte as u64, 8);
+        }
+    }
+
+    /// The written bytes, zero padded to a whole byte.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::announcements::wav::WavFormat;
+
+    /// Just enough of a decoder to read back what `encode_flac` writes.
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+    }
+
+    impl BitReader<'_> {
+        fn read(&mut self, bits: u32) -> u64 {
+            (0..bits).fold(0, |value, _| {
+                let bit = (self.bytes[self.position / 8] >> (7 - self.position % 8)) & 1;
+                self.position += 1;
+                (value << 1) | bit as u64
+            })
+        }
+
+        fn read_signed(&mut self, bits: u32) -> i64 {
+            let value = self.read(bits) as i64;
+            (value << (64 - bits)) >> (64 - bits)
+        }
+
+        fn align(&mut self) {
+            self.position = self.position.div_ceil(8) * 8;
+        }
+    }
+
+    fn decode(flac: &[u8], channels: usize, bits_per_sample: u32) -> Vec<Vec<i64>> {
+        assert_eq!(&flac[..4], b"fLaC");
+
+        let mut reader = BitReader {
+            bytes: flac,
+            position: (4 + 4 + 34) * 8,
+        };
+        let mut output = vec![Vec::new(); channels];
+
+        while reader.position / 8 < flac.len() {
+            let start = reader.position / 8;
+
+            assert_eq!(reader.read(14), 0b11111111111110);
+            reader.read(2 + 4 + 4 + 4 + 3 + 1);
+            // frame numbers stay below 128 in these tests
+            reader.read(8);
+            let block_size = reader.read(16) as usize + 1;
+            assert_eq!(
+                crc8(&flac[start..reader.position / 8]),
+                reader.read(8) as u8
+            );
+
+            for samples in output.iter_mut() {
+                reader.read(1);
+                let kind = reader.read(6);
+                reader.read(1);
+
+                match kind {
+                    0 => {
+                        let value = reader.read_signed(bits_per_sample);
+                        samples.extend(std::iter::repeat_n(value, block_size));
+                    }
+                    1 => {
+                        for _ in 0..block_size {
+                            samples.push(reader.read_signed(bits_per_sample));
+                        }
+                    }
+                    _ => {
+                        let order = (kind & 0b111) as usize;
+                        let mut decoded = (0..order)
+                            .map(|_| reader.read_signed(bits_per_sample))
+                            .collect::<Vec<i64>>();
+
+                        reader.read(2 + 4);
+                        let parameter = reader.read(4) as u32;
+
+                        let mut residuals = Vec::new();
+                        for _ in order..block_size {
+                            let mut quotient = 0;
+                            while reader.read(1) == 0 {
+                                quotient += 1;
+                            }
+                            let folded = (quotient << parameter) | reader.read(parameter);
+                            residuals.push(match folded & 1 {
+                                0 => (folded >> 1) as i64,
+                                _ => -((folded >> 1) as i64) - 1,
+                            });
+                        }
+
+                        // undo the differencing one order at a time
+                        for level in (0..order).rev() {
+                            let mut value = fixed_residuals(&decoded, level)[0];
+                            let mut integrated = vec![value];
+                            for residual in &residuals {
+                                value += residual;
+                                integrated.push(value);
+                            }
+                            residuals = integrated;
+                        }
+
+                        decoded.truncate(0);
+                        decoded.extend(residuals);
+                        samples.extend(decoded);
+                    }
+                }
+            }
+
+            reader.align();
+            let end = reader.position / 8;
+            assert_eq!(crc16(&flac[start..end]), reader.read(16) as u16);
+        }
+
+        output
+    }
+
+    fn pcm16(samples: &[i16]) -> Bytes {
+        Bytes::from(
+            samples
+                .iter()
+                .flat_map(|s| s.to_le_bytes())
+                .collect::<Vec<u8>>(),
+        )
+    }
+
+    #[test]
+    fn round_trips_samples() {
+        // a tone long enough to span several frames, with a burst of noise in the middle
+        let left = (0..10_000)
+            .map(|i| match i {
+                5000..=5100 => ((i * 7919) % 65536 - 32768) as i16,
+                _ => ((i as f64 / 10.0).sin() * 8000.0) as i16,
+            })
+            .collect::<Vec<i16>>();
+        let right = vec![0i16; left.len()];
+
+        let interleaved = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(l, r)| [*l, *r])
+            .collect::<Vec<i16>>();
+
+        let clip = WavClip {
+            format: WavFormat {
+                channels: 2,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+            },
+            data: pcm16(&interleaved),
+        };
+
+        let flac = encode_flac(&clip).unwrap();
+        let decoded = decode(&flac, 2, 16);
+
+        assert!(flac.len() < clip.data.len() / 2);
+        assert_eq!(
+            decoded[0],
+            left.iter().map(|s| *s as i64).collect::<Vec<i64>>()
+        );
+        assert_eq!(
+            decoded[1],
+            right.iter().map(|s| *s as i64).collect::<Vec<i64>>()
+        );
+    }
+
+    #[test]
+    fn reads_unsigned_8_bit() {
+        let clip = WavClip {
+            format: WavFormat {
+                channels: 1,
+                sample_rate: 8000,
+                bits_per_sample: 8,
+            },
+            data: Bytes::from(vec![0u8, 128, 255, 128]),
+        };
+
+        let decoded = decode(&encode_flac(&clip).unwrap(), 1, 8);
+
+        assert_eq!(decoded[0], vec![-128, 0, 127, 0]);
+    }
+
+    #[test]
+    fn codes_frame_numbers() {
+        assert_eq!(utf8_number(0x7f), vec![0x7f]);
+        assert_eq!(utf8_number(0x80), vec![0xc2, 0x80]);
+        assert_eq!(utf8_number(0x20ac), vec![0xe2, 0x82, 0xac]);
+    }
+}
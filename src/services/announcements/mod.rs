@@ -0,0 +1,4 @@
+pub mod announcer;
+pub mod clip_library;
+pub mod flac;
+pub mod wav;
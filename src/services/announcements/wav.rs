@@ -0,0 +1,299 @@
+use std::{fmt, time::Duration};
+
+use axum::body::Bytes;
+
+use crate::utils::audio_format::AudioFormat;
+
+use super::flac::encode_flac;
+
+const PCM: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum WavError {
+    Invalid(String),
+    Unsupported(String),
+    FormatMismatch {
+        expected: WavFormat,
+        found: WavFormat,
+    },
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WavError::Invalid(reason) => write!(f, "Invalid WAV: {}", reason),
+            WavError::Unsupported(reason) => write!(f, "Unsupported WAV: {}", reason),
+            WavError::FormatMismatch { expected, found } => {
+                write!(f, "Expected {} but found {}", expected, found)
+            }
+        }
+    }
+}
+
+/// The sample layout of uncompressed PCM audio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WavFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl fmt::Display for WavFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} channel {}Hz {} bit PCM",
+            self.channels, self.sample_rate, self.bits_per_sample
+        )
+    }
+}
+
+impl WavFormat {
+    fn block_align(&self) -> u16 {
+        self.channels * self.bits_per_sample.div_ceil(8)
+    }
+
+    fn byte_rate(&self) -> u32 {
+        self.sample_rate * self.block_align() as u32
+    }
+
+    /// The 44 byte RIFF header for `data_len` bytes of samples.
+    pub fn header(&self, data_len: u32) -> Bytes {
+        let mut header = Vec::with_capacity(44);
+
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(36 + data_len).to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&PCM.to_le_bytes());
+        header.extend_from_slice(&self.channels.to_le_bytes());
+        header.extend_from_slice(&self.sample_rate.to_le_bytes());
+        header.extend_from_slice(&self.byte_rate().to_le_bytes());
+        header.extend_from_slice(&self.block_align().to_le_bytes());
+        header.extend_from_slice(&self.bits_per_sample.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_len.to_le_bytes());
+
+        Bytes::from(header)
+    }
+
+    pub fn silence(&self, duration: Duration) -> Bytes {
+        let frames = (self.sample_rate as u128 * duration.as_millis() / 1000) as usize;
+        // 8 bit PCM is unsigned, so its silence is the midpoint rather than zero
+        let value = match self.bits_per_sample {
+            8 => 0x80,
+            _ => 0,
+        };
+
+        Bytes::from(vec![value; frames * self.block_align() as usize])
+    }
+}
+
+/// The samples of a PCM WAV file.
+#[derive(Clone, Debug)]
+pub struct WavClip {
+    pub format: WavFormat,
+    pub data: Bytes,
+}
+
+impl WavClip {
+    pub fn parse(bytes: Bytes) -> Result<WavClip, WavError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(WavError::Invalid("missing RIFF/WAVE header".to_string()));
+        }
+
+        let mut format = None;
+        let mut offset = 12;
+
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let len =
+                u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let start = offset + 8;
+            let end = start + len;
+
+            if end > bytes.len() {
+                return Err(WavError::Invalid(format!(
+                    "{} chunk runs past the end of the file",
+                    String::from_utf8_lossy(id)
+                )));
+            }
+
+            match id {
+                b"fmt " => format = Some(parse_format(&bytes[start..end])?),
+                b"data" => {
+                    let format = format
+                        .ok_or(WavError::Invalid("data chunk before fmt chunk".to_string()))?;
+
+                    // drop any partial frame so clips can be joined without shifting channels
+                    let frames = len / format.block_align() as usize;
+
+                    return Ok(WavClip {
+                        format,
+                        data: bytes.slice(start..start + frames * format.block_align() as usize),
+                    });
+                }
+                _ => {}
+            }
+
+            // chunks are padded to an even length
+            offset = end + len % 2;
+        }
+
+        Err(WavError::Invalid("missing data chunk".to_string()))
+    }
+
+    pub fn encode(&self, format: AudioFormat) -> Result<Bytes, WavError> {
+        match format {
+            AudioFormat::Wav => Ok(Bytes::from(
+                [
+                    self.format.header(self.data.len() as u32),
+                    self.data.clone(),
+                ]
+                .concat(),
+            )),
+            AudioFormat::Flac => encode_flac(self),
+        }
+    }
+}
+
+fn parse_format(chunk: &[u8]) -> Result<WavFormat, WavError> {
+    if chunk.len() < 16 {
+        return Err(WavError::Invalid("fmt chunk is too short".to_string()));
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+
+    let audio_format = u16_at(0);
+    if audio_format != PCM {
+        return Err(WavError::Unsupported(format!(
+            "audio format {} is not PCM",
+            audio_format
+        )));
+    }
+
+    let format = WavFormat {
+        channels: u16_at(2),
+        sample_rate: u32::from_le_bytes(chunk[4..8].try_into().unwrap()),
+        bits_per_sample: u16_at(14),
+    };
+
+    if format.channels == 0 || format.bits_per_sample == 0 {
+        return Err(WavError::Invalid(format!("{} has no samples", format)));
+    }
+
+    Ok(format)
+}
+
+/// Joins clips of one sample format into a single WAV.
+#[derive(Default)]
+pub struct WavBuilder {
+    format: Option<WavFormat>,
+    chunks: Vec<Bytes>,
+}
+
+impl WavBuilder {
+    pub fn push(&mut self, clip: &WavClip) -> Result<(), WavError> {
+        match self.format {
+            Some(expected) if expected != clip.format => {
+                return Err(WavError::FormatMismatch {
+                    expected,
+                    found: clip.format,
+                })
+            }
+            _ => self.format = Some(clip.format),
+        }
+
+        self.chunks.push(clip.data.clone());
+
+        Ok(())
+    }
+
+    /// Adds a pause after the clips pushed so far. Does nothing before the first clip.
+    pub fn pause(&mut self, duration: Duration) {
+        if let Some(format) = self.format {
+            self.chunks.push(format.silence(duration));
+        }
+    }
+
+    /// Every clip joined in order. `None` when nothing was pushed.
+    pub fn finish(self) -> Option<WavClip> {
+        Some(WavClip {
+            format: self.format?,
+            data: Bytes::from(self.chunks.concat()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: WavFormat = WavFormat {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+    };
+
+    fn clip(format: WavFormat, data: &[u8]) -> Bytes {
+        let mut bytes = format.header(data.len() as u32).to_vec();
+        bytes.extend_from_slice(data);
+        Bytes::from(bytes)
+    }
+
+    #[test]
+    fn joins_clips() {
+        let mut builder = WavBuilder::default();
+
+        builder
+            .push(&WavClip::parse(clip(FORMAT, &[1, 0, 2, 0])).unwrap())
+            .unwrap();
+        builder.pause(Duration::from_millis(1));
+        builder
+            .push(&WavClip::parse(clip(FORMAT, &[3, 0])).unwrap())
+            .unwrap();
+
+        let joined = builder.finish().unwrap().encode(AudioFormat::Wav).unwrap();
+        let parsed = WavClip::parse(joined).unwrap();
+
+        assert_eq!(parsed.format, FORMAT);
+        // 8 samples of silence sit between the clips
+        assert_eq!(parsed.data.len(), 4 + 16 + 2);
+        assert_eq!(&parsed.data[..4], &[1, 0, 2, 0]);
+        assert_eq!(&parsed.data[20..], &[3, 0]);
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let mut bytes = clip(FORMAT, &[]).to_vec();
+        bytes.truncate(36);
+        bytes.extend_from_slice(b"LIST\x03\x00\x00\x00abc\x00");
+        bytes.extend_from_slice(b"data\x02\x00\x00\x00\x05\x00");
+
+        let parsed = WavClip::parse(Bytes::from(bytes)).unwrap();
+
+        assert_eq!(&parsed.data[..], &[5, 0]);
+    }
+
+    #[test]
+    fn rejects_mismatched_formats() {
+        let stereo = WavFormat {
+            channels: 2,
+            ..FORMAT
+        };
+        let mut builder = WavBuilder::default();
+
+        builder
+            .push(&WavClip::parse(clip(FORMAT, &[1, 0])).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            builder.push(&WavClip::parse(clip(stereo, &[1, 0, 2, 0])).unwrap()),
+            Err(WavError::FormatMismatch {
+                expected: FORMAT,
+                found: stereo
+            })
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use super::api_key_store::{ApiKey, ApiKeyError, ApiKeyStore, Scope};
+
+const TOKEN_PREFIX: &str = "ow";
+const ID_LENGTH: usize = 12;
+const SECRET_LENGTH: usize = 40;
+
+/// A key along with the token to hand to its client, which cannot be recovered later.
+pub struct IssuedKey {
+    pub key: ApiKey,
+    pub token: String,
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn token(id: &str, secret: &str) -> String {
+    format!("{}_{}_{}", TOKEN_PREFIX, id, secret)
+}
+
+/// Issues and checks the `ow_<id>_<secret>` tokens clients authenticate with.
+#[derive(Clone)]
+pub struct ApiKeyService {
+    store: Arc<dyn ApiKeyStore>,
+}
+
+impl ApiKeyService {
+    pub fn new(store: Arc<dyn ApiKeyStore>) -> Self {
+        ApiKeyService { store }
+    }
+
+    pub async fn issue(
+        &self,
+        label: &str,
+        scopes: Vec<Scope>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<IssuedKey, ApiKeyError> {
+        let id = random_string(ID_LENGTH);
+        let secret = random_string(SECRET_LENGTH);
+        let key = ApiKey {
+            id: id.clone(),
+            label: label.to_string(),
+            scopes,
+            secret_hash: hash_secret(&secret),
+            created_at: Utc::now(),
+            expires_at,
+            revoked_at: None,
+        };
+
+        self.store.insert(key.clone()).await?;
+
+        Ok(IssuedKey {
+            key,
+            token: token(&id, &secret),
+        })
+    }
+
+    /// The active key `token` belongs to, if any.
+    pub async fn authenticate(&self, token: &str) -> Result<Option<ApiKey>, ApiKeyError> {
+        let mut parts = token.splitn(3, '_');
+        let (Some(TOKEN_PREFIX), Some(id), Some(secret)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+
+        let key = match self.store.get(id).await {
+            Ok(key) => key,
+            Err(ApiKeyError::NotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let matches: bool = hash_secret(secret)
+            .as_bytes()
+            .ct_eq(key.secret_hash.as_bytes())
+            .into();
+
+        Ok((matches && key.is_active(Utc::now())).then_some(key))
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKey>, ApiKeyError> {
+        self.store.list().await
+    }
+
+    /// Replaces the secret of an active key, so that its old token stops working at once while
+    /// its ID, and whatever clients saved under it, stay the same.
+    pub async fn rotate(&self, id: &str) -> Result<IssuedKey, ApiKeyError> {
+        let mut key = self.store.get(id).await?;
+        if !key.is_active(Utc::now()) {
+            return Err(ApiKeyError::NotFound);
+        }
+
+        let secret = random_string(SECRET_LENGTH);
+        key.secret_hash = hash_secret(&secret);
+        self.store.set_secret_hash(id, &key.secret_hash).await?;
+
+        Ok(IssuedKey {
+            key,
+            token: token(id, &secret),
+        })
+    }
+
+    pub async fn revoke(&self, id: &str) -> Result<(), ApiKeyError> {
+        self.store.revoke(id, Utc::now()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::services::api_keys::sqlite_api_key_store::SqliteApiKeyStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn authenticates_active_keys() {
+        let service = ApiKeyService::new(Arc::new(SqliteApiKeyStore::open_in_memory().unwrap()));
+
+        let issued = service
+            .issue("kiosk", vec![Scope::ArrivalsRead], None)
+            .await
+            .unwrap();
+        let key = service.authenticate(&issued.token).await.unwrap().unwrap();
+        assert_eq!(key.label, "kiosk");
+        assert_eq!(key.scopes, [Scope::ArrivalsRead]);
+
+        let forged = format!("{}x", issued.token);
+        assert!(service.authenticate(&forged).await.unwrap().is_none());
+        assert!(service
+            .authenticate("ow_nope_nope")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.authenticate("garbage").await.unwrap().is_none());
+
+        let rotated = service.rotate(&issued.key.id).await.unwrap();
+        assert_eq!(rotated.key.id, issued.key.id);
+        assert!(service.authenticate(&issued.token).await.unwrap().is_none());
+        assert!(service
+            .authenticate(&rotated.token)
+            .await
+            .unwrap()
+            .is_some());
+
+        service.revoke(&issued.key.id).await.unwrap();
+        assert!(service
+            .authenticate(&rotated.token)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            service.rotate(&issued.key.id).await,
+            Err(ApiKeyError::NotFound)
+        ));
+
+        let expired = service
+            .issue("old", Scope::all(), Some(Utc::now() - Duration::minutes(1)))
+            .await
+            .unwrap();
+        assert!(service
+            .authenticate(&expired.token)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(service.list().await.unwrap().len(), 2);
+    }
+}
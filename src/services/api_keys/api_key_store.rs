@@ -0,0 +1,83 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Scope {
+    #[serde(rename = "arrivals:read")]
+    ArrivalsRead,
+    #[serde(rename = "audio:read")]
+    AudioRead,
+    /// Manages keys and the server itself.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn all() -> Vec<Scope> {
+        vec![Scope::ArrivalsRead, Scope::AudioRead, Scope::Admin]
+    }
+
+    /// What requests without a known credential may do while auth is off.
+    pub fn public() -> Vec<Scope> {
+        vec![Scope::ArrivalsRead, Scope::AudioRead]
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "arrivals:read" => Some(Scope::ArrivalsRead),
+            "audio:read" => Some(Scope::AudioRead),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// A key as stored. Only a hash of its secret is kept, so the secret is shown once when the key
+/// is issued or rotated.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ApiKey {
+    pub id: String,
+    pub label: String,
+    pub scopes: Vec<Scope>,
+    /// Hex SHA-256 of the secret.
+    pub secret_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.map_or(true, |expires_at| expires_at > now)
+    }
+}
+
+#[derive(Debug)]
+pub enum ApiKeyError {
+    NotFound,
+    Internal(String),
+}
+
+impl std::fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ApiKeyError::NotFound => write!(f, "Key not found"),
+            ApiKeyError::Internal(e) => write!(f, "Internal error: {}", e),
+        }
+    }
+}
+
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn insert(&self, key: ApiKey) -> Result<(), ApiKeyError>;
+
+    async fn get(&self, id: &str) -> Result<ApiKey, ApiKeyError>;
+
+    /// Every key including revoked and expired ones, oldest first.
+    async fn list(&self) -> Result<Vec<ApiKey>, ApiKeyError>;
+
+    async fn set_secret_hash(&self, id: &str, secret_hash: &str) -> Result<(), ApiKeyError>;
+
+    async fn revoke(&self, id: &str, at: DateTime<Utc>) -> Result<(), ApiKeyError>;
+}
@@ -0,0 +1,3 @@
+pub mod api_key_service;
+pub mod api_key_store;
+pub mod sqlite_api_key_store;
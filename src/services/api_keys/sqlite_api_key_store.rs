@@ -0,0 +1,199 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::api_key_store::{ApiKey, ApiKeyError, ApiKeyStore, Scope};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    label TEXT NOT NULL,
+    scopes TEXT NOT NULL,
+    secret_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    revoked_at INTEGER
+);
+";
+
+const COLUMNS: &str = "id, label, scopes, secret_hash, created_at, expires_at, revoked_at";
+
+type ApiKeyRow = (
+    String,
+    String,
+    String,
+    String,
+    i64,
+    Option<i64>,
+    Option<i64>,
+);
+
+fn key_from_row(
+    (id, label, scopes, secret_hash, created_at, expires_at, revoked_at): ApiKeyRow,
+) -> Result<ApiKey, ApiKeyError> {
+    let time = |secs: i64| DateTime::from_timestamp(secs, 0).unwrap_or_default();
+
+    Ok(ApiKey {
+        id,
+        label,
+        scopes: serde_json::from_str::<Vec<Scope>>(&scopes)
+            .map_err(|e| ApiKeyError::Internal(e.to_string()))?,
+        secret_hash,
+        created_at: time(created_at),
+        expires_at: expires_at.map(time),
+        revoked_at: revoked_at.map(time),
+    })
+}
+
+fn read_row(row: &rusqlite::Row) -> rusqlite::Result<ApiKeyRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+fn updated(rows: usize) -> Result<(), ApiKeyError> {
+    match rows {
+        0 => Err(ApiKeyError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+/// Keeps API keys in SQLite, with timestamps as unix seconds.
+#[derive(Clone)]
+pub struct SqliteApiKeyStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteApiKeyStore {
+    /// Opens the database at `path`, creating it and its table when missing.
+    pub fn open(path: &Path) -> Result<Self, ApiKeyError> {
+        Self::init(Connection::open(path))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, ApiKeyError> {
+        Self::init(Connection::open_in_memory())
+    }
+
+    fn init(connection: rusqlite::Result<Connection>) -> Result<Self, ApiKeyError> {
+        let connection = connection.map_err(|e| ApiKeyError::Internal(e.to_string()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| ApiKeyError::Internal(e.to_string()))?;
+
+        Ok(SqliteApiKeyStore {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<T, F>(&self, query: F) -> Result<T, ApiKeyError>
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || query(&connection.lock().unwrap()))
+            .await
+            .map_err(|e| ApiKeyError::Internal(e.to_string()))?
+            .map_err(|e| ApiKeyError::Internal(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for SqliteApiKeyStore {
+    async fn insert(&self, key: ApiKey) -> Result<(), ApiKeyError> {
+        let scopes = serde_json::to_string(&key.scopes).unwrap();
+
+        self.run(move |c| {
+            c.execute(
+                &format!(
+                    "INSERT INTO api_keys ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    COLUMNS
+                ),
+                params![
+                    key.id,
+                    key.label,
+                    scopes,
+                    key.secret_hash,
+                    key.created_at.timestamp(),
+                    key.expires_at.map(|t| t.timestamp()),
+                    key.revoked_at.map(|t| t.timestamp())
+                ],
+            )
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn get(&self, id: &str) -> Result<ApiKey, ApiKeyError> {
+        let id = id.to_string();
+
+        let row = self
+            .run(move |c| {
+                c.query_row(
+                    &format!("SELECT {} FROM api_keys WHERE id = ?1", COLUMNS),
+                    params![id],
+                    read_row,
+                )
+                .optional()
+            })
+            .await?;
+
+        key_from_row(row.ok_or(ApiKeyError::NotFound)?)
+    }
+
+    async fn list(&self) -> Result<Vec<ApiKey>, ApiKeyError> {
+        self.run(move |c| {
+            c.prepare(&format!(
+                "SELECT {} FROM api_keys ORDER BY created_at, id",
+                COLUMNS
+            ))?
+            .query_map([], read_row)?
+            .collect::<rusqlite::Result<Vec<ApiKeyRow>>>()
+        })
+        .await?
+        .into_iter()
+        .map(key_from_row)
+        .collect()
+    }
+
+    async fn set_secret_hash(&self, id: &str, secret_hash: &str) -> Result<(), ApiKeyError> {
+        let (id, secret_hash) = (id.to_string(), secret_hash.to_string());
+
+        updated(
+            self.run(move |c| {
+                c.execute(
+                    "UPDATE api_keys SET secret_hash = ?2 WHERE id = ?1",
+                    params![id, secret_hash],
+                )
+            })
+            .await?,
+        )
+    }
+
+    async fn revoke(&self, id: &str, at: DateTime<Utc>) -> Result<(), ApiKeyError> {
+        let id = id.to_string();
+
+        updated(
+            self.run(move |c| {
+                c.execute(
+                    "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, ?2) WHERE id = ?1",
+                    params![id, at.timestamp()],
+                )
+            })
+            .await?,
+        )
+    }
+}
@@ -0,0 +1,294 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use tracing::warn;
+
+use crate::{
+    services::transit_service::transit_service::{
+        StopArrivalsError, StopInformation, StopNearLocation, TransitClientError, TransitService,
+    },
+    types::lat_long_location::GetStopsAtLocationInput,
+    utils::cache_control::CachePolicy,
+};
+
+use super::walking_time::{StraightLineWalking, WalkingTimeEstimator};
+
+/// Stops further down the nearest-first list are unlikely to be worth the walk.
+const MAX_STOPS: usize = 10;
+/// Upcoming arrivals checked per route and direction, so a bus that leaves too soon to catch
+/// still leaves the next one to recommend.
+const ARRIVALS_PER_ROUTE: usize = 3;
+
+pub struct DepartureAdviceOptions {
+    pub radius_meters: f64,
+    pub route_ids: Option<Vec<String>>,
+    /// Time to spare at the stop before the bus arrives.
+    pub buffer: Duration,
+}
+
+#[derive(Clone)]
+pub struct DepartureOption {
+    pub arrival: StopInformation,
+    pub stop_name: String,
+    pub walking_distance_meters: f64,
+    pub walking_time: Duration,
+    pub arrives_at: DateTime<Utc>,
+    pub leave_at: DateTime<Utc>,
+}
+
+pub struct DepartureAdvice {
+    /// The soonest bus that can still be caught on each route and direction, soonest first.
+    pub options: Vec<DepartureOption>,
+    pub errors: Vec<StopArrivalsError>,
+}
+
+/// Works out which buses near an origin can be caught on foot and when to set off for each.
+#[derive(Clone)]
+pub struct DepartureAdvisor {
+    transit_service: TransitService,
+    walking: Arc<dyn WalkingTimeEstimator>,
+}
+
+impl DepartureAdvisor {
+    pub fn new(transit_service: TransitService, walking: Arc<dyn WalkingTimeEstimator>) -> Self {
+        DepartureAdvisor {
+            transit_service,
+            walking,
+        }
+    }
+
+    pub fn with_cache_policy(&self, policy: CachePolicy) -> Self {
+        DepartureAdvisor {
+            transit_service: self.transit_service.with_cache_policy(policy),
+            walking: self.walking.clone(),
+        }
+    }
+
+    pub async fn advise(
+        &self,
+        origin: GetStopsAtLocationInput,
+        options: DepartureAdviceOptions,
+    ) -> Result<DepartureAdvice, TransitClientError> {
+        let nearby = self
+            .transit_service
+            .get_stops_near(origin, options.radius_meters, None)
+            .await?;
+        let origin = (nearby.lat, nearby.lon);
+
+        let stops = nearby
+            .stops
+            .into_iter()
+            .filter(|s| {
+                options
+                    .route_ids
+                    .as_ref()
+                    .map_or(true, |ids| s.route_ids.iter().any(|id| ids.contains(id)))
+            })
+            .take(MAX_STOPS)
+            .collect::<Vec<StopNearLocation>>();
+
+        if stops.is_empty() {
+            return Ok(DepartureAdvice {
+                options: Vec::new(),
+                errors: Vec::new(),
+            });
+        }
+
+        let walking_times = join_all(stops.iter().map(|s| self.walking_time(origin, s))).await;
+
+        let arrivals = self
+            .transit_service
+            .fetch_multiple_stop_arrivals(
+                stops.iter().map(|s| s.id.as_str()).collect(),
+                ARRIVALS_PER_ROUTE,
+            )
+            .await;
+
+        if arrivals.errors.len() == stops.len() {
+            if let Some(retry_after) = arrivals.unavailable_for() {
+                return Err(TransitClientError::Unavailable(
+                    "Every nearby stop's provider".to_string(),
+                    retry_after,
+                ));
+            }
+
+            return Err(TransitClientError::Internal(format!(
+                "Failed to fetch arrivals for all {} nearby stops",
+                stops.len()
+            )));
+        }
+
+        let stops_by_id = stops
+            .iter()
+            .zip(walking_times)
+            .map(|(s, walking_time)| (s.id.as_str(), (s, walking_time)))
+            .collect::<HashMap<&str, (&StopNearLocation, Duration)>>();
+
+        let now = Utc::now();
+        let mut best: HashMap<(String, String), DepartureOption> = HashMap::new();
+
+        for arrival in arrivals.arrivals {
+            if options
+                .route_ids
+                .as_ref()
+                .is_some_and(|ids| !ids.contains(&arrival.route_id))
+            {
+                continue;
+            }
+
+            let (stop, walking_time) = match stops_by_id.get(arrival.stop_id.as_str()) {
+                Some(stop) => *stop,
+                None => continue,
+            };
+
+            let arrives_at = DateTime::parse_from_rfc3339(&arrival.expected_arrival_time)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or(now + chrono::Duration::minutes(arrival.minutes_until_arrival));
+            let leave_at = arrives_at
+                - chrono::Duration::from_std(walking_time + options.buffer).unwrap_or_default();
+
+            if leave_at < now {
+                continue;
+            }
+
+            let option = DepartureOption {
+                stop_name: stop.name.clone(),
+                walking_distance_meters: stop.distance_meters,
+                walking_time,
+                arrives_at,
+                leave_at,
+                arrival,
+            };
+
+            let key = (
+                option.arrival.route_id.clone(),
+                option.arrival.direction_id.clone(),
+            );
+
+            // the same bus can be caught at several stops, prefer whichever comes first, then the
+            // shorter walk
+            match best.get(&key) {
+                Some(existing)
+                    if (existing.arrives_at, existing.walking_time)
+                        <= (option.arrives_at, option.walking_time) => {}
+                _ => {
+                    best.insert(key, option);
+                }
+            }
+        }
+
+        let mut options = best.into_values().collect::<Vec<DepartureOption>>();
+        options.sort_by_key(|o| (o.arrives_at, o.leave_at));
+
+        Ok(DepartureAdvice {
+            options,
+            errors: arrivals.errors,
+        })
+    }
+
+    /// Falls back to the straight line model when the estimator fails.
+    async fn walking_time(&self, origin: (f64, f64), stop: &StopNearLocation) -> Duration {
+        let destination = (stop.lat, stop.lon);
+
+        match self.walking.walking_time(origin, destination).await {
+            Ok(time) => time,
+            Err(e) => {
+                warn!("Failed to estimate walking time to {}: {}", stop.id, e);
+
+                StraightLineWalking::default()
+                    .walking_time(origin, destination)
+                    .await
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        services::{
+            maps_client::maps_service::{MapsService, MapsServiceConfig},
+            transit_service::{
+                providers::fake_transit_provider::FakeTransitProvider,
+                stale_arrivals::StaleArrivals,
+                transit_provider::{NearbyStop, TransitProvider},
+                transit_service::TransitServiceConfig,
+            },
+        },
+        utils::upstream_client::UpstreamConfig,
+    };
+
+    use super::*;
+
+    fn stop(id: &str, lat: f64, route_ids: &[&str]) -> NearbyStop {
+        NearbyStop {
+            id: id.to_string(),
+            name: id.to_string(),
+            lat,
+            lon: -73.98,
+            direction: None,
+            route_ids: route_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn recommends_catchable_buses() {
+        let provider = FakeTransitProvider {
+            name: "fake".to_string(),
+            // about 110m and 220m north of the origin
+            stops: vec![
+                stop("near", 40.681, &["B63", "B61"]),
+                stop("far", 40.682, &["B63", "B65"]),
+            ],
+            arrivals: HashMap::from([
+                (
+                    "near".to_string(),
+                    vec![
+                        ("B63".to_string(), 1),
+                        ("B63".to_string(), 9),
+                        ("B61".to_string(), 4),
+                    ],
+                ),
+                ("far".to_string(), vec![("B63".to_string(), 6)]),
+            ]),
+            ..Default::default()
+        };
+
+        let advisor = DepartureAdvisor::new(
+            TransitService::new(TransitServiceConfig {
+                providers: vec![Arc::new(provider) as Arc<dyn TransitProvider>],
+                maps_service: MapsService::new(MapsServiceConfig {
+                    host: "http://localhost".to_string(),
+                    api_key: "key".to_string(),
+                    upstream: UpstreamConfig::default(),
+                }),
+                stale_arrivals: StaleArrivals::default(),
+            }),
+            Arc::new(StraightLineWalking::default()),
+        );
+
+        let advice = advisor
+            .advise(
+                GetStopsAtLocationInput::LatLong("40.68".to_string(), "-73.98".to_string()),
+                DepartureAdviceOptions {
+                    radius_meters: 500.0,
+                    route_ids: Some(vec!["B63".to_string()]),
+                    buffer: Duration::from_secs(60),
+                },
+            )
+            .await
+            .unwrap();
+
+        // the B63 in 1 minute leaves no time to walk, the one in 6 minutes at the further stop
+        // arrives before the next at the nearer stop
+        assert_eq!(advice.options.len(), 1);
+        assert_eq!(advice.options[0].arrival.stop_id, "far");
+        assert_eq!(advice.options[0].arrival.minutes_until_arrival, 6);
+        assert!(advice.options[0].walking_time > Duration::from_secs(200));
+        assert!(advice.options[0].leave_at > Utc::now());
+    }
+}
@@ -0,0 +1,2 @@
+pub mod departure_advisor;
+pub mod walking_time;
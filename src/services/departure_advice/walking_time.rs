@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use axum::async_trait;
+
+use crate::utils::geo::distance_meters;
+
+/// Estimates how long it takes to walk between two points. A routing provider can implement this
+/// to replace the straight line model.
+#[async_trait]
+pub trait WalkingTimeEstimator: Send + Sync {
+    async fn walking_time(&self, from: (f64, f64), to: (f64, f64)) -> Result<Duration, String>;
+}
+
+/// Walks the straight line distance stretched by `detour_factor`, since streets rarely lead
+/// straight to a stop.
+#[derive(Clone)]
+pub struct StraightLineWalking {
+    pub meters_per_second: f64,
+    pub detour_factor: f64,
+}
+
+impl Default for StraightLineWalking {
+    fn default() -> Self {
+        StraightLineWalking {
+            meters_per_second: 1.3,
+            detour_factor: 1.3,
+        }
+    }
+}
+
+#[async_trait]
+impl WalkingTimeEstimator for StraightLineWalking {
+    async fn walking_time(&self, from: (f64, f64), to: (f64, f64)) -> Result<Duration, String> {
+        let meters = distance_meters(from, to) * self.detour_factor;
+
+        Ok(Duration::from_secs_f64(meters / self.meters_per_second))
+    }
+}
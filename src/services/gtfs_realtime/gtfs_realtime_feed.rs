@@ -0,0 +1,514 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+
+use crate::{
+    services::{
+        gtfs_static::gtfs_static_store::GtfsStaticIndex,
+        transit_service::transit_service::{
+            StopInformation, TransitClientError, VehicleFilter, VehiclePosition,
+        },
+    },
+    utils::{
+        single_flight::SingleFlight,
+        ttl_cache::TtlCache,
+        upstream_client::{UpstreamClient, UpstreamConfig, UpstreamError},
+    },
+};
+
+use super::types::gtfs_realtime_proto::{
+    FeedMessage, StopTimeUpdateScheduleRelationship, VehicleStopStatus,
+};
+
+#[derive(Clone)]
+pub enum GtfsRealtimeSource {
+    Url(String),
+    File(PathBuf),
+}
+
+impl GtfsRealtimeSource {
+    /// Treats anything with an http(s) scheme as a URL and everything else as a local path.
+    pub fn parse(source: &str) -> Self {
+        match source.starts_with("http://") || source.starts_with("https://") {
+            true => GtfsRealtimeSource::Url(source.to_string()),
+            false => GtfsRealtimeSource::File(PathBuf::from(source)),
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            GtfsRealtimeSource::Url(url) => url.clone(),
+            GtfsRealtimeSource::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GtfsRealtimeConfig {
+    pub trip_updates: Option<GtfsRealtimeSource>,
+    pub vehicle_positions: Option<GtfsRealtimeSource>,
+    pub cache_ttl: Duration,
+    pub upstream: UpstreamConfig,
+}
+
+/// Reads arrivals from GTFS-Realtime TripUpdates and VehiclePositions feeds. A decoded feed is
+/// shared by every stop lookup until `cache_ttl` passes.
+#[derive(Clone)]
+pub struct GtfsRealtimeFeed {
+    config: GtfsRealtimeConfig,
+    client: UpstreamClient,
+    cache: TtlCache<Arc<FeedMessage>>,
+    in_flight: SingleFlight<Result<Arc<FeedMessage>, TransitClientError>>,
+}
+
+impl GtfsRealtimeFeed {
+    pub fn new(config: GtfsRealtimeConfig) -> Self {
+        GtfsRealtimeFeed {
+            client: UpstreamClient::new("GTFS-Realtime", config.upstream.clone()),
+            config,
+            cache: TtlCache::new(2),
+            in_flight: SingleFlight::default(),
+        }
+    }
+
+    pub async fn fetch_stop_info(
+        &self,
+        stop_id: &str,
+        gtfs_static: Option<&GtfsStaticIndex>,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        let trip_updates = match &self.config.trip_updates {
+            Some(source) => Some(self.fetch(source).await?),
+            None => None,
+        };
+
+        let vehicle_positions = match &self.config.vehicle_positions {
+            Some(source) => Some(self.fetch(source).await?),
+            None => None,
+        };
+
+        Ok(stop_info_from_feeds(
+            trip_updates.as_deref(),
+            vehicle_positions.as_deref(),
+            stop_id,
+            gtfs_static,
+            limit_per_route,
+            Utc::now(),
+        ))
+    }
+
+    /// Returns `ResourceNotFound` when no VehiclePositions feed is configured.
+    pub async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+        gtfs_static: Option<&GtfsStaticIndex>,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError> {
+        let source = self
+            .config
+            .vehicle_positions
+            .as_ref()
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        Ok(vehicle_positions_from_feed(
+            &*self.fetch(source).await?,
+            filter,
+            gtfs_static,
+        ))
+    }
+
+    async fn fetch(
+        &self,
+        source: &GtfsRealtimeSource,
+    ) -> Result<Arc<FeedMessage>, TransitClientError> {
+        let key = source.key();
+
+        if let Some(feed) = self.cache.get(&key) {
+            return Ok(feed);
+        }
+
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        let ttl = self.config.cache_ttl;
+        let source = source.clone();
+        let owned_key = key.clone();
+
+        self.in_flight
+            .run(&key, async move {
+                let body = match &source {
+                    GtfsRealtimeSource::Url(url) => client
+                        .get(url)
+                        .await
+                        .map_err(|e| match e {
+                            UpstreamError::Unavailable(retry_after) => {
+                                TransitClientError::Unavailable(
+                                    "GTFS-Realtime".to_string(),
+                                    retry_after,
+                                )
+                            }
+                            _ => TransitClientError::Internal(format!(
+                                "Failed to fetch GTFS-Realtime feed: {}",
+                                e
+                            )),
+                        })?
+                        .to_vec(),
+                    GtfsRealtimeSource::File(path) => tokio::fs::read(path).await.map_err(|e| {
+                        TransitClientError::Internal(format!(
+                            "Failed to read GTFS-Realtime feed {}: {}",
+                            path.display(),
+                            e
+                        ))
+                    })?,
+                };
+
+                let feed = Arc::new(FeedMessage::decode(body.as_slice()).map_err(|e| {
+                    TransitClientError::Internal(format!(
+                        "Failed to decode GTFS-Realtime feed: {}",
+                        e
+                    ))
+                })?);
+
+                cache.insert(owned_key, feed.clone(), ttl);
+
+                Ok(feed)
+            })
+            .await
+    }
+}
+
+/// Builds the upcoming arrivals at `stop_id`, keeping the soonest `limit_per_route` per route and
+/// direction.
+///
+/// Trip updates provide predicted times. Vehicles that report themselves as stopped at or
+/// incoming at the stop, without a matching trip update, are treated as arriving now.
+pub fn stop_info_from_feeds(
+    trip_updates: Option<&FeedMessage>,
+    vehicle_positions: Option<&FeedMessage>,
+    stop_id: &str,
+    gtfs_static: Option<&GtfsStaticIndex>,
+    limit_per_route: usize,
+    now: DateTime<Utc>,
+) -> Vec<StopInformation> {
+    // realtime feeds reference the feed's own ids rather than the prefixed ones the api uses
+    let gtfs_stop_id = gtfs_static
+        .and_then(|index| index.stop(stop_id))
+        .map(|s| s.gtfs_id.as_str())
+        .unwrap_or(stop_id);
+
+    // (arrival unix time, trip id, gtfs route id, direction, vehicle id)
+    let mut visits: Vec<(i64, String, String, u32, Option<String>)> = Vec::new();
+
+    for entity in trip_updates.iter().flat_map(|f| f.entity.iter()) {
+        let trip_update = match &entity.trip_update {
+            Some(t) => t,
+            None => continue,
+        };
+        let trip = trip_update.trip.as_ref();
+
+        for update in trip_update.stop_time_update.iter() {
+            if update.stop_id.as_deref() != Some(gtfs_stop_id)
+                || update.schedule_relationship
+                    == Some(StopTimeUpdateScheduleRelationship::Skipped as i32)
+            {
+                continue;
+            }
+
+            let time = update
+                .arrival
+                .as_ref()
+                .and_then(|e| e.time)
+                .or(update.departure.as_ref().and_then(|e| e.time));
+
+            if let Some(time) = time {
+                visits.push((
+                    time,
+                    trip.and_then(|t| t.trip_id.clone()).unwrap_or_default(),
+                    trip.and_then(|t| t.route_id.clone()).unwrap_or_default(),
+                    trip.and_then(|t| t.direction_id).unwrap_or_default(),
+                    trip_update.vehicle.as_ref().and_then(|v| v.id.clone()),
+                ));
+            }
+        }
+    }
+
+    let predicted_trips: HashSet<String> = visits.iter().map(|v| v.1.clone()).collect();
+
+    for entity in vehicle_positions.iter().flat_map(|f| f.entity.iter()) {
+        let vehicle = match &entity.vehicle {
+            Some(v) => v,
+            None => continue,
+        };
+        let trip = vehicle.trip.as_ref();
+        let trip_id = trip.and_then(|t| t.trip_id.clone()).unwrap_or_default();
+
+        let at_stop = matches!(
+            vehicle.current_status.map(VehicleStopStatus::try_from),
+            Some(Ok(VehicleStopStatus::StoppedAt)) | Some(Ok(VehicleStopStatus::IncomingAt))
+        );
+
+        if vehicle.stop_id.as_deref() != Some(gtfs_stop_id)
+            || !at_stop
+            || predicted_trips.contains(&trip_id)
+        {
+            continue;
+        }
+
+        visits.push((
+            now.timestamp(),
+            trip_id,
+            trip.and_then(|t| t.route_id.clone()).unwrap_or_default(),
+            trip.and_then(|t| t.direction_id).unwrap_or_default(),
+            vehicle.vehicle.as_ref().and_then(|v| v.id.clone()),
+        ));
+    }
+
+    visits.sort_by_key(|v| v.0);
+
+    let mut output = Vec::<StopInformation>::new();
+    let mut tracked_routes = HashMap::<(String, u32), usize>::new();
+
+    for (time, _, gtfs_route_id, direction, vehicle_id) in visits {
+        let expected_arrival_time = match DateTime::from_timestamp(time, 0) {
+            Some(t) if time >= now.timestamp() => t,
+            _ => continue,
+        };
+
+        let count = tracked_routes
+            .entry((gtfs_route_id.clone(), direction))
+            .or_default();
+        if *count >= limit_per_route {
+            continue;
+        }
+        *count += 1;
+
+        let (route_id, route_label) =
+            match gtfs_static.and_then(|i| i.route_by_gtfs_id(&gtfs_route_id)) {
+                Some(route) => (route.id.clone(), route.name.clone()),
+                None => (gtfs_route_id.clone(), gtfs_route_id),
+            };
+
+        output.push(StopInformation {
+            expected_arrival_time: expected_arrival_time.to_rfc3339(),
+            minutes_until_arrival: expected_arrival_time
+                .signed_duration_since(now)
+                .num_minutes(),
+            stop_id: stop_id.to_string(),
+            route_label,
+            route_id,
+            direction_id: direction.to_string(),
+            vehicle_id,
+            ..Default::default()
+        });
+    }
+
+    output
+}
+
+/// Reads the vehicles matching `filter`, reporting routes by their prefixed IDs when a static
+/// feed is loaded.
+pub fn vehicle_positions_from_feed(
+    vehicle_positions: &FeedMessage,
+    filter: &VehicleFilter,
+    gtfs_static: Option<&GtfsStaticIndex>,
+) -> Vec<VehiclePosition> {
+    let mut output = Vec::new();
+
+    for entity in vehicle_positions.entity.iter() {
+        let vehicle = match &entity.vehicle {
+            Some(v) => v,
+            None => continue,
+        };
+        let position = match &vehicle.position {
+            Some(p) => p,
+            None => continue,
+        };
+        let trip = vehicle.trip.as_ref();
+
+        let vehicle_id = vehicle
+            .vehicle
+            .as_ref()
+            .and_then(|v| v.id.clone())
+            .or(entity.id.clone())
+            .unwrap_or_default();
+        let gtfs_route_id = trip.and_then(|t| t.route_id.clone()).unwrap_or_default();
+        let (route_id, route_label) =
+            match gtfs_static.and_then(|i| i.route_by_gtfs_id(&gtfs_route_id)) {
+                Some(route) => (route.id.clone(), route.name.clone()),
+                None => (gtfs_route_id.clone(), gtfs_route_id),
+            };
+
+        let matches = match filter {
+            VehicleFilter::Route(id) => *id == route_id,
+            VehicleFilter::Vehicle(id) => *id == vehicle_id,
+        };
+        if !matches {
+            continue;
+        }
+
+        output.push(VehiclePosition {
+            vehicle_id,
+            route_id,
+            route_label,
+            direction_id: trip.and_then(|t| t.direction_id).map(|d| d.to_string()),
+            lat: position.latitude.unwrap_or_default() as f64,
+            lon: position.longitude.unwrap_or_default() as f64,
+            bearing: position.bearing.map(|b| b as f64),
+            destination_name: None,
+            next_stop_id: vehicle.stop_id.as_ref().map(|id| {
+                gtfs_static
+                    .and_then(|i| i.stop_by_gtfs_id(id))
+                    .map(|s| s.id.clone())
+                    .unwrap_or(id.clone())
+            }),
+            progress_status: None,
+            recorded_at: vehicle
+                .timestamp
+                .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                .map(|t| t.to_rfc3339()),
+        });
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::services::gtfs_static::gtfs_static_store::tests::write_test_feed;
+
+    use super::*;
+
+    const FIXTURES: &str = "src/services/gtfs_realtime/fixtures";
+
+    /// The header timestamp of both fixtures.
+    fn fixture_time() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn read_fixture(name: &str) -> FeedMessage {
+        FeedMessage::decode(
+            std::fs::read(Path::new(FIXTURES).join(name))
+                .unwrap()
+                .as_slice(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_arrivals_from_fixtures() {
+        let trip_updates = read_fixture("trip_updates.pb");
+        let vehicle_positions = read_fixture("vehicle_positions.pb");
+
+        let arrivals = stop_info_from_feeds(
+            Some(&trip_updates),
+            Some(&vehicle_positions),
+            "1",
+            None,
+            1,
+            fixture_time(),
+        );
+
+        // a B61 stopped at the stop, the soonest B63 in each direction, and no past arrivals
+        assert_eq!(arrivals.len(), 3);
+        assert_eq!(arrivals[0].route_id, "B61");
+        assert_eq!(arrivals[0].minutes_until_arrival, 0);
+        assert_eq!(arrivals[1].route_id, "B63");
+        assert_eq!(arrivals[1].minutes_until_arrival, 4);
+        assert_eq!(arrivals[2].route_id, "B63");
+        assert_eq!(arrivals[2].minutes_until_arrival, 10);
+        assert!(arrivals.iter().all(|a| a.stop_id == "1"));
+
+        let skipped = stop_info_from_feeds(Some(&trip_updates), None, "3", None, 1, fixture_time());
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn maps_ids_through_static_feed() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = GtfsStaticIndex::from_zip(&write_test_feed(dir.path()), Some("MTA")).unwrap();
+
+        let arrivals = stop_info_from_feeds(
+            Some(&read_fixture("trip_updates.pb")),
+            None,
+            "MTA_1",
+            Some(&index),
+            1,
+            fixture_time(),
+        );
+
+        assert_eq!(arrivals.len(), 2);
+        assert_eq!(arrivals[0].stop_id, "MTA_1");
+        assert_eq!(arrivals[0].route_id, "MTA NYCT_B63");
+        assert_eq!(arrivals[0].route_label, "B63");
+    }
+
+    #[test]
+    fn reads_vehicle_positions() {
+        let vehicle_positions = read_fixture("vehicle_positions.pb");
+
+        let on_route = vehicle_positions_from_feed(
+            &vehicle_positions,
+            &VehicleFilter::Route("B61".to_string()),
+            None,
+        );
+
+        assert_eq!(on_route.len(), 1);
+        assert!(on_route[0].lat != 0.0);
+
+        let by_id = vehicle_positions_from_feed(
+            &vehicle_positions,
+            &VehicleFilter::Vehicle(on_route[0].vehicle_id.clone()),
+            None,
+        );
+
+        assert_eq!(by_id.len(), 1);
+        assert_eq!(by_id[0].route_id, "B61");
+    }
+
+    #[tokio::test]
+    async fn reads_feed_from_file() {
+        let source = GtfsRealtimeSource::parse(&format!("{}/trip_updates.pb", FIXTURES));
+        let feed = GtfsRealtimeFeed::new(GtfsRealtimeConfig {
+            trip_updates: Some(source.clone()),
+            vehicle_positions: None,
+            cache_ttl: Duration::from_secs(15),
+            upstream: UpstreamConfig::default(),
+        });
+
+        let trip_updates = feed.fetch(&source).await.unwrap();
+        assert_eq!(trip_updates.entity.len(), 4);
+
+        let first = trip_updates.entity[0].trip_update.as_ref().unwrap();
+        let trip = first.trip.as_ref().unwrap();
+        assert_eq!(trip.trip_id.as_deref(), Some("t1"));
+        assert_eq!(trip.route_id.as_deref(), Some("B63"));
+        assert_eq!(first.stop_time_update[1].stop_id.as_deref(), Some("1"));
+        assert_eq!(
+            first.stop_time_update[1].arrival.as_ref().unwrap().time,
+            Some(1_700_000_240)
+        );
+
+        // the fixture's arrivals are in the past by now, so they are read as of its timestamp
+        let arrivals =
+            stop_info_from_feeds(Some(&trip_updates), None, "1", None, 1, fixture_time());
+        assert_eq!(arrivals.len(), 2);
+        assert_eq!(arrivals[0].route_id, "B63");
+        assert_eq!(arrivals[0].direction_id, "0");
+        assert_eq!(arrivals[0].minutes_until_arrival, 4);
+        assert_eq!(arrivals[0].vehicle_id.as_deref(), Some("MTA NYCT_7001"));
+        assert_eq!(arrivals[1].direction_id, "1");
+        assert_eq!(arrivals[1].minutes_until_arrival, 10);
+
+        // the decoded feed is cached rather than read again
+        assert!(Arc::ptr_eq(
+            &trip_updates,
+            &feed.fetch(&source).await.unwrap()
+        ));
+    }
+}
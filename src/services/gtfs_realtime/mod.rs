@@ -0,0 +1,2 @@
+pub mod gtfs_realtime_feed;
+pub mod types;
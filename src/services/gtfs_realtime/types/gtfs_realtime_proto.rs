@@ -0,0 +1,129 @@
+//! The subset of `gtfs-realtime.proto` that the realtime feed reader consumes. Field tags match
+//! https://gtfs.org/realtime/proto/ and unknown fields are skipped while decoding.
+#![allow(dead_code)]
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FeedMessage {
+    #[prost(message, optional, tag = "1")]
+    pub header: Option<FeedHeader>,
+    #[prost(message, repeated, tag = "2")]
+    pub entity: Vec<FeedEntity>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FeedHeader {
+    #[prost(string, optional, tag = "1")]
+    pub gtfs_realtime_version: Option<String>,
+    #[prost(uint64, optional, tag = "3")]
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FeedEntity {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(bool, optional, tag = "2")]
+    pub is_deleted: Option<bool>,
+    #[prost(message, optional, tag = "3")]
+    pub trip_update: Option<TripUpdate>,
+    #[prost(message, optional, tag = "4")]
+    pub vehicle: Option<VehiclePosition>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TripUpdate {
+    #[prost(message, optional, tag = "1")]
+    pub trip: Option<TripDescriptor>,
+    #[prost(message, repeated, tag = "2")]
+    pub stop_time_update: Vec<StopTimeUpdate>,
+    #[prost(message, optional, tag = "3")]
+    pub vehicle: Option<VehicleDescriptor>,
+    #[prost(uint64, optional, tag = "4")]
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StopTimeEvent {
+    #[prost(int32, optional, tag = "1")]
+    pub delay: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    pub time: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StopTimeUpdate {
+    #[prost(uint32, optional, tag = "1")]
+    pub stop_sequence: Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub arrival: Option<StopTimeEvent>,
+    #[prost(message, optional, tag = "3")]
+    pub departure: Option<StopTimeEvent>,
+    #[prost(string, optional, tag = "4")]
+    pub stop_id: Option<String>,
+    #[prost(
+        enumeration = "StopTimeUpdateScheduleRelationship",
+        optional,
+        tag = "5"
+    )]
+    pub schedule_relationship: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+pub enum StopTimeUpdateScheduleRelationship {
+    Scheduled = 0,
+    Skipped = 1,
+    NoData = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VehiclePosition {
+    #[prost(message, optional, tag = "1")]
+    pub trip: Option<TripDescriptor>,
+    #[prost(message, optional, tag = "2")]
+    pub position: Option<Position>,
+    #[prost(uint32, optional, tag = "3")]
+    pub current_stop_sequence: Option<u32>,
+    #[prost(enumeration = "VehicleStopStatus", optional, tag = "4")]
+    pub current_status: Option<i32>,
+    #[prost(uint64, optional, tag = "5")]
+    pub timestamp: Option<u64>,
+    #[prost(string, optional, tag = "7")]
+    pub stop_id: Option<String>,
+    #[prost(message, optional, tag = "8")]
+    pub vehicle: Option<VehicleDescriptor>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+pub enum VehicleStopStatus {
+    IncomingAt = 0,
+    StoppedAt = 1,
+    InTransitTo = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Position {
+    #[prost(float, optional, tag = "1")]
+    pub latitude: Option<f32>,
+    #[prost(float, optional, tag = "2")]
+    pub longitude: Option<f32>,
+    #[prost(float, optional, tag = "3")]
+    pub bearing: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TripDescriptor {
+    #[prost(string, optional, tag = "1")]
+    pub trip_id: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub route_id: Option<String>,
+    #[prost(uint32, optional, tag = "6")]
+    pub direction_id: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VehicleDescriptor {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub label: Option<String>,
+}
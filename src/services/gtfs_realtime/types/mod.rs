@@ -0,0 +1 @@
+pub mod gtfs_realtime_proto;
@@ -0,0 +1,497 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use serde::de::DeserializeOwned;
+use zip::ZipArchive;
+
+use crate::utils::geo::distance_meters;
+
+use super::types::gtfs_static_records::{
+    GtfsAgencyRecord, GtfsRouteRecord, GtfsShapeRecord, GtfsStopRecord, GtfsStopTimeRecord,
+    GtfsTripRecord,
+};
+
+type StopSequence<'a> = Vec<(u32, &'a str)>;
+
+#[derive(Clone)]
+pub struct GtfsStaticConfig {
+    pub path: PathBuf,
+    /// Prefix for stop IDs, matching how OneBusAway namespaces them (e.g. `MTA` for `MTA_308209`).
+    /// Defaults to the feed's first agency ID.
+    pub stop_id_prefix: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum GtfsStaticError {
+    NotLoaded,
+    Io(String),
+    Parse(String),
+}
+
+impl std::fmt::Display for GtfsStaticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GtfsStaticError::NotLoaded => write!(f, "No GTFS feed has been loaded"),
+            GtfsStaticError::Io(e) => write!(f, "Failed to read GTFS feed: {}", e),
+            GtfsStaticError::Parse(e) => write!(f, "Failed to parse GTFS feed: {}", e),
+        }
+    }
+}
+
+pub struct GtfsRouteDirection {
+    pub id: String,
+    pub name: String,
+    pub stop_ids: Vec<String>,
+    /// The shape of the trip the stops were taken from.
+    pub shape_id: Option<String>,
+}
+
+pub struct GtfsRoute {
+    pub id: String,
+    pub agency_id: String,
+    pub name: String,
+    pub directions: Vec<GtfsRouteDirection>,
+}
+
+pub struct GtfsStop {
+    pub id: String,
+    /// The unprefixed `stop_id` from the feed itself, as used by GTFS-Realtime.
+    pub gtfs_id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub route_ids: Vec<String>,
+}
+
+pub struct GtfsStaticSummary {
+    pub agencies: usize,
+    pub routes: usize,
+    pub stops: usize,
+    pub trips: usize,
+    pub shapes: usize,
+}
+
+/// Routes and stops from a GTFS static feed, keyed by the same agency-prefixed IDs that
+/// OneBusAway uses so that they are interchangeable with live API results.
+pub struct GtfsStaticIndex {
+    routes: HashMap<String, GtfsRoute>,
+    route_ids_by_gtfs_id: HashMap<String, String>,
+    stops: HashMap<String, GtfsStop>,
+    stop_id_prefix: String,
+    shapes: HashMap<String, Vec<(f64, f64)>>,
+    agency_count: usize,
+    trip_count: usize,
+}
+
+impl GtfsStaticIndex {
+    pub fn from_zip(path: &Path, stop_id_prefix: Option<&str>) -> Result<Self, GtfsStaticError> {
+        let file = File::open(path).map_err(|e| GtfsStaticError::Io(e.to_string()))?;
+        let mut archive = ZipArchive::new(file).map_err(|e| GtfsStaticError::Io(e.to_string()))?;
+
+        let agencies = read_records::<GtfsAgencyRecord>(&mut archive, "agency.txt")?;
+        let default_agency_id = agencies
+            .first()
+            .map(|a| a.agency_id.clone().unwrap_or(a.agency_name.clone()))
+            .ok_or_else(|| GtfsStaticError::Parse("agency.txt has no agencies".to_string()))?;
+        let stop_id_prefix = stop_id_prefix.unwrap_or(&default_agency_id).to_string();
+
+        let route_records = read_records::<GtfsRouteRecord>(&mut archive, "routes.txt")?;
+        let stop_records = read_records::<GtfsStopRecord>(&mut archive, "stops.txt")?;
+        let trip_records = read_records::<GtfsTripRecord>(&mut archive, "trips.txt")?;
+        let stop_time_records = read_records::<GtfsStopTimeRecord>(&mut archive, "stop_times.txt")?;
+        let shape_records = match find_file_name(&archive, "shapes.txt") {
+            Some(_) => read_records::<GtfsShapeRecord>(&mut archive, "shapes.txt")?,
+            None => Vec::new(),
+        };
+
+        let route_ids_by_gtfs_id: HashMap<&str, String> = route_records
+            .iter()
+            .map(|r| {
+                let agency_id = r.agency_id.as_deref().unwrap_or(&default_agency_id);
+                (r.route_id.as_str(), format!("{}_{}", agency_id, r.route_id))
+            })
+            .collect();
+
+        let trips_by_id: HashMap<&str, &GtfsTripRecord> = trip_records
+            .iter()
+            .map(|t| (t.trip_id.as_str(), t))
+            .collect();
+
+        let mut stop_sequences: HashMap<&str, StopSequence> = HashMap::new();
+        for stop_time in stop_time_records.iter() {
+            stop_sequences
+                .entry(stop_time.trip_id.as_str())
+                .or_default()
+                .push((stop_time.stop_sequence, stop_time.stop_id.as_str()));
+        }
+
+        // the trip with the most stops represents each direction of a route
+        let mut longest_trips: HashMap<(&str, &str), (&GtfsTripRecord, &StopSequence)> =
+            HashMap::new();
+        let mut stop_route_ids: HashMap<&str, HashSet<&str>> = HashMap::new();
+
+        for (trip_id, sequence) in stop_sequences.iter_mut() {
+            let trip = match trips_by_id.get(trip_id) {
+                Some(t) => *t,
+                None => continue,
+            };
+
+            sequence.sort_by_key(|(stop_sequence, _)| *stop_sequence);
+            let sequence: &StopSequence = sequence;
+
+            for (_, stop_id) in sequence.iter() {
+                stop_route_ids
+                    .entry(stop_id)
+                    .or_default()
+                    .insert(trip.route_id.as_str());
+            }
+
+            let direction = trip.direction_id.as_deref().unwrap_or("0");
+            let key = (trip.route_id.as_str(), direction);
+
+            match longest_trips.get(&key) {
+                Some((_, longest)) if longest.len() >= sequence.len() => {}
+                _ => {
+                    longest_trips.insert(key, (trip, sequence));
+                }
+            }
+        }
+
+        let mut routes: HashMap<String, GtfsRoute> = HashMap::new();
+        for route in route_records.iter() {
+            let id = route_ids_by_gtfs_id[route.route_id.as_str()].clone();
+            let name = route
+                .route_short_name
+                .clone()
+                .or(route.route_long_name.clone())
+                .unwrap_or(route.route_id.clone());
+
+            routes.insert(
+                id.clone(),
+                GtfsRoute {
+                    id,
+                    agency_id: route.agency_id.clone().unwrap_or(default_agency_id.clone()),
+                    name,
+                    directions: Vec::new(),
+                },
+            );
+        }
+
+        let mut directions: Vec<_> = longest_trips.into_iter().collect();
+        directions.sort_by(|((a_route, a_dir), _), ((b_route, b_dir), _)| {
+            (a_route, a_dir).cmp(&(b_route, b_dir))
+        });
+
+        for ((gtfs_route_id, direction_id), (trip, sequence)) in directions {
+            let route = match route_ids_by_gtfs_id
+                .get(gtfs_route_id)
+                .and_then(|id| routes.get_mut(id))
+            {
+                Some(r) => r,
+                None => continue,
+            };
+
+            route.directions.push(GtfsRouteDirection {
+                id: direction_id.to_string(),
+                name: trip.trip_headsign.clone().unwrap_or_default(),
+                stop_ids: sequence
+                    .iter()
+                    .map(|(_, stop_id)| format!("{}_{}", stop_id_prefix, stop_id))
+                    .collect(),
+                shape_id: trip.shape_id.clone(),
+            });
+        }
+
+        let mut stops: HashMap<String, GtfsStop> = HashMap::new();
+        for stop in stop_records.iter() {
+            let (lat, lon) = match (stop.stop_lat, stop.stop_lon) {
+                (Some(lat), Some(lon)) => (lat, lon),
+                _ => continue,
+            };
+
+            let mut route_ids: Vec<String> = stop_route_ids
+                .get(stop.stop_id.as_str())
+                .map(|ids| {
+                    ids.iter()
+                        .filter_map(|id| route_ids_by_gtfs_id.get(id).cloned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            route_ids.sort();
+
+            let id = format!("{}_{}", stop_id_prefix, stop.stop_id);
+            stops.insert(
+                id.clone(),
+                GtfsStop {
+                    id,
+                    gtfs_id: stop.stop_id.clone(),
+                    name: stop.stop_name.clone().unwrap_or_default(),
+                    lat,
+                    lon,
+                    route_ids,
+                },
+            );
+        }
+
+        let mut shape_points: HashMap<String, Vec<(u32, f64, f64)>> = HashMap::new();
+        for point in shape_records.into_iter() {
+            shape_points.entry(point.shape_id).or_default().push((
+                point.shape_pt_sequence,
+                point.shape_pt_lat,
+                point.shape_pt_lon,
+            ));
+        }
+
+        let shapes = shape_points
+            .into_iter()
+            .map(|(id, mut points)| {
+                points.sort_by_key(|(sequence, _, _)| *sequence);
+                (
+                    id,
+                    points.into_iter().map(|(_, lat, lon)| (lat, lon)).collect(),
+                )
+            })
+            .collect();
+
+        Ok(GtfsStaticIndex {
+            route_ids_by_gtfs_id: route_ids_by_gtfs_id
+                .into_iter()
+                .map(|(gtfs_id, id)| (gtfs_id.to_string(), id))
+                .collect(),
+            routes,
+            stops,
+            stop_id_prefix,
+            shapes,
+            agency_count: agencies.len(),
+            trip_count: trip_records.len(),
+        })
+    }
+
+    pub fn summary(&self) -> GtfsStaticSummary {
+        GtfsStaticSummary {
+            agencies: self.agency_count,
+            routes: self.routes.len(),
+            stops: self.stops.len(),
+            trips: self.trip_count,
+            shapes: self.shapes.len(),
+        }
+    }
+
+    pub fn find_routes(&self, search: &str) -> Vec<&GtfsRoute> {
+        let search = search.to_lowercase();
+        let mut routes: Vec<&GtfsRoute> = self
+            .routes
+            .values()
+            .filter(|r| r.name.to_lowercase().contains(&search))
+            .collect();
+        routes.sort_by(|a, b| a.name.cmp(&b.name));
+        routes
+    }
+
+    pub fn route(&self, route_id: &str) -> Option<&GtfsRoute> {
+        self.routes.get(route_id)
+    }
+
+    pub fn route_by_gtfs_id(&self, gtfs_route_id: &str) -> Option<&GtfsRoute> {
+        self.route_ids_by_gtfs_id
+            .get(gtfs_route_id)
+            .and_then(|id| self.routes.get(id))
+    }
+
+    /// The `(lat, lon)` points of a shape in order.
+    pub fn shape(&self, shape_id: &str) -> Option<&[(f64, f64)]> {
+        self.shapes.get(shape_id).map(|s| s.as_slice())
+    }
+
+    pub fn stop(&self, stop_id: &str) -> Option<&GtfsStop> {
+        self.stops.get(stop_id)
+    }
+
+    pub fn stop_by_gtfs_id(&self, gtfs_stop_id: &str) -> Option<&GtfsStop> {
+        self.stops
+            .get(&format!("{}_{}", self.stop_id_prefix, gtfs_stop_id))
+    }
+
+    /// Stops within `radius_meters` of the given point, mirroring `radius` in OneBusAway's
+    /// `stops-for-location`.
+    pub fn stops_within(&self, lat: f64, lon: f64, radius_meters: f64) -> Vec<&GtfsStop> {
+        self.stops
+            .values()
+            .filter(|s| distance_meters((lat, lon), (s.lat, s.lon)) <= radius_meters)
+            .collect()
+    }
+}
+
+/// Holds the currently loaded GTFS static feed. Clones share the same feed, so a reload is seen by
+/// every service holding the store.
+#[derive(Clone, Default)]
+pub struct GtfsStaticStore {
+    index: Arc<RwLock<Option<Arc<GtfsStaticIndex>>>>,
+    config: Arc<RwLock<Option<GtfsStaticConfig>>>,
+}
+
+impl GtfsStaticStore {
+    pub fn index(&self) -> Option<Arc<GtfsStaticIndex>> {
+        self.index.read().unwrap().clone()
+    }
+
+    pub fn config(&self) -> Option<GtfsStaticConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Parses the feed at `config.path` and swaps it in. The previous feed keeps serving requests
+    /// until the new one has been fully parsed, and stays in place if parsing fails.
+    pub async fn load(
+        &self,
+        config: GtfsStaticConfig,
+    ) -> Result<GtfsStaticSummary, GtfsStaticError> {
+        let blocking_config = config.clone();
+        let index = tokio::task::spawn_blocking(move || {
+            GtfsStaticIndex::from_zip(
+                &blocking_config.path,
+                blocking_config.stop_id_prefix.as_deref(),
+            )
+        })
+        .await
+        .map_err(|e| GtfsStaticError::Io(e.to_string()))??;
+
+        let summary = index.summary();
+        *self.index.write().unwrap() = Some(Arc::new(index));
+        *self.config.write().unwrap() = Some(config);
+
+        Ok(summary)
+    }
+}
+
+/// Feeds are sometimes zipped with their files nested in a directory.
+fn find_file_name(archive: &ZipArchive<File>, name: &str) -> Option<String> {
+    archive
+        .file_names()
+        .find(|f| *f == name || f.ends_with(&format!("/{}", name)))
+        .map(|f| f.to_string())
+}
+
+fn read_records<T: DeserializeOwned>(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+) -> Result<Vec<T>, GtfsStaticError> {
+    let file_name = find_file_name(archive, name)
+        .ok_or_else(|| GtfsStaticError::Parse(format!("{} is missing", name)))?;
+    let file = archive
+        .by_name(&file_name)
+        .map_err(|e| GtfsStaticError::Io(e.to_string()))?;
+
+    csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file)
+        .deserialize::<T>()
+        .collect::<Result<Vec<T>, csv::Error>>()
+        .map_err(|e| GtfsStaticError::Parse(format!("{}: {}", name, e)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::Write;
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    /// Writes a small feed with one route (`MTA NYCT_B63`) running in both directions through
+    /// three stops (`MTA_1`, `MTA_2`, `MTA_3`) around 40.68,-73.98.
+    pub fn write_test_feed(dir: &Path) -> PathBuf {
+        let path = dir.join("gtfs.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+
+        let files = [
+            (
+                "agency.txt",
+                "agency_id,agency_name,agency_url,agency_timezone\n\
+                 MTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\n",
+            ),
+            (
+                "routes.txt",
+                "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+                 B63,MTA NYCT,B63,Pier 6 - Bay Ridge,3\n",
+            ),
+            (
+                "stops.txt",
+                "stop_id,stop_name,stop_lat,stop_lon\n\
+                 1,5 AV/UNION ST,40.6800,-73.9800\n\
+                 2,5 AV/PRESIDENT ST,40.6790,-73.9810\n\
+                 3,5 AV/9 ST,40.6700,-73.9880\n",
+            ),
+            (
+                "trips.txt",
+                "route_id,service_id,trip_id,trip_headsign,direction_id,shape_id\n\
+                 B63,WKD,t1,COBBLE HILL,0,s1\n\
+                 B63,WKD,t2,BAY RIDGE,1,s2\n\
+                 B63,WKD,t3,BAY RIDGE,1,s2\n",
+            ),
+            (
+                "stop_times.txt",
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+                 t1,08:00:00,08:00:00,3,1\n\
+                 t1,08:05:00,08:05:00,2,2\n\
+                 t1,08:07:00,08:07:00,1,3\n\
+                 t2,09:00:00,09:00:00,1,1\n\
+                 t2,09:02:00,09:02:00,2,2\n\
+                 t2,09:07:00,09:07:00,3,3\n\
+                 t3,10:00:00,10:00:00,2,1\n",
+            ),
+            (
+                "shapes.txt",
+                "shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence\n\
+                 s1,40.6700,-73.9880,1\n\
+                 s1,40.6800,-73.9800,2\n\
+                 s2,40.6800,-73.9800,1\n\
+                 s2,40.6700,-73.9880,2\n",
+            ),
+        ];
+
+        for (name, contents) in files {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn indexes_feed() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = GtfsStaticIndex::from_zip(&write_test_feed(dir.path()), Some("MTA")).unwrap();
+
+        let summary = index.summary();
+        assert_eq!(summary.routes, 1);
+        assert_eq!(summary.stops, 3);
+        assert_eq!(summary.trips, 3);
+        assert_eq!(summary.shapes, 2);
+
+        let route = index.route("MTA NYCT_B63").unwrap();
+        assert_eq!(route.name, "B63");
+        assert_eq!(route.directions.len(), 2);
+        assert_eq!(route.directions[0].name, "COBBLE HILL");
+        assert_eq!(route.directions[0].stop_ids, ["MTA_3", "MTA_2", "MTA_1"]);
+        // the longer of the two direction 1 trips is kept
+        assert_eq!(route.directions[1].stop_ids, ["MTA_1", "MTA_2", "MTA_3"]);
+        assert_eq!(route.directions[1].shape_id.as_deref(), Some("s2"));
+        assert_eq!(
+            index.shape("s2").unwrap(),
+            [(40.68, -73.98), (40.67, -73.988)]
+        );
+
+        let mut nearby = index.stops_within(40.68, -73.98, 250.0);
+        nearby.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0].id, "MTA_1");
+        assert_eq!(nearby[1].id, "MTA_2");
+        assert_eq!(nearby[1].route_ids, ["MTA NYCT_B63"]);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod gtfs_static_store;
+pub mod types;
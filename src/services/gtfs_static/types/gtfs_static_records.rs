@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct GtfsAgencyRecord {
+    pub agency_id: Option<String>,
+    pub agency_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct GtfsRouteRecord {
+    pub route_id: String,
+    pub agency_id: Option<String>,
+    pub route_short_name: Option<String>,
+    pub route_long_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GtfsStopRecord {
+    pub stop_id: String,
+    pub stop_name: Option<String>,
+    pub stop_lat: Option<f64>,
+    pub stop_lon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct GtfsTripRecord {
+    pub route_id: String,
+    pub trip_id: String,
+    pub trip_headsign: Option<String>,
+    pub direction_id: Option<String>,
+    pub shape_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GtfsStopTimeRecord {
+    pub trip_id: String,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+}
+
+#[derive(Deserialize)]
+pub struct GtfsShapeRecord {
+    pub shape_id: String,
+    pub shape_pt_lat: f64,
+    pub shape_pt_lon: f64,
+    pub shape_pt_sequence: u32,
+}
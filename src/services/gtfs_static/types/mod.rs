@@ -0,0 +1 @@
+pub mod gtfs_static_records;
@@ -0,0 +1,320 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+
+use jsonwebtoken::{
+    decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, TokenData, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::services::api_keys::api_key_store::Scope;
+
+/// Algorithms accepted from a JWKS. Symmetric ones are left out so that a public key can never
+/// be used as an HMAC secret.
+const JWKS_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Clone)]
+pub enum JwtKeySource {
+    Hs256Secret(String),
+    /// A local JSON Web Key Set, as published by the identity provider.
+    JwksFile(PathBuf),
+}
+
+#[derive(Clone)]
+pub struct JwtConfig {
+    pub key: JwtKeySource,
+    /// Required in `aud`.
+    pub audience: String,
+    /// Required as `iss` when set.
+    pub issuer: Option<String>,
+    /// The `sub`s granted admin. The identity provider's own scopes never are.
+    pub admin_subjects: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum JwtError {
+    Io(String),
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for JwtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JwtError::Io(e) => write!(f, "Failed to read JWKS: {}", e),
+            JwtError::Parse(e) => write!(f, "Failed to parse JWKS: {}", e),
+            JwtError::Invalid(e) => write!(f, "Invalid token: {}", e),
+        }
+    }
+}
+
+/// The verified claims of a bearer token, added to the request's extensions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JwtClaims {
+    pub sub: String,
+    pub exp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    /// Space separated, as in OAuth 2.0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Every other claim, including `aud`.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl JwtClaims {
+    /// The scopes named in `scope` other than admin. Tokens without one can read arrivals and
+    /// audio.
+    pub fn scopes(&self) -> Vec<Scope> {
+        match &self.scope {
+            Some(scope) => scope
+                .split_whitespace()
+                .filter_map(Scope::parse)
+                .filter(|s| *s != Scope::Admin)
+                .collect(),
+            None => Scope::public(),
+        }
+    }
+}
+
+enum JwtKeys {
+    Secret(DecodingKey),
+    Jwks(JwkSet),
+}
+
+#[derive(Clone)]
+pub struct JwtVerifier {
+    keys: Arc<JwtKeys>,
+    audience: String,
+    issuer: Option<String>,
+    admin_subjects: Arc<Vec<String>>,
+}
+
+impl JwtVerifier {
+    /// Reads the JWKS file up front, so that a bad one fails at startup.
+    pub fn new(config: JwtConfig) -> Result<Self, JwtError> {
+        let keys = match config.key {
+            JwtKeySource::Hs256Secret(secret) => {
+                JwtKeys::Secret(DecodingKey::from_secret(secret.as_bytes()))
+            }
+            JwtKeySource::JwksFile(path) => {
+                let json = fs::read_to_string(&path).map_err(|e| JwtError::Io(e.to_string()))?;
+                JwtKeys::Jwks(
+                    serde_json::from_str(&json).map_err(|e| JwtError::Parse(e.to_string()))?,
+                )
+            }
+        };
+
+        Ok(JwtVerifier {
+            keys: Arc::new(keys),
+            audience: config.audience,
+            issuer: config.issuer,
+            admin_subjects: Arc::new(config.admin_subjects),
+        })
+    }
+
+    /// What a verified token may do: its own scopes, and admin for the configured subjects.
+    pub fn scopes(&self, claims: &JwtClaims) -> Vec<Scope> {
+        let mut scopes = claims.scopes();
+        if self.admin_subjects.contains(&claims.sub) {
+            scopes.push(Scope::Admin);
+        }
+
+        scopes
+    }
+
+    /// Checks the signature along with `exp`, `nbf`, `aud` and, when configured, `iss`.
+    pub fn verify(&self, token: &str) -> Result<JwtClaims, JwtError> {
+        let header = decode_header(token).map_err(|e| JwtError::Invalid(e.to_string()))?;
+
+        let (key, algorithm) = match self.keys.as_ref() {
+            JwtKeys::Secret(key) => (key.clone(), Algorithm::HS256),
+            JwtKeys::Jwks(jwks) => {
+                if !JWKS_ALGORITHMS.contains(&header.alg) {
+                    return Err(JwtError::Invalid(format!(
+                        "{:?} is not accepted",
+                        header.alg
+                    )));
+                }
+
+                // a set with a single key may be used without naming it
+                let jwk = match (&header.kid, jwks.keys.as_slice()) {
+                    (Some(kid), _) => jwks.find(kid),
+                    (None, [jwk]) => Some(jwk),
+                    (None, _) => None,
+                }
+                .ok_or(JwtError::Invalid("Unknown signing key".to_string()))?;
+
+                (
+                    DecodingKey::from_jwk(jwk).map_err(|e| JwtError::Invalid(e.to_string()))?,
+                    header.alg,
+                )
+            }
+        };
+
+        let mut validation = Validation::new(algorithm);
+        validation.validate_nbf = true;
+        validation.set_required_spec_claims(&["exp", "sub", "aud"]);
+        validation.set_audience(&[&self.audience]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        decode::<JwtClaims>(token, &key, &validation)
+            .map(|TokenData { claims, .. }| claims)
+            .map_err(|e| JwtError::Invalid(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::{Duration, Utc};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn sign_hs256(claims: serde_json::Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(b"shared"),
+        )
+        .unwrap()
+    }
+
+    /// A fresh P-256 key pair: the key to sign with, and a JWKS publishing it as `test-key`.
+    fn es256_key_pair() -> (EncodingKey, serde_json::Value) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+
+        // an uncompressed point: 0x04, then x and y
+        let point = pair.public_key().as_ref();
+        let jwks = json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "kid": "test-key",
+                "alg": "ES256",
+                "use": "sig",
+                "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+                "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }]
+        });
+
+        (EncodingKey::from_ec_der(pkcs8.as_ref()), jwks)
+    }
+
+    #[test]
+    fn verifies_hs256_tokens() {
+        let verifier = JwtVerifier::new(JwtConfig {
+            key: JwtKeySource::Hs256Secret("shared".to_string()),
+            audience: "overwatch".to_string(),
+            issuer: None,
+            admin_subjects: vec!["operator".to_string()],
+        })
+        .unwrap();
+
+        let exp = (Utc::now() + Duration::minutes(5)).timestamp();
+        let claims = verifier
+            .verify(&sign_hs256(json!({
+                "sub": "rider-1", "exp": exp, "aud": "overwatch", "email": "a@b.c", "scope": "admin"
+            })))
+            .unwrap();
+        assert_eq!(claims.sub, "rider-1");
+        assert_eq!(claims.extra["email"], "a@b.c");
+        // the identity provider can't hand out admin
+        assert!(verifier.scopes(&claims).is_empty());
+
+        let claims = verifier
+            .verify(&sign_hs256(
+                json!({ "sub": "operator", "exp": exp, "aud": "overwatch" }),
+            ))
+            .unwrap();
+        assert_eq!(verifier.scopes(&claims), Scope::all());
+
+        let expired = (Utc::now() - Duration::minutes(5)).timestamp();
+        let not_yet = (Utc::now() + Duration::minutes(5)).timestamp();
+        for claims in [
+            json!({ "sub": "rider-1", "exp": expired, "aud": "overwatch" }),
+            json!({ "sub": "rider-1", "exp": exp, "nbf": not_yet, "aud": "overwatch" }),
+            json!({ "sub": "rider-1", "exp": exp, "aud": "someone-else" }),
+            json!({ "sub": "rider-1", "exp": exp }),
+            json!({ "exp": exp, "aud": "overwatch" }),
+        ] {
+            assert!(verifier.verify(&sign_hs256(claims)).is_err());
+        }
+
+        let forged = encode(
+            &Header::new(Algorithm::HS256),
+            &json!({ "sub": "rider-1", "exp": exp, "aud": "overwatch" }),
+            &EncodingKey::from_secret(b"guessed"),
+        )
+        .unwrap();
+        assert!(verifier.verify(&forged).is_err());
+    }
+
+    #[test]
+    fn verifies_tokens_against_jwks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (private_key, jwks) = es256_key_pair();
+        let jwks_path = dir.path().join("jwks.json");
+        fs::write(&jwks_path, jwks.to_string()).unwrap();
+
+        let verifier = JwtVerifier::new(JwtConfig {
+            key: JwtKeySource::JwksFile(jwks_path),
+            audience: "overwatch".to_string(),
+            issuer: Some("https://id.example.com".to_string()),
+            admin_subjects: Vec::new(),
+        })
+        .unwrap();
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("test-key".to_string());
+
+        let token = encode(
+            &header,
+            &json!({
+                "sub": "rider-2",
+                "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+                "aud": "overwatch",
+                "iss": "https://id.example.com",
+                "scope": "arrivals:read openid"
+            }),
+            &private_key,
+        )
+        .unwrap();
+
+        let claims = verifier.verify(&token).unwrap();
+        assert_eq!(claims.sub, "rider-2");
+        assert_eq!(verifier.scopes(&claims), [Scope::ArrivalsRead]);
+
+        // an HMAC token cannot stand in for the key set's signatures
+        assert!(verifier
+            .verify(&sign_hs256(json!({
+                "sub": "rider-2",
+                "exp": (Utc::now() + Duration::minutes(5)).timestamp(),
+                "aud": "overwatch",
+                "iss": "https://id.example.com"
+            })))
+            .is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use crate::{
+    services::transit_service::transit_service::{
+        MultipleStopArrivals, TransitClientError, TransitService, VehicleFilter, VehiclePosition,
+    },
+    utils::shared_poll::{PollUpdate, SharedPoll},
+};
+
+pub type VehiclePositionsResult = Result<Vec<VehiclePosition>, TransitClientError>;
+
+/// Polls `TransitService` on behalf of every streaming client, so that clients watching the same
+/// stops or vehicles share one upstream request per interval.
+#[derive(Clone)]
+pub struct LivePoller {
+    transit_service: TransitService,
+    arrivals: SharedPoll<MultipleStopArrivals>,
+    vehicles: SharedPoll<VehiclePositionsResult>,
+}
+
+impl LivePoller {
+    pub fn new(transit_service: TransitService, interval: Duration) -> Self {
+        LivePoller {
+            transit_service,
+            arrivals: SharedPoll::new(interval),
+            vehicles: SharedPoll::new(interval),
+        }
+    }
+
+    /// The order of `stop_ids` does not matter.
+    pub fn subscribe_arrivals(
+        &self,
+        stop_ids: &[String],
+        limit_per_route: usize,
+    ) -> watch::Receiver<PollUpdate<MultipleStopArrivals>> {
+        let mut stop_ids = stop_ids.to_vec();
+        stop_ids.sort();
+        stop_ids.dedup();
+
+        let key = format!("{}|{}", stop_ids.join(","), limit_per_route);
+        let transit_service = self.transit_service.clone();
+
+        self.arrivals.subscribe(&key, move || {
+            let transit_service = transit_service.clone();
+            let stop_ids = stop_ids.clone();
+
+            async move {
+                transit_service
+                    .fetch_multiple_stop_arrivals(
+                        stop_ids.iter().map(|s| s.as_str()).collect(),
+                        limit_per_route,
+                    )
+                    .await
+            }
+        })
+    }
+
+    pub fn subscribe_vehicles(
+        &self,
+        filter: VehicleFilter,
+    ) -> watch::Receiver<PollUpdate<VehiclePositionsResult>> {
+        let key = format!("{:?}", filter);
+        let transit_service = self.transit_service.clone();
+
+        self.vehicles.subscribe(&key, move || {
+            let transit_service = transit_service.clone();
+            let filter = filter.clone();
+
+            async move { transit_service.fetch_vehicle_positions(&filter).await }
+        })
+    }
+}
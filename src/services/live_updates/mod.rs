@@ -0,0 +1 @@
+pub mod live_poller;
@@ -1,5 +1,12 @@
+use axum::body::Bytes;
+use serde::de::DeserializeOwned;
 use urlencoding::encode;
 
+use crate::utils::{
+    single_flight::SingleFlight,
+    upstream_client::{UpstreamClient, UpstreamConfig, UpstreamError},
+};
+
 use super::types::{
     google_autocomplete_response::GoogleAutocompleteResponse, maps_service_error::MapsServiceError,
 };
@@ -8,12 +15,14 @@ use super::types::{
 pub struct MapsServiceConfig {
     pub api_key: String,
     pub host: String,
+    pub upstream: UpstreamConfig,
 }
 
 #[derive(Clone)]
 pub struct MapsService {
     config: MapsServiceConfig,
-    client: reqwest::Client,
+    client: UpstreamClient,
+    in_flight: SingleFlight<Result<Bytes, MapsServiceError>>,
 }
 
 pub struct AutocompleteSearchInput {
@@ -35,11 +44,33 @@ pub struct AutocompleteSearchOutput {
 impl MapsService {
     pub fn new(config: MapsServiceConfig) -> Self {
         Self {
+            client: UpstreamClient::new("Google Maps", config.upstream.clone()),
             config,
-            client: reqwest::Client::new(),
+            in_flight: SingleFlight::default(),
         }
     }
 
+    async fn fetch_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, MapsServiceError> {
+        let client = self.client.clone();
+        let owned_url = url.to_string();
+
+        // concurrent requests for the same url share one upstream call
+        let body = self
+            .in_flight
+            .run(url, async move {
+                client.get(&owned_url).await.map_err(|e| match e {
+                    UpstreamError::Unavailable(retry_after) => {
+                        MapsServiceError::Unavailable(retry_after)
+                    }
+                    _ => MapsServiceError::Internal(format!("Failed to send request: {}", e)),
+                })
+            })
+            .await?;
+
+        serde_json::from_slice::<T>(&body)
+            .map_err(|e| MapsServiceError::Internal(format!("Failed to get response body: {}", e)))
+    }
+
     pub async fn get_autocomplete(
         &self,
         input: AutocompleteSearchInput,
@@ -53,17 +84,7 @@ impl MapsService {
             self.config.api_key
         );
 
-        let resp =
-            self.client.get(&url).send().await.map_err(|e| {
-                MapsServiceError::Internal(format!("Failed to send request: {}", e))
-            })?;
-
-        let body = resp
-            .json::<GoogleAutocompleteResponse>()
-            .await
-            .map_err(|e| {
-                MapsServiceError::Internal(format!("Failed to get response body: {}", e))
-            })?;
+        let body = self.fetch_json::<GoogleAutocompleteResponse>(&url).await?;
 
         Ok(AutocompleteSearchOutput {
             predictions: body
@@ -87,14 +108,7 @@ impl MapsService {
             self.config.host, place_id, self.config.api_key
         );
 
-        let resp =
-            self.client.get(&url).send().await.map_err(|e| {
-                MapsServiceError::Internal(format!("Failed to send request: {}", e))
-            })?;
-
-        let body = resp.json::<serde_json::Value>().await.map_err(|e| {
-            MapsServiceError::Internal(format!("Failed to get response body: {}", e))
-        })?;
+        let body = self.fetch_json::<serde_json::Value>(&url).await?;
 
         let lat = body
             .get("result")
@@ -1,11 +1,17 @@
+use std::time::Duration;
+
+#[derive(Clone)]
 pub enum MapsServiceError {
     Internal(String),
+    /// Google's circuit breaker is open, for about this long.
+    Unavailable(Duration),
 }
 
 impl std::fmt::Display for MapsServiceError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             MapsServiceError::Internal(e) => write!(f, "Internal error: {}", e),
+            MapsServiceError::Unavailable(_) => write!(f, "Google Maps is unavailable"),
         }
     }
 }
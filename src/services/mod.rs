@@ -1,2 +1,11 @@
+pub mod announcements;
+pub mod api_keys;
+pub mod departure_advice;
+pub mod gtfs_realtime;
+pub mod gtfs_static;
+pub mod jwt;
+pub mod live_updates;
 pub mod maps_client;
+pub mod notifications;
 pub mod transit_service;
+pub mod user_data;
@@ -0,0 +1,2 @@
+pub mod notification_scheduler;
+pub mod webhook_sender;
@@ -0,0 +1,353 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, time::MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::services::{
+    transit_service::transit_service::{StopInformation, TransitService},
+    user_data::user_data_store::{NotificationRule, UserDataStore},
+};
+
+use super::webhook_sender::WebhookSender;
+
+/// Enough arrivals per route and direction to still see a bus that is about to be overtaken.
+const ARRIVALS_PER_ROUTE: usize = 3;
+
+/// Webhooks called at once, so that slow receivers don't hold up everyone else's.
+const MAX_CONCURRENT_DELIVERIES: usize = 16;
+
+/// Keys of the arrivals already notified, by client and rule name.
+type SentArrivals = HashMap<(String, String), HashSet<String>>;
+
+/// The JSON body of each webhook call.
+#[derive(Serialize, Deserialize)]
+pub struct ArrivalNotification {
+    pub rule: String,
+    pub stop_id: String,
+    pub route_id: String,
+    pub route_label: String,
+    pub direction_id: String,
+    pub vehicle_id: Option<String>,
+    pub destination_name: Option<String>,
+    pub expected_arrival_time: String,
+    pub minutes_until_arrival: i64,
+    /// Unix seconds, signed along with the rest so that a captured call can't be replayed later.
+    pub sent_at: i64,
+}
+
+impl ArrivalNotification {
+    fn new(rule: &NotificationRule, arrival: &StopInformation) -> Self {
+        ArrivalNotification {
+            rule: rule.name.clone(),
+            stop_id: arrival.stop_id.clone(),
+            route_id: arrival.route_id.clone(),
+            route_label: arrival.route_label.clone(),
+            direction_id: arrival.direction_id.clone(),
+            vehicle_id: arrival.vehicle_id.clone(),
+            destination_name: arrival.destination_name.clone(),
+            expected_arrival_time: arrival.expected_arrival_time.clone(),
+            minutes_until_arrival: arrival.minutes_until_arrival,
+            sent_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Identifies the trip an arrival belongs to across polls. Without a vehicle, the scheduled time
+/// is the most stable thing left.
+fn arrival_key(arrival: &StopInformation) -> String {
+    arrival
+        .vehicle_id
+        .clone()
+        .or(arrival.aimed_arrival_time.clone())
+        .unwrap_or_else(|| arrival.expected_arrival_time.clone())
+}
+
+/// Polls the arrivals that notification rules watch and calls their webhooks once per bus.
+#[derive(Clone)]
+pub struct NotificationScheduler {
+    transit_service: TransitService,
+    user_data: Arc<dyn UserDataStore>,
+    webhooks: WebhookSender,
+    /// Only kept in memory, so a restart can repeat a notification for a bus that is still
+    /// approaching.
+    notified: Arc<Mutex<SentArrivals>>,
+}
+
+impl NotificationScheduler {
+    pub fn new(
+        transit_service: TransitService,
+        user_data: Arc<dyn UserDataStore>,
+        webhooks: WebhookSender,
+    ) -> Self {
+        NotificationScheduler {
+            transit_service,
+            user_data,
+            webhooks,
+            notified: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Checks every rule each `interval` for as long as the runtime lives.
+    pub fn spawn(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                self.run_once().await;
+            }
+        });
+    }
+
+    /// Drops expired rules, then fires the webhooks of rules whose condition is newly met.
+    /// Returns how many webhooks were delivered.
+    pub async fn run_once(&self) -> usize {
+        let now = Utc::now();
+
+        match self.user_data.delete_expired_notification_rules(now).await {
+            Ok(0) => {}
+            Ok(count) => info!("Removed {} expired notification rules", count),
+            Err(e) => warn!("Failed to remove expired notification rules: {}", e),
+        }
+
+        let rules = match self.user_data.all_notification_rules().await {
+            Ok(rules) => rules
+                .into_iter()
+                .filter(|(_, rule)| rule.expires_at > now)
+                .collect::<Vec<(String, NotificationRule)>>(),
+            Err(e) => {
+                error!("Failed to read notification rules: {}", e);
+                return 0;
+            }
+        };
+
+        let mut stop_ids = rules
+            .iter()
+            .map(|(_, rule)| rule.stop_id.clone())
+            .collect::<Vec<String>>();
+        stop_ids.sort();
+        stop_ids.dedup();
+
+        let results = join_all(stop_ids.iter().map(|stop_id| {
+            self.transit_service
+                .fetch_stop_info(stop_id, ARRIVALS_PER_ROUTE)
+        }))
+        .await;
+
+        let arrivals = stop_ids
+            .into_iter()
+            .zip(results)
+            .filter_map(|(stop_id, result)| match result {
+                Ok(arrivals) => Some((stop_id, arrivals)),
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch arrivals at {} for notifications: {}",
+                        stop_id, e
+                    );
+                    None
+                }
+            })
+            .collect::<HashMap<String, Vec<StopInformation>>>();
+
+        let mut deliveries = Vec::new();
+        {
+            let mut notified = self.notified.lock().unwrap();
+            notified.retain(|(client_id, name), _| {
+                rules
+                    .iter()
+                    .any(|(c, rule)| c == client_id && &rule.name == name)
+            });
+
+            for (client_id, rule) in rules.iter() {
+                // what was already sent is kept as is while the stop cannot be read
+                let Some(stop_arrivals) = arrivals.get(&rule.stop_id) else {
+                    continue;
+                };
+                let route_arrivals = stop_arrivals
+                    .iter()
+                    .filter(|a| a.route_id == rule.route_id)
+                    .collect::<Vec<&StopInformation>>();
+
+                let sent = notified
+                    .entry((client_id.clone(), rule.name.clone()))
+                    .or_default();
+                // buses that dropped out of the arrivals have passed the stop
+                sent.retain(|key| route_arrivals.iter().any(|a| &arrival_key(a) == key));
+
+                for arrival in route_arrivals {
+                    if arrival.minutes_until_arrival <= rule.minutes_before
+                        && sent.insert(arrival_key(arrival))
+                    {
+                        deliveries.push((rule.clone(), ArrivalNotification::new(rule, arrival)));
+                    }
+                }
+            }
+        }
+
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+        let handles = deliveries
+            .into_iter()
+            .map(|(rule, notification)| {
+                let (permits, webhooks) = (permits.clone(), self.webhooks.clone());
+
+                tokio::spawn(async move {
+                    let _permit = permits.acquire_owned().await.unwrap();
+                    let body = serde_json::to_vec(&notification).unwrap();
+
+                    webhooks
+                        .send(&rule.webhook_url, &rule.secret, &body)
+                        .await
+                        .map_err(|e| warn!("Failed to notify for rule {}: {}", rule.name, e))
+                        .is_ok()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        join_all(handles)
+            .await
+            .into_iter()
+            .filter(|delivered| matches!(delivered, Ok(true)))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+
+    use crate::{
+        services::{
+            maps_client::maps_service::{MapsService, MapsServiceConfig},
+            notifications::webhook_sender::{sign, WebhookConfig, SIGNATURE_HEADER},
+            transit_service::{
+                providers::fake_transit_provider::FakeTransitProvider,
+                stale_arrivals::StaleArrivals, transit_provider::TransitProvider,
+                transit_service::TransitServiceConfig,
+            },
+            user_data::sqlite_user_data_store::SqliteUserDataStore,
+        },
+        utils::upstream_client::UpstreamConfig,
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Receiver {
+        attempts: Arc<AtomicUsize>,
+        received: Arc<Mutex<Vec<(String, Bytes)>>>,
+    }
+
+    /// Stands in for a client's webhook endpoint, failing the first call to force a retry.
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        if receiver.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+        receiver.received.lock().unwrap().push((signature, body));
+        StatusCode::NO_CONTENT
+    }
+
+    fn rule(name: &str, url: &str, expires_in: chrono::Duration) -> NotificationRule {
+        NotificationRule {
+            name: name.to_string(),
+            stop_id: "MTA_308209".to_string(),
+            route_id: "B63".to_string(),
+            minutes_before: 5,
+            webhook_url: url.to_string(),
+            secret: "shh".to_string(),
+            expires_at: Utc::now() + expires_in,
+        }
+    }
+
+    #[tokio::test]
+    async fn notifies_once_per_bus() {
+        let receiver = Receiver::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let router = Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let provider = FakeTransitProvider {
+            name: "fake".to_string(),
+            arrivals: HashMap::from([(
+                "MTA_308209".to_string(),
+                vec![
+                    ("B63".to_string(), 3),
+                    ("B63".to_string(), 12),
+                    ("B61".to_string(), 2),
+                ],
+            )]),
+            ..Default::default()
+        };
+
+        let user_data = Arc::new(SqliteUserDataStore::open_in_memory().unwrap());
+        user_data
+            .save_notification_rule("alice", rule("Morning", &url, chrono::Duration::hours(1)))
+            .await
+            .unwrap();
+        user_data
+            .save_notification_rule("bob", rule("Old", &url, chrono::Duration::seconds(-1)))
+            .await
+            .unwrap();
+
+        let scheduler = NotificationScheduler::new(
+            TransitService::new(TransitServiceConfig {
+                providers: vec![Arc::new(provider) as Arc<dyn TransitProvider>],
+                maps_service: MapsService::new(MapsServiceConfig {
+                    host: "http://localhost".to_string(),
+                    api_key: "key".to_string(),
+                    upstream: UpstreamConfig::default(),
+                }),
+                stale_arrivals: StaleArrivals::default(),
+            }),
+            user_data.clone(),
+            WebhookSender::new(WebhookConfig {
+                retry_delay: Duration::from_millis(10),
+                allow_private_addresses: true,
+                ..Default::default()
+            }),
+        );
+
+        assert_eq!(scheduler.run_once().await, 1);
+        // the same bus is still 3 minutes away, which was already sent
+        assert_eq!(scheduler.run_once().await, 0);
+
+        assert_eq!(receiver.attempts.load(Ordering::SeqCst), 2);
+        let (signature, body) = receiver.received.lock().unwrap()[0].clone();
+        assert_eq!(signature, sign("shh", &body));
+
+        let notification: ArrivalNotification = serde_json::from_slice(&body).unwrap();
+        assert_eq!(notification.rule, "Morning");
+        assert_eq!(notification.vehicle_id.as_deref(), Some("B63_0"));
+        assert_eq!(notification.minutes_until_arrival, 3);
+
+        assert!(user_data
+            .list_notification_rules("bob")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
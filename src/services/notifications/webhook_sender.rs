@@ -0,0 +1,221 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use reqwest::{redirect, StatusCode, Url};
+use sha2::Sha256;
+use tracing::warn;
+
+/// Carries `sha256=<hex HMAC of the body>`, keyed by the rule's secret. Bodies carry the time
+/// they were sent, so that receivers can turn away replays.
+pub const SIGNATURE_HEADER: &str = "X-Overwatch-Signature";
+
+#[derive(Clone)]
+pub struct WebhookConfig {
+    pub max_attempts: u32,
+    /// Doubled after every failed attempt.
+    pub retry_delay: Duration,
+    pub timeout: Duration,
+    /// Lets webhooks reach loopback, link-local and private addresses, which only tests should.
+    pub allow_private_addresses: bool,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(2),
+            timeout: Duration::from_secs(5),
+            allow_private_addresses: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WebhookError {
+    /// The URL is invalid, can't be resolved, or points into a private network.
+    Blocked(String),
+    /// The receiver answered with a status that retrying will not change.
+    Rejected(StatusCode),
+    /// Every attempt failed, the last one for this reason.
+    Failed(String),
+}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WebhookError::Blocked(reason) => write!(f, "Webhook URL not allowed: {}", reason),
+            WebhookError::Rejected(status) => write!(f, "Webhook rejected with {}", status),
+            WebhookError::Failed(e) => write!(f, "Webhook failed: {}", e),
+        }
+    }
+}
+
+/// Whether `ip` is reachable from the internet rather than loopback, link-local, private or
+/// otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // carrier-grade NAT
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local and link-local
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves the host of an http(s) `url`, failing unless every address it resolves to is public.
+pub async fn resolve_webhook_url(
+    url: &str,
+    allow_private_addresses: bool,
+) -> Result<(Url, Vec<SocketAddr>), WebhookError> {
+    let url = Url::parse(url).map_err(|e| WebhookError::Blocked(e.to_string()))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(WebhookError::Blocked("must be http or https".to_string()));
+    }
+
+    let host = url
+        .host_str()
+        .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string())
+        .ok_or(WebhookError::Blocked("has no host".to_string()))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| WebhookError::Blocked(format!("cannot resolve {}", host)))?
+        .collect::<Vec<SocketAddr>>();
+
+    match addrs.is_empty() {
+        true => return Err(WebhookError::Blocked(format!("cannot resolve {}", host))),
+        false if !allow_private_addresses && !addrs.iter().all(|a| is_public(a.ip())) => {
+            return Err(WebhookError::Blocked(format!(
+                "{} is not a public address",
+                host
+            )))
+        }
+        false => {}
+    }
+
+    Ok((url, addrs))
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[derive(Clone)]
+pub struct WebhookSender {
+    config: WebhookConfig,
+}
+
+impl WebhookSender {
+    pub fn new(config: WebhookConfig) -> Self {
+        WebhookSender { config }
+    }
+
+    /// POSTs `body` to `url`, retrying connection failures, timeouts, 429s and 5xxs. The host is
+    /// checked again before sending, and connected to at the addresses that were checked.
+    pub async fn send(&self, url: &str, secret: &str, body: &[u8]) -> Result<(), WebhookError> {
+        let (url, addrs) = resolve_webhook_url(url, self.config.allow_private_addresses).await?;
+        // a redirect could lead anywhere, so they are not followed
+        let client = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs)
+            .build()
+            .map_err(|e| WebhookError::Failed(e.to_string()))?;
+        let signature = sign(secret, body);
+        let mut delay = self.config.retry_delay;
+        let mut attempt = 1;
+
+        loop {
+            let result = client
+                .post(url.clone())
+                .timeout(self.config.timeout)
+                .header("content-type", "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.to_vec())
+                .send()
+                .await;
+
+            let reason = match result {
+                Ok(res) if res.status().is_success() => return Ok(()),
+                Ok(res)
+                    if res.status() != StatusCode::TOO_MANY_REQUESTS
+                        && !res.status().is_server_error() =>
+                {
+                    return Err(WebhookError::Rejected(res.status()));
+                }
+                Ok(res) => res.status().to_string(),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= self.config.max_attempts {
+                return Err(WebhookError::Failed(reason));
+            }
+
+            warn!(
+                "Webhook attempt {} to {} failed with {}, retrying",
+                attempt, url, reason
+            );
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn blocks_private_addresses() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://10.1.2.3/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "ftp://93.184.215.14/hook",
+        ] {
+            assert!(
+                matches!(
+                    resolve_webhook_url(url, false).await,
+                    Err(WebhookError::Blocked(_))
+                ),
+                "{}",
+                url
+            );
+        }
+
+        let (_, addrs) = resolve_webhook_url("https://93.184.215.14/hook", false)
+            .await
+            .unwrap();
+        assert_eq!(addrs, ["93.184.215.14:443".parse().unwrap()]);
+        assert!(resolve_webhook_url("http://127.0.0.1/hook", true)
+            .await
+            .is_ok());
+    }
+}
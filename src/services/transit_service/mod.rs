@@ -1,2 +1,6 @@
+pub mod providers;
+pub mod stale_arrivals;
+pub mod transit_provider;
+#[allow(clippy::module_inception)]
 pub mod transit_service;
 pub mod types;
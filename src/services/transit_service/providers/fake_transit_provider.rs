@@ -0,0 +1,160 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::async_trait;
+
+use crate::{
+    services::transit_service::{
+        transit_provider::{NearbyStop, TransitProvider},
+        transit_service::{
+            AlertFilter, FindTransitRoutesResult, FindTransitRoutesResultRoute,
+            GetStopsForRouteResult, ServiceAlert, StopInformation, TransitClientError,
+            VehicleFilter, VehiclePosition,
+        },
+    },
+    utils::cache_control::CachePolicy,
+};
+
+/// An in-memory provider for exercising `TransitService` without an upstream. When `failing` is
+/// set every call returns an internal error.
+#[derive(Clone, Default)]
+pub struct FakeTransitProvider {
+    pub name: String,
+    /// (id, name) pairs. The agency is the part of the ID before the first `_`.
+    pub routes: Vec<(String, String)>,
+    /// Minutes until arrival by route ID, keyed by stop ID. Each arrival's vehicle is named after
+    /// its route and position in the list.
+    pub arrivals: HashMap<String, Vec<(String, i64)>>,
+    /// Returned by `get_stops_near` whatever the location, the service filters by distance.
+    pub stops: Vec<NearbyStop>,
+    pub vehicles: Vec<VehiclePosition>,
+    pub alerts: Vec<ServiceAlert>,
+    pub failing: bool,
+}
+
+impl FakeTransitProvider {
+    fn check(&self) -> Result<(), TransitClientError> {
+        match self.failing {
+            true => Err(TransitClientError::Internal(format!(
+                "{} is down",
+                self.name
+            ))),
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TransitProvider for FakeTransitProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn with_cache_policy(&self, _policy: CachePolicy) -> Arc<dyn TransitProvider> {
+        Arc::new(self.clone())
+    }
+
+    async fn get_routes(
+        &self,
+        search: &str,
+        agency_id: Option<&str>,
+    ) -> Result<FindTransitRoutesResult, TransitClientError> {
+        self.check()?;
+
+        Ok(FindTransitRoutesResult {
+            routes: self
+                .routes
+                .iter()
+                .filter(|(_, name)| name.to_lowercase().contains(&search.to_lowercase()))
+                .map(|(id, name)| FindTransitRoutesResultRoute {
+                    id: id.clone(),
+                    agency_id: id.split('_').next().unwrap_or_default().to_string(),
+                    name: name.clone(),
+                })
+                .filter(|r| agency_id.map_or(true, |a| r.agency_id == a))
+                .collect(),
+        })
+    }
+
+    async fn get_stops_for_route(
+        &self,
+        _route_id: &str,
+        _include_polylines: bool,
+    ) -> Result<GetStopsForRouteResult, TransitClientError> {
+        self.check()?;
+
+        Err(TransitClientError::ResourceNotFound)
+    }
+
+    async fn get_stops_near(
+        &self,
+        _lat: f64,
+        _lon: f64,
+        _radius_meters: f64,
+    ) -> Result<Vec<NearbyStop>, TransitClientError> {
+        self.check()?;
+
+        Ok(self.stops.clone())
+    }
+
+    async fn fetch_stop_info(
+        &self,
+        stop_id: &str,
+        _limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        self.check()?;
+
+        let arrivals = self
+            .arrivals
+            .get(stop_id)
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        Ok(arrivals
+            .iter()
+            .enumerate()
+            .map(|(i, (route_id, minutes))| StopInformation {
+                expected_arrival_time: (chrono::Utc::now() + chrono::Duration::minutes(*minutes))
+                    .to_rfc3339(),
+                minutes_until_arrival: *minutes,
+                stop_id: stop_id.to_string(),
+                route_label: route_id.clone(),
+                route_id: route_id.clone(),
+                vehicle_id: Some(format!("{}_{}", route_id, i)),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError> {
+        self.check()?;
+
+        Ok(self
+            .vehicles
+            .iter()
+            .filter(|v| match filter {
+                VehicleFilter::Route(id) => v.route_id == *id,
+                VehicleFilter::Vehicle(id) => v.vehicle_id == *id,
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn fetch_alerts(
+        &self,
+        filter: &AlertFilter,
+    ) -> Result<Vec<ServiceAlert>, TransitClientError> {
+        self.check()?;
+
+        Ok(self
+            .alerts
+            .iter()
+            .filter(|a| match filter {
+                AlertFilter::Stop(id) => a.stop_ids.contains(id),
+                AlertFilter::Route(id) => a.route_ids.contains(id),
+            })
+            .cloned()
+            .collect())
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+use crate::{
+    services::{
+        gtfs_realtime::gtfs_realtime_feed::GtfsRealtimeFeed,
+        gtfs_static::gtfs_static_store::GtfsStaticStore,
+        transit_service::{
+            transit_provider::{NearbyStop, TransitProvider},
+            transit_service::{
+                FindTransitRoutesResult, FindTransitRoutesResultRoute, GetStopsForRouteResult,
+                GetStopsForRouteResultGroup, GetStopsForRouteResultGroupStop, StopInformation,
+                TransitClientError, VehicleFilter, VehiclePosition,
+            },
+        },
+    },
+    utils::{cache_control::CachePolicy, polyline::encode_polyline},
+};
+
+#[derive(Clone)]
+pub struct GtfsProviderConfig {
+    pub name: String,
+    /// Routes and stops come from whichever feed is currently loaded in the store, so a reload
+    /// takes effect without rebuilding the provider.
+    pub gtfs_static: GtfsStaticStore,
+    pub realtime: Option<GtfsRealtimeFeed>,
+}
+
+/// Routes and stops from a GTFS static feed, with arrivals from GTFS-Realtime feeds.
+#[derive(Clone)]
+pub struct GtfsProvider {
+    config: GtfsProviderConfig,
+}
+
+impl GtfsProvider {
+    pub fn new(config: GtfsProviderConfig) -> Self {
+        GtfsProvider { config }
+    }
+}
+
+#[async_trait]
+impl TransitProvider for GtfsProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn with_cache_policy(&self, _policy: CachePolicy) -> Arc<dyn TransitProvider> {
+        Arc::new(self.clone())
+    }
+
+    async fn get_routes(
+        &self,
+        search: &str,
+        agency_id: Option<&str>,
+    ) -> Result<FindTransitRoutesResult, TransitClientError> {
+        let routes = match self.config.gtfs_static.index() {
+            Some(index) => index
+                .find_routes(search)
+                .iter()
+                .filter(|r| agency_id.map_or(true, |a| r.agency_id == a))
+                .map(|r| FindTransitRoutesResultRoute {
+                    id: r.id.clone(),
+                    agency_id: r.agency_id.clone(),
+                    name: r.name.clone(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(FindTransitRoutesResult { routes })
+    }
+
+    async fn get_stops_for_route(
+        &self,
+        route_id: &str,
+        include_polylines: bool,
+    ) -> Result<GetStopsForRouteResult, TransitClientError> {
+        let index = self
+            .config
+            .gtfs_static
+            .index()
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        let route = index
+            .route(route_id)
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        Ok(GetStopsForRouteResult {
+            groups: route
+                .directions
+                .iter()
+                .map(|d| GetStopsForRouteResultGroup {
+                    id: d.id.clone(),
+                    name: d.name.clone(),
+                    route_id: route.id.clone(),
+                    route_name: route.name.clone(),
+                    stops: d
+                        .stop_ids
+                        .iter()
+                        .filter_map(|id| index.stop(id))
+                        .map(|s| GetStopsForRouteResultGroupStop {
+                            id: s.id.clone(),
+                            name: s.name.clone(),
+                            lat: Some(s.lat),
+                            lon: Some(s.lon),
+                        })
+                        .collect(),
+                    polylines: d
+                        .shape_id
+                        .as_deref()
+                        .filter(|_| include_polylines)
+                        .and_then(|id| index.shape(id))
+                        .map(|shape| vec![encode_polyline(shape)])
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+
+    async fn get_stops_near(
+        &self,
+        lat: f64,
+        lon: f64,
+        radius_meters: f64,
+    ) -> Result<Vec<NearbyStop>, TransitClientError> {
+        let stops = match self.config.gtfs_static.index() {
+            Some(index) => index
+                .stops_within(lat, lon, radius_meters)
+                .iter()
+                .map(|s| NearbyStop {
+                    id: s.id.clone(),
+                    name: s.name.clone(),
+                    lat: s.lat,
+                    lon: s.lon,
+                    direction: None,
+                    route_ids: s.route_ids.clone(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(stops)
+    }
+
+    async fn fetch_stop_info(
+        &self,
+        stop_id: &str,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        let feed = self
+            .config
+            .realtime
+            .as_ref()
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        let index = self.config.gtfs_static.index();
+
+        // leave stops the loaded feed doesn't know about to the other providers
+        if let Some(index) = &index {
+            if index.stop(stop_id).is_none() {
+                return Err(TransitClientError::ResourceNotFound);
+            }
+        }
+
+        let arrivals = feed
+            .fetch_stop_info(stop_id, index.as_deref(), limit_per_route)
+            .await?;
+
+        // without a static feed, a stop the realtime feeds say nothing about may be another
+        // provider's
+        if index.is_none() && arrivals.is_empty() {
+            return Err(TransitClientError::ResourceNotFound);
+        }
+
+        Ok(arrivals)
+    }
+
+    async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError> {
+        let feed = self
+            .config
+            .realtime
+            .as_ref()
+            .ok_or(TransitClientError::ResourceNotFound)?;
+
+        let index = self.config.gtfs_static.index();
+
+        if let (Some(index), VehicleFilter::Route(route_id)) = (&index, filter) {
+            if index.route(route_id).is_none() {
+                return Err(TransitClientError::ResourceNotFound);
+            }
+        }
+
+        feed.fetch_vehicle_positions(filter, index.as_deref()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        services::gtfs_realtime::gtfs_realtime_feed::{GtfsRealtimeConfig, GtfsRealtimeSource},
+        utils::upstream_client::UpstreamConfig,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn leaves_unknown_stops_to_other_providers_without_a_static_feed() {
+        let provider = GtfsProvider::new(GtfsProviderConfig {
+            name: "gtfs".to_string(),
+            gtfs_static: GtfsStaticStore::default(),
+            realtime: Some(GtfsRealtimeFeed::new(GtfsRealtimeConfig {
+                trip_updates: Some(GtfsRealtimeSource::parse(
+                    "src/services/gtfs_realtime/fixtures/trip_updates.pb",
+                )),
+                vehicle_positions: None,
+                cache_ttl: Duration::from_secs(15),
+                upstream: UpstreamConfig::default(),
+            })),
+        });
+
+        assert!(matches!(
+            provider.fetch_stop_info("MTA_308209", 1).await,
+            Err(TransitClientError::ResourceNotFound)
+        ));
+    }
+}
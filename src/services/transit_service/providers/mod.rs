@@ -0,0 +1,4 @@
+#[cfg(test)]
+pub mod fake_transit_provider;
+pub mod gtfs_provider;
+pub mod one_bus_away_provider;
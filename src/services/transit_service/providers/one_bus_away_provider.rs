@@ -0,0 +1,842 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use ::futures::future::join_all;
+use axum::{async_trait, body::Bytes};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use tracing::warn;
+use urlencoding::encode;
+
+use crate::{
+    services::transit_service::{
+        transit_provider::{NearbyStop, TransitProvider},
+        transit_service::{
+            AlertFilter, FindTransitRoutesResult, FindTransitRoutesResultRoute,
+            GetStopsForRouteResult, GetStopsForRouteResultGroup, GetStopsForRouteResultGroupStop,
+            ServiceAlert, StopInformation, TransitClientError, VehicleFilter, VehiclePosition,
+        },
+        types::{
+            mta_get_agencies_with_coverage_response::GetAgenciesWithCoverageResponse,
+            mta_get_routes_response::GetRoutesResponse,
+            mta_get_stop_response::GetStopInfoResponse,
+            mta_get_stops_at_location_response::GetStopsAtLocationResponse,
+            mta_get_stops_for_route_response::{
+                GetStopsForRouteResponse, GetStopsForRouteResponseDataEntryStopGroupingStopGroup,
+                GetStopsForRouteResponseDataReferencesStop,
+            },
+            mta_get_vehicle_monitoring_response::GetVehicleMonitoringResponse,
+            mta_situation_exchange_delivery::SituationExchangeDelivery,
+        },
+    },
+    utils::{
+        cache_control::CachePolicy,
+        single_flight::SingleFlight,
+        ttl_cache::{CacheStats, TtlCache},
+        upstream_client::{UpstreamClient, UpstreamConfig, UpstreamError},
+    },
+};
+
+#[derive(Clone)]
+pub struct TransitCacheConfig {
+    pub max_entries: usize,
+    pub routes_ttl: Duration,
+    pub stops_ttl: Duration,
+    pub stop_monitoring_ttl: Duration,
+}
+
+impl Default for TransitCacheConfig {
+    fn default() -> Self {
+        TransitCacheConfig {
+            max_entries: 2000,
+            routes_ttl: Duration::from_secs(6 * 60 * 60),
+            stops_ttl: Duration::from_secs(6 * 60 * 60),
+            stop_monitoring_ttl: Duration::from_secs(15),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OneBusAwayProviderConfig {
+    pub name: String,
+    pub host: String,
+    pub api_key: String,
+    /// Agencies whose routes are searched, e.g. `MTA NYCT` and `MTABC`. When unset, every agency
+    /// listed by `agencies-with-coverage` is searched.
+    pub agency_ids: Option<Vec<String>>,
+    pub cache: TransitCacheConfig,
+    /// Timeouts, retries and circuit breaking for the deployment's API.
+    pub upstream: UpstreamConfig,
+}
+
+/// Routes and stops from a OneBusAway deployment's REST API, with arrivals from its SIRI
+/// `stop-monitoring` endpoint. MTA Bus Time is one such deployment.
+#[derive(Clone)]
+pub struct OneBusAwayProvider {
+    config: OneBusAwayProviderConfig,
+    client: UpstreamClient,
+    cache: TtlCache<Bytes>,
+    cache_policy: CachePolicy,
+    in_flight: SingleFlight<Result<Bytes, TransitClientError>>,
+}
+
+#[derive(Clone, Copy)]
+enum TransitEndpoint {
+    AgenciesWithCoverage,
+    RoutesForAgency,
+    StopsForRoute,
+    StopsForLocation,
+    StopMonitoring,
+    VehicleMonitoring,
+}
+
+impl TransitEndpoint {
+    fn name(&self) -> &'static str {
+        match self {
+            TransitEndpoint::AgenciesWithCoverage => "agencies-with-coverage",
+            TransitEndpoint::RoutesForAgency => "routes-for-agency",
+            TransitEndpoint::StopsForRoute => "stops-for-route",
+            TransitEndpoint::StopsForLocation => "stops-for-location",
+            TransitEndpoint::StopMonitoring => "stop-monitoring",
+            TransitEndpoint::VehicleMonitoring => "vehicle-monitoring",
+        }
+    }
+}
+
+impl OneBusAwayProvider {
+    pub fn new(config: OneBusAwayProviderConfig) -> Self {
+        let request_client = UpstreamClient::new(&config.name, config.upstream.clone());
+        let cache = TtlCache::new(config.cache.max_entries);
+
+        OneBusAwayProvider {
+            config,
+            client: request_client,
+            cache,
+            cache_policy: CachePolicy::Use,
+            in_flight: SingleFlight::default(),
+        }
+    }
+
+    fn cache_ttl(&self, endpoint: TransitEndpoint) -> Duration {
+        match endpoint {
+            TransitEndpoint::AgenciesWithCoverage | TransitEndpoint::RoutesForAgency => {
+                self.config.cache.routes_ttl
+            }
+            TransitEndpoint::StopsForRoute | TransitEndpoint::StopsForLocation => {
+                self.config.cache.stops_ttl
+            }
+            TransitEndpoint::StopMonitoring | TransitEndpoint::VehicleMonitoring => {
+                self.config.cache.stop_monitoring_ttl
+            }
+        }
+    }
+
+    async fn agency_ids(&self) -> Result<Vec<String>, TransitClientError> {
+        if let Some(agency_ids) = &self.config.agency_ids {
+            return Ok(agency_ids.clone());
+        }
+
+        Ok(self
+            .fetch_json::<GetAgenciesWithCoverageResponse>(
+                TransitEndpoint::AgenciesWithCoverage,
+                &format!(
+                    "{}/api/where/agencies-with-coverage.json?key={}",
+                    self.config.host, self.config.api_key
+                ),
+            )
+            .await?
+            .data
+            .list
+            .into_iter()
+            .map(|a| a.agencyId)
+            .collect())
+    }
+
+    async fn get_routes_for_agency(
+        &self,
+        agency_id: &str,
+        search: &str,
+    ) -> Result<Vec<FindTransitRoutesResultRoute>, TransitClientError> {
+        Ok(self
+            .fetch_json::<GetRoutesResponse>(
+                TransitEndpoint::RoutesForAgency,
+                &format!(
+                    "{}/api/where/routes-for-agency/{}.json?key={}",
+                    self.config.host,
+                    encode(agency_id),
+                    self.config.api_key
+                ),
+            )
+            .await?
+            .data
+            .list
+            .iter()
+            .filter(|d| d.shortName.to_lowercase().contains(&search.to_lowercase()))
+            .map(|d| FindTransitRoutesResultRoute {
+                id: d.id.clone(),
+                agency_id: agency_id.to_string(),
+                name: d.shortName.clone(),
+            })
+            .collect())
+    }
+
+    fn stop_monitoring_url(&self, stop_id: &str) -> String {
+        format!(
+            "{}/api/siri/stop-monitoring.json?key={}&MonitoringRef={}",
+            self.config.host, self.config.api_key, stop_id
+        )
+    }
+
+    fn vehicle_monitoring_url(&self, filter: &VehicleFilter) -> String {
+        let (param, id) = match filter {
+            VehicleFilter::Route(route_id) => ("LineRef", route_id),
+            VehicleFilter::Vehicle(vehicle_id) => ("VehicleRef", vehicle_id),
+        };
+
+        format!(
+            "{}/api/siri/vehicle-monitoring.json?key={}&{}={}",
+            self.config.host,
+            self.config.api_key,
+            param,
+            encode(id)
+        )
+    }
+
+    async fn fetch_json<T: DeserializeOwned>(
+        &self,
+        endpoint: TransitEndpoint,
+        url: &str,
+    ) -> Result<T, TransitClientError> {
+        let body = self.fetch_bytes(endpoint, url).await?;
+
+        serde_json::from_slice::<T>(&body).map_err(|e| {
+            TransitClientError::Internal(format!(
+                "Failed to parse json for {} request: {}",
+                endpoint.name(),
+                e
+            ))
+        })
+    }
+
+    async fn fetch_bytes(
+        &self,
+        endpoint: TransitEndpoint,
+        url: &str,
+    ) -> Result<Bytes, TransitClientError> {
+        if self.cache_policy != CachePolicy::Bypass {
+            if let Some(body) = self.cache.get(url) {
+                return Ok(body);
+            }
+        }
+
+        // nothing is known about what isn't cached
+        if self.cache_policy == CachePolicy::Only {
+            return Err(TransitClientError::ResourceNotFound);
+        }
+
+        let client = self.client.clone();
+        let cache = self.cache.clone();
+        let ttl = self.cache_ttl(endpoint);
+        let owned_url = url.to_string();
+        let name = self.config.name.clone();
+
+        // concurrent requests for the same url share one upstream call
+        self.in_flight
+            .run(url, async move {
+                let body = client.get(&owned_url).await.map_err(|e| match e {
+                    UpstreamError::Unavailable(retry_after) => {
+                        TransitClientError::Unavailable(name, retry_after)
+                    }
+                    UpstreamError::Status(s) if s == 404 => TransitClientError::ResourceNotFound,
+                    _ => TransitClientError::Internal(format!(
+                        "Failed to send {} API request: {}",
+                        endpoint.name(),
+                        e
+                    )),
+                })?;
+
+                cache.insert(owned_url, body.clone(), ttl);
+
+                Ok(body)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl TransitProvider for OneBusAwayProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn with_cache_policy(&self, policy: CachePolicy) -> Arc<dyn TransitProvider> {
+        Arc::new(OneBusAwayProvider {
+            cache_policy: policy,
+            ..self.clone()
+        })
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.cache.stats())
+    }
+
+    async fn get_stops_near(
+        &self,
+        lat: f64,
+        lon: f64,
+        radius_meters: f64,
+    ) -> Result<Vec<NearbyStop>, TransitClientError> {
+        let url = &format!(
+            "{}/api/where/stops-for-location.json?lat={}&lon={}&radius={}&key={}",
+            self.config.host, lat, lon, radius_meters, self.config.api_key
+        );
+
+        let routes_for_location = self
+            .fetch_json::<GetStopsAtLocationResponse>(TransitEndpoint::StopsForLocation, url)
+            .await?;
+
+        Ok(routes_for_location
+            .data
+            .stops
+            .into_iter()
+            .map(|stop| NearbyStop {
+                id: stop.id,
+                name: stop.name,
+                lat: stop.lat,
+                lon: stop.lon,
+                direction: stop.direction.filter(|d| !d.is_empty()),
+                route_ids: stop.routes.into_iter().map(|r| r.id).collect(),
+            })
+            .collect())
+    }
+
+    async fn get_stops_for_route(
+        &self,
+        route_id: &str,
+        include_polylines: bool,
+    ) -> Result<GetStopsForRouteResult, TransitClientError> {
+        // polylines are most of the response, and cached apart from the stops alone
+        let json = self
+            .fetch_json::<GetStopsForRouteResponse>(
+                TransitEndpoint::StopsForRoute,
+                &format!(
+                    "{}/api/where/stops-for-route/{}.json?key={}&includePolylines={}&version=2",
+                    self.config.host,
+                    encode(route_id),
+                    self.config.api_key,
+                    include_polylines
+                ),
+            )
+            .await?;
+
+        let route_name = json
+            .data
+            .references
+            .routes
+            .iter()
+            .find(|r| r.id == route_id)
+            .map(|r| r.shortName.clone())
+            .unwrap_or("".to_string());
+
+        let stops_by_id = json
+            .data
+            .references
+            .stops
+            .iter()
+            .map(|s| (s.id.clone(), s))
+            .collect::<HashMap<String, &GetStopsForRouteResponseDataReferencesStop>>();
+
+        let mut result = GetStopsForRouteResult { groups: vec![] };
+
+        for stop_group in json.data.entry.stopGroupings.iter() {
+            for stop_group_nested in stop_group.stopGroups.iter() {
+                let GetStopsForRouteResponseDataEntryStopGroupingStopGroup {
+                    id: grouping_id,
+                    name: grouping_name,
+                    stopIds: stop_ids,
+                    polylines,
+                } = stop_group_nested;
+
+                let mut group_stops: Vec<GetStopsForRouteResultGroupStop> = vec![];
+
+                for stop_id in stop_ids.iter() {
+                    let stop_info = match stops_by_id.get(stop_id) {
+                        Some(i) => i,
+                        None => continue,
+                    };
+
+                    group_stops.push(GetStopsForRouteResultGroupStop {
+                        id: stop_id.clone(),
+                        name: stop_info.name.clone(),
+                        lat: stop_info.lat,
+                        lon: stop_info.lon,
+                    });
+                }
+
+                result.groups.push(GetStopsForRouteResultGroup {
+                    id: grouping_id.clone(),
+                    name: grouping_name.name.clone(),
+                    stops: group_stops,
+                    route_id: route_id.to_string(),
+                    route_name: route_name.clone(),
+                    polylines: polylines.iter().map(|p| p.points.clone()).collect(),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Searches each agency separately, so that one failing agency only drops its own routes.
+    async fn get_routes(
+        &self,
+        search: &str,
+        agency_id: Option<&str>,
+    ) -> Result<FindTransitRoutesResult, TransitClientError> {
+        let agency_ids = self
+            .agency_ids()
+            .await?
+            .into_iter()
+            .filter(|id| agency_id.map_or(true, |a| id == a))
+            .collect::<Vec<String>>();
+
+        let results = join_all(
+            agency_ids
+                .iter()
+                .map(|agency_id| self.get_routes_for_agency(agency_id, search)),
+        )
+        .await;
+
+        let mut routes = Vec::new();
+        let mut last_error = None;
+        let mut any_succeeded = false;
+
+        for (agency_id, result) in agency_ids.iter().zip(results) {
+            match result {
+                Ok(found) => {
+                    any_succeeded = true;
+                    routes.extend(found);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch routes of {} from {}: {}",
+                        agency_id, self.config.name, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match (any_succeeded, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(FindTransitRoutesResult { routes }),
+        }
+    }
+
+    async fn fetch_stop_info(
+        &self,
+        stop_id: &str,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        let url = &self.stop_monitoring_url(stop_id);
+
+        let response = self
+            .fetch_json::<GetStopInfoResponse>(TransitEndpoint::StopMonitoring, url)
+            .await?;
+
+        let stop_monitoring_delivery = response.Siri.ServiceDelivery.StopMonitoringDelivery.first();
+
+        if let Some(error) = stop_monitoring_delivery.and_then(|d| d.ErrorCondition.as_ref()) {
+            let description = error.Description.clone().unwrap_or_default();
+
+            // bus time reports unknown stops as a successful response with an error condition
+            if description.starts_with("No such stop") {
+                return Err(TransitClientError::ResourceNotFound);
+            }
+
+            return Err(TransitClientError::Internal(format!(
+                "stop-monitoring error condition: {}",
+                description
+            )));
+        }
+
+        let mut output = Vec::<StopInformation>::new();
+
+        let mut tracked_routes: HashMap<String, usize> = HashMap::new();
+
+        if let Some(delivery) = stop_monitoring_delivery {
+            for stop_visit in delivery.MonitoredStopVisit.iter() {
+                let hash_key = format!(
+                    "{}{}",
+                    stop_visit.MonitoredVehicleJourney.PublishedLineName,
+                    stop_visit.MonitoredVehicleJourney.DirectionRef
+                );
+
+                if tracked_routes.get(&hash_key).copied().unwrap_or(0) >= limit_per_route {
+                    continue;
+                }
+
+                let minutes_until_arrival = match &stop_visit
+                    .MonitoredVehicleJourney
+                    .MonitoredCall
+                    .ExpectedArrivalTime
+                {
+                    Some(s) => match DateTime::parse_from_rfc3339(s) {
+                        Ok(d) => Some(d.signed_duration_since(Utc::now()).num_minutes()),
+                        Err(_) => Option::None,
+                    },
+                    None => None,
+                };
+
+                let expected_arrival_time = stop_visit
+                    .MonitoredVehicleJourney
+                    .MonitoredCall
+                    .ExpectedArrivalTime
+                    .clone();
+
+                if let (Some(minutes_until_arrival), Some(expected_arrival_time)) =
+                    (minutes_until_arrival, expected_arrival_time)
+                {
+                    let journey = &stop_visit.MonitoredVehicleJourney;
+                    let extensions = journey.MonitoredCall.Extensions.as_ref();
+                    let distances = extensions.and_then(|e| e.Distances.as_ref());
+                    let capacities = extensions.and_then(|e| e.Capacities.as_ref());
+
+                    output.push(StopInformation {
+                        expected_arrival_time,
+                        minutes_until_arrival,
+                        route_id: journey.LineRef.clone(),
+                        direction_id: journey.DirectionRef.clone(),
+                        route_label: journey.PublishedLineName.clone(),
+                        stop_id: stop_id.to_string(),
+                        aimed_arrival_time: journey.MonitoredCall.AimedArrivalTime.clone(),
+                        destination_name: journey.DestinationName.clone(),
+                        vehicle_id: journey.VehicleRef.clone(),
+                        progress_status: journey.ProgressStatus.clone(),
+                        occupancy: journey.Occupancy.clone(),
+                        passenger_count: capacities.and_then(|c| c.EstimatedPassengerCount),
+                        passenger_capacity: capacities.and_then(|c| c.EstimatedPassengerCapacity),
+                        presentable_distance: distances.and_then(|d| d.PresentableDistance.clone()),
+                        stops_away: distances.and_then(|d| d.StopsFromCall),
+                        distance_from_stop_meters: distances.and_then(|d| d.DistanceFromCall),
+                        stale_since: None,
+                    });
+
+                    *tracked_routes.entry(hash_key).or_default() += 1;
+                }
+            }
+
+            Ok(output)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError> {
+        let url = &self.vehicle_monitoring_url(filter);
+
+        let response = self
+            .fetch_json::<GetVehicleMonitoringResponse>(TransitEndpoint::VehicleMonitoring, url)
+            .await?;
+
+        let delivery = match response
+            .Siri
+            .ServiceDelivery
+            .VehicleMonitoringDelivery
+            .first()
+        {
+            Some(d) => d,
+            None => return Ok(Vec::new()),
+        };
+
+        if let Some(error) = delivery.ErrorCondition.as_ref() {
+            let description = error.Description.clone().unwrap_or_default();
+
+            if description.starts_with("No such") {
+                return Err(TransitClientError::ResourceNotFound);
+            }
+
+            return Err(TransitClientError::Internal(format!(
+                "vehicle-monitoring error condition: {}",
+                description
+            )));
+        }
+
+        Ok(delivery
+            .VehicleActivity
+            .iter()
+            .map(|activity| {
+                let journey = &activity.MonitoredVehicleJourney;
+
+                VehiclePosition {
+                    vehicle_id: journey.VehicleRef.clone(),
+                    route_id: journey.LineRef.clone(),
+                    route_label: journey.PublishedLineName.clone(),
+                    direction_id: journey.DirectionRef.clone(),
+                    lat: journey.VehicleLocation.Latitude,
+                    lon: journey.VehicleLocation.Longitude,
+                    bearing: journey.Bearing,
+                    destination_name: journey.DestinationName.clone(),
+                    next_stop_id: journey
+                        .MonitoredCall
+                        .as_ref()
+                        .and_then(|c| c.StopPointRef.clone()),
+                    progress_status: journey.ProgressStatus.clone(),
+                    recorded_at: activity.RecordedAtTime.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// Situations ride along with monitoring responses, so these are read from the same cached
+    /// responses as arrivals and vehicle positions.
+    async fn fetch_alerts(
+        &self,
+        filter: &AlertFilter,
+    ) -> Result<Vec<ServiceAlert>, TransitClientError> {
+        let deliveries = match filter {
+            AlertFilter::Stop(stop_id) => {
+                self.fetch_json::<GetStopInfoResponse>(
+                    TransitEndpoint::StopMonitoring,
+                    &self.stop_monitoring_url(stop_id),
+                )
+                .await?
+                .Siri
+                .ServiceDelivery
+                .SituationExchangeDelivery
+            }
+            AlertFilter::Route(route_id) => {
+                self.fetch_json::<GetVehicleMonitoringResponse>(
+                    TransitEndpoint::VehicleMonitoring,
+                    &self.vehicle_monitoring_url(&VehicleFilter::Route(route_id.clone())),
+                )
+                .await?
+                .Siri
+                .ServiceDelivery
+                .SituationExchangeDelivery
+            }
+        };
+
+        Ok(alerts_from_deliveries(deliveries))
+    }
+}
+
+fn alerts_from_deliveries(deliveries: Vec<SituationExchangeDelivery>) -> Vec<ServiceAlert> {
+    deliveries
+        .into_iter()
+        .flat_map(|d| d.Situations.PtSituationElement)
+        .map(|situation| {
+            let window = situation.PublicationWindow.unwrap_or_default();
+            let affects = situation.Affects.unwrap_or_default();
+
+            let mut route_ids: Vec<String> = Vec::new();
+            for journey in affects
+                .VehicleJourneys
+                .map(|j| j.AffectedVehicleJourney)
+                .unwrap_or_default()
+            {
+                // journeys are listed once per direction
+                if !route_ids.contains(&journey.LineRef) {
+                    route_ids.push(journey.LineRef);
+                }
+            }
+
+            ServiceAlert {
+                id: situation.SituationNumber,
+                summary: situation
+                    .Summary
+                    .or(situation.Description.clone())
+                    .unwrap_or_default(),
+                description: situation.Description,
+                severity: situation.Severity.filter(|s| s != "undefined"),
+                valid_from: window.StartTime,
+                valid_until: window.EndTime,
+                route_ids,
+                stop_ids: affects
+                    .StopPoints
+                    .map(|s| {
+                        s.AffectedStopPoint
+                            .into_iter()
+                            .map(|p| p.StopPointRef)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::transit_service::types::{
+        mta_get_agencies_with_coverage_response::{
+            GetAgenciesWithCoverageResponseAgency, GetAgenciesWithCoverageResponseData,
+        },
+        mta_get_routes_response::{GetRoutesResponseData, GetRoutesResponseRoute},
+    };
+
+    use super::*;
+
+    fn routes_response(routes: &[(&str, &str)]) -> String {
+        serde_json::to_string(&GetRoutesResponse {
+            data: GetRoutesResponseData {
+                list: routes
+                    .iter()
+                    .map(|(id, name)| GetRoutesResponseRoute {
+                        id: id.to_string(),
+                        shortName: name.to_string(),
+                    })
+                    .collect(),
+            },
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn searches_routes_of_every_agency_with_coverage() {
+        let mut server = mockito::Server::new_async().await;
+
+        let agencies = GetAgenciesWithCoverageResponse {
+            data: GetAgenciesWithCoverageResponseData {
+                list: vec![
+                    GetAgenciesWithCoverageResponseAgency {
+                        agencyId: "MTA NYCT".to_string(),
+                    },
+                    GetAgenciesWithCoverageResponseAgency {
+                        agencyId: "MTABC".to_string(),
+                    },
+                ],
+            },
+        };
+
+        let mocks = [
+            server
+                .mock("GET", "/api/where/agencies-with-coverage.json")
+                .match_query(mockito::Matcher::Regex(".*".to_string()))
+                .with_body(serde_json::to_string(&agencies).unwrap())
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/where/routes-for-agency/MTA%20NYCT.json")
+                .match_query(mockito::Matcher::Regex(".*".to_string()))
+                .with_body(routes_response(&[
+                    ("MTA NYCT_Q58", "Q58"),
+                    ("MTA NYCT_B63", "B63"),
+                ]))
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/where/routes-for-agency/MTABC.json")
+                .match_query(mockito::Matcher::Regex(".*".to_string()))
+                .with_body(routes_response(&[("MTABC_Q53+", "Q53-SBS")]))
+                .create_async()
+                .await,
+        ];
+
+        let provider = OneBusAwayProvider::new(OneBusAwayProviderConfig {
+            name: "mta".to_string(),
+            host: server.url(),
+            api_key: "key".to_string(),
+            agency_ids: None,
+            cache: TransitCacheConfig::default(),
+            upstream: UpstreamConfig::default(),
+        });
+
+        let routes = provider.get_routes("q5", None).await.unwrap().routes;
+
+        mocks.iter().for_each(|m| m.assert());
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].id, "MTA NYCT_Q58");
+        assert_eq!(routes[0].agency_id, "MTA NYCT");
+        assert_eq!(routes[1].id, "MTABC_Q53+");
+        assert_eq!(routes[1].agency_id, "MTABC");
+    }
+
+    #[tokio::test]
+    async fn searches_requested_agencies_separately() {
+        let mut server = mockito::Server::new_async().await;
+        let nyct = server
+            .mock("GET", "/api/where/routes-for-agency/MTA%20NYCT.json")
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .with_body(routes_response(&[("MTA NYCT_Q58", "Q58")]))
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/where/routes-for-agency/MTABC.json")
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let provider = OneBusAwayProvider::new(OneBusAwayProviderConfig {
+            name: "mta".to_string(),
+            host: server.url(),
+            api_key: "key".to_string(),
+            agency_ids: Some(vec!["MTA NYCT".to_string(), "MTABC".to_string()]),
+            cache: TransitCacheConfig::default(),
+            upstream: UpstreamConfig {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        });
+
+        // a failing agency leaves the others' routes
+        let routes = provider.get_routes("q", None).await.unwrap().routes;
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].id, "MTA NYCT_Q58");
+
+        // other agencies aren't fetched at all, so the only one failing fails the search
+        assert!(provider.get_routes("q", Some("MTABC")).await.is_err());
+        assert!(provider
+            .get_routes("q", Some("MTA"))
+            .await
+            .unwrap()
+            .routes
+            .is_empty());
+
+        nyct.assert();
+    }
+
+    #[tokio::test]
+    async fn fails_fast_while_the_deployment_is_down() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/where/routes-for-agency/MTA%20NYCT.json")
+            .match_query(mockito::Matcher::Regex(".*".to_string()))
+            .with_status(502)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let provider = OneBusAwayProvider::new(OneBusAwayProviderConfig {
+            name: "mta".to_string(),
+            host: server.url(),
+            api_key: "key".to_string(),
+            agency_ids: Some(vec!["MTA NYCT".to_string()]),
+            cache: TransitCacheConfig::default(),
+            upstream: UpstreamConfig {
+                max_attempts: 1,
+                failure_threshold: 2,
+                ..Default::default()
+            },
+        });
+
+        for _ in 0..2 {
+            assert!(matches!(
+                provider.get_routes("b", None).await,
+                Err(TransitClientError::Internal(_))
+            ));
+        }
+        assert!(matches!(
+            provider.get_routes("b", None).await,
+            Err(TransitClientError::Unavailable(name, retry_after))
+                if name == "mta" && retry_after <= Duration::from_secs(30)
+        ));
+
+        mock.assert();
+    }
+}
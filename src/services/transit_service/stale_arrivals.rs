@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::transit_service::StopInformation;
+
+#[derive(Clone)]
+pub struct StaleArrivalsConfig {
+    /// Responses older than this are not served.
+    pub max_age: Duration,
+    /// Also keeps responses as files here, so that they survive a restart.
+    pub dir: Option<PathBuf>,
+    /// Stops kept in memory, past which the least recently used one is dropped.
+    pub max_stops: usize,
+    /// How often a stop's file may be rewritten.
+    pub write_interval: Duration,
+}
+
+impl Default for StaleArrivalsConfig {
+    fn default() -> Self {
+        StaleArrivalsConfig {
+            max_age: Duration::from_secs(30 * 60),
+            dir: None,
+            max_stops: 10_000,
+            write_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Snapshot {
+    /// Unix seconds.
+    fetched_at: i64,
+    arrivals: Vec<StopInformation>,
+}
+
+struct Entry {
+    snapshot: Option<Snapshot>,
+    /// The last fetch failed, so callers are served the snapshot without waiting on another.
+    failing: bool,
+    refreshing: bool,
+    used_at: Instant,
+    written_at: Option<Instant>,
+}
+
+impl Entry {
+    fn new() -> Self {
+        Entry {
+            snapshot: None,
+            failing: false,
+            refreshing: false,
+            used_at: Instant::now(),
+            written_at: None,
+        }
+    }
+}
+
+/// The last good arrivals of each stop, served in place of an error while its provider fails.
+#[derive(Clone)]
+pub struct StaleArrivals {
+    config: StaleArrivalsConfig,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl Default for StaleArrivals {
+    fn default() -> Self {
+        StaleArrivals::new(StaleArrivalsConfig::default())
+    }
+}
+
+impl StaleArrivals {
+    /// Files are swept for expired snapshots in the background, so a directory needs a runtime.
+    pub fn new(config: StaleArrivalsConfig) -> Self {
+        if let Some(dir) = config.dir.clone() {
+            let max_age = config.max_age;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(max_age);
+                loop {
+                    interval.tick().await;
+                    remove_expired_files(&dir, max_age).await;
+                }
+            });
+        }
+
+        StaleArrivals {
+            config,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Keeps the arrivals of the latest fetch, whatever its limit per route.
+    pub fn save(&self, stop_id: &str, arrivals: &[StopInformation]) {
+        let snapshot = Snapshot {
+            fetched_at: Utc::now().timestamp(),
+            arrivals: arrivals.to_vec(),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        let entry = self.entry(&mut entries, stop_id);
+
+        let write_due = entry
+            .written_at
+            .map_or(true, |at| at.elapsed() >= self.config.write_interval);
+        if let (true, Some(path)) = (write_due, self.path(stop_id)) {
+            entry.written_at = Some(Instant::now());
+            let json = serde_json::to_vec(&snapshot).unwrap();
+            tokio::spawn(async move {
+                if let Err(e) = tokio::fs::write(&path, json).await {
+                    warn!("Failed to write {}: {}", path.display(), e);
+                }
+            });
+        }
+
+        entry.snapshot = Some(snapshot);
+        entry.failing = false;
+        entry.refreshing = false;
+    }
+
+    pub fn mark_failing(&self, stop_id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = self.entry(&mut entries, stop_id);
+        entry.failing = true;
+        entry.refreshing = false;
+    }
+
+    /// Drops what is known about a stop its providers no longer have.
+    pub fn forget(&self, stop_id: &str) {
+        let known = self.entries.lock().unwrap().remove(stop_id).is_some();
+
+        if known {
+            self.remove_file(stop_id);
+        }
+    }
+
+    pub fn is_failing(&self, stop_id: &str) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(stop_id)
+            .is_some_and(|e| e.failing)
+    }
+
+    /// Whether the caller should refresh the failing stop, which is true for one caller at a time.
+    pub fn begin_refresh(&self, stop_id: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(stop_id) {
+            Some(entry) if entry.failing && !entry.refreshing => {
+                entry.refreshing = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The last good arrivals, up to `limit_per_route` per route and direction, marked stale and
+    /// counted down against the current clock. Buses that should have arrived by now are left
+    /// out, and when none are left there is nothing to serve.
+    pub async fn get(&self, stop_id: &str, limit_per_route: usize) -> Option<Vec<StopInformation>> {
+        let saved = self.entries.lock().unwrap().get_mut(stop_id).and_then(|e| {
+            e.used_at = Instant::now();
+            e.snapshot.clone()
+        });
+        let snapshot = match saved {
+            Some(snapshot) => snapshot,
+            None => self.read(stop_id).await?,
+        };
+
+        let now = Utc::now();
+        if self.is_expired(&snapshot, now) {
+            if let Some(entry) = self.entries.lock().unwrap().get_mut(stop_id) {
+                entry.snapshot = None;
+            }
+            self.remove_file(stop_id);
+
+            return None;
+        }
+
+        let fetched_at = DateTime::from_timestamp(snapshot.fetched_at, 0)?;
+        let mut per_route: HashMap<(String, String), usize> = HashMap::new();
+        let arrivals = snapshot
+            .arrivals
+            .into_iter()
+            .filter_map(|arrival| {
+                let expected = DateTime::parse_from_rfc3339(&arrival.expected_arrival_time).ok()?;
+                let minutes_until_arrival = expected.signed_duration_since(now).num_minutes();
+
+                (expected >= now).then_some(StopInformation {
+                    minutes_until_arrival,
+                    stale_since: Some(fetched_at),
+                    ..arrival
+                })
+            })
+            .filter(|arrival| {
+                let count = per_route
+                    .entry((arrival.route_id.clone(), arrival.direction_id.clone()))
+                    .or_default();
+                *count += 1;
+                *count <= limit_per_route
+            })
+            .collect::<Vec<_>>();
+
+        (!arrivals.is_empty()).then_some(arrivals)
+    }
+
+    async fn read(&self, stop_id: &str) -> Option<Snapshot> {
+        let json = tokio::fs::read(self.path(stop_id)?).await.ok()?;
+        let snapshot = serde_json::from_slice::<Snapshot>(&json).ok()?;
+
+        let mut entries = self.entries.lock().unwrap();
+        let entry = self.entry(&mut entries, stop_id);
+        entry.snapshot = Some(snapshot.clone());
+        // the file is as recent as what it holds
+        entry.written_at = Some(Instant::now());
+
+        Some(snapshot)
+    }
+
+    /// The stop's entry, made room for by dropping expired snapshots and then the least recently
+    /// used stop.
+    fn entry<'a>(&self, entries: &'a mut HashMap<String, Entry>, stop_id: &str) -> &'a mut Entry {
+        if !entries.contains_key(stop_id) && entries.len() >= self.config.max_stops {
+            let now = Utc::now();
+            entries.retain(|_, e| {
+                e.snapshot
+                    .as_ref()
+                    .is_some_and(|s| !self.is_expired(s, now))
+            });
+
+            if entries.len() >= self.config.max_stops {
+                let least_recent = entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.used_at)
+                    .map(|(id, _)| id.clone());
+                if let Some(id) = least_recent {
+                    entries.remove(&id);
+                }
+            }
+        }
+
+        let entry = entries
+            .entry(stop_id.to_string())
+            .or_insert_with(Entry::new);
+        entry.used_at = Instant::now();
+        entry
+    }
+
+    fn is_expired(&self, snapshot: &Snapshot, now: DateTime<Utc>) -> bool {
+        now.timestamp() - snapshot.fetched_at > self.config.max_age.as_secs() as i64
+    }
+
+    fn remove_file(&self, stop_id: &str) {
+        if let Some(path) = self.path(stop_id) {
+            tokio::spawn(async move {
+                let _ = tokio::fs::remove_file(path).await;
+            });
+        }
+    }
+
+    /// Stop IDs come from clients, so they are hashed rather than used as file names.
+    fn path(&self, stop_id: &str) -> Option<PathBuf> {
+        self.config
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:x}.json", Sha256::digest(stop_id.as_bytes()))))
+    }
+}
+
+/// Snapshots are never older than their file, so files last modified before `max_age` are
+/// expired.
+async fn remove_expired_files(dir: &Path, max_age: Duration) {
+    let mut files = match tokio::fs::read_dir(dir).await {
+        Ok(files) => files,
+        Err(e) => {
+            warn!("Failed to read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    while let Ok(Some(file)) = files.next_entry().await {
+        let path = file.path();
+        let expired = file
+            .metadata()
+            .await
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > max_age);
+
+        if expired && path.extension().is_some_and(|e| e == "json") {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arrival(route_id: &str, minutes: i64) -> StopInformation {
+        StopInformation {
+            expected_arrival_time: (Utc::now() + chrono::Duration::seconds(minutes * 60 + 30))
+                .to_rfc3339(),
+            minutes_until_arrival: minutes + 2,
+            stop_id: "1".to_string(),
+            route_id: route_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn recomputes_minutes_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StaleArrivalsConfig {
+            dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        StaleArrivals::new(config.clone())
+            .save("1", &[arrival("A", -2), arrival("A", 5), arrival("A", 9)]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // a new process reads what the last one saved
+        let stale_arrivals = StaleArrivals::new(config);
+        let arrivals = stale_arrivals.get("1", 1).await.unwrap();
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(arrivals[0].minutes_until_arrival, 5);
+        assert!(arrivals[0].stale_since.is_some());
+        assert_eq!(stale_arrivals.get("1", 2).await.unwrap().len(), 2);
+        assert!(stale_arrivals.get("2", 1).await.is_none());
+
+        stale_arrivals.mark_failing("1");
+        assert!(stale_arrivals.begin_refresh("1"));
+        assert!(!stale_arrivals.begin_refresh("1"));
+    }
+
+    #[tokio::test]
+    async fn serves_nothing_once_every_arrival_has_passed() {
+        let stale_arrivals = StaleArrivals::default();
+
+        stale_arrivals.save("1", &[arrival("A", -2)]);
+        assert!(stale_arrivals.get("1", 1).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_most_recently_used_stops() {
+        let stale_arrivals = StaleArrivals::new(StaleArrivalsConfig {
+            max_stops: 2,
+            ..Default::default()
+        });
+
+        stale_arrivals.save("1", &[arrival("A", 5)]);
+        stale_arrivals.save("2", &[arrival("A", 5)]);
+        assert!(stale_arrivals.get("1", 1).await.is_some());
+        stale_arrivals.save("3", &[arrival("A", 5)]);
+
+        assert!(stale_arrivals.get("1", 1).await.is_some());
+        assert!(stale_arrivals.get("2", 1).await.is_none());
+        assert!(stale_arrivals.get("3", 1).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn throttles_writes_and_removes_expired_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let stale_arrivals = StaleArrivals::new(StaleArrivalsConfig {
+            dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        });
+        let path = stale_arrivals.path("1").unwrap();
+
+        stale_arrivals.save("1", &[arrival("A", 5)]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let written = std::fs::read(&path).unwrap();
+
+        stale_arrivals.save("1", &[arrival("A", 5), arrival("B", 5)]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(std::fs::read(&path).unwrap(), written);
+
+        std::fs::write(dir.path().join("old.json"), "{}").unwrap();
+        remove_expired_files(dir.path(), Duration::ZERO).await;
+        assert!(!path.exists());
+        assert!(!dir.path().join("old.json").exists());
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+use crate::utils::{cache_control::CachePolicy, ttl_cache::CacheStats};
+
+use super::transit_service::{
+    AlertFilter, FindTransitRoutesResult, GetStopsForRouteResult, ServiceAlert, StopInformation,
+    TransitClientError, VehicleFilter, VehiclePosition,
+};
+
+#[derive(Clone)]
+pub struct NearbyStop {
+    pub id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// The way traffic at the stop is heading, e.g. "N" or "SW", when the provider knows it.
+    pub direction: Option<String>,
+    pub route_ids: Vec<String>,
+}
+
+/// A source of routes, stops and arrivals. `TransitService` fans requests out over one or more
+/// providers, so a provider should return `TransitClientError::ResourceNotFound` for IDs it does
+/// not know rather than an empty result.
+#[async_trait]
+pub trait TransitProvider: Send + Sync {
+    /// Identifies the provider in logs.
+    fn name(&self) -> &str;
+
+    /// Returns a handle to the same provider that reads any cache according to `policy`.
+    fn with_cache_policy(&self, policy: CachePolicy) -> Arc<dyn TransitProvider>;
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Routes whose name contains `search`, only from `agency_id` when it is given.
+    async fn get_routes(
+        &self,
+        search: &str,
+        agency_id: Option<&str>,
+    ) -> Result<FindTransitRoutesResult, TransitClientError>;
+
+    /// The route's stops by direction, with the path of each direction only when
+    /// `include_polylines` is set.
+    async fn get_stops_for_route(
+        &self,
+        route_id: &str,
+        include_polylines: bool,
+    ) -> Result<GetStopsForRouteResult, TransitClientError>;
+
+    async fn get_stops_near(
+        &self,
+        lat: f64,
+        lon: f64,
+        radius_meters: f64,
+    ) -> Result<Vec<NearbyStop>, TransitClientError>;
+
+    /// Upcoming arrivals at a stop, keeping the soonest `limit_per_route` for each route and
+    /// direction.
+    async fn fetch_stop_info(
+        &self,
+        stop_id: &str,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError>;
+
+    async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError>;
+
+    /// Alerts affecting a stop or route, including ones that are not active yet. Providers
+    /// without an alerts source report none.
+    async fn fetch_alerts(
+        &self,
+        _filter: &AlertFilter,
+    ) -> Result<Vec<ServiceAlert>, TransitClientError> {
+        Ok(Vec::new())
+    }
+}
@@ -1,51 +1,182 @@
-use std::collections::{HashMap, HashSet};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
-use ::futures::future::try_join_all;
+use ::futures::future::{join_all, try_join_all};
 use chrono::{DateTime, Utc};
-use urlencoding::encode;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
-    services::maps_client::maps_service::MapsService,
+    services::maps_client::{
+        maps_service::MapsService, types::maps_service_error::MapsServiceError,
+    },
     types::lat_long_location::GetStopsAtLocationInput,
-};
-
-use super::types::{
-    mta_get_routes_response::GetRoutesResponse,
-    mta_get_stop_response::GetStopInfoResponse,
-    mta_get_stops_at_location_response::GetStopsAtLocationResponse,
-    mta_get_stops_for_route_response::{
-        GetStopsForRouteResponse, GetStopsForRouteResponseDataEntryStopGroupingStopGroup,
-        GetStopsForRouteResponseDataReferencesStop,
+    utils::{
+        cache_control::CachePolicy,
+        geo::{bearing_degrees, distance_meters},
+        ttl_cache::CacheStats,
     },
 };
 
+use super::{stale_arrivals::StaleArrivals, transit_provider::TransitProvider};
+
 #[derive(Clone)]
 pub struct TransitServiceConfig {
-    pub host: String,
-    pub api_key: String,
+    /// Consulted in order. Lookups by ID go to the first provider that knows the ID, and searches
+    /// to the first provider that finds anything.
+    pub providers: Vec<Arc<dyn TransitProvider>>,
     pub maps_service: MapsService,
+    pub stale_arrivals: StaleArrivals,
 }
 
 #[derive(Clone)]
 pub struct TransitService {
     config: TransitServiceConfig,
-    client: reqwest::Client,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct StopInformation {
     pub expected_arrival_time: String,
     pub minutes_until_arrival: i64,
     pub stop_id: String,
     pub route_label: String,
     pub route_id: String,
+    pub direction_id: String,
+    // details that not every provider reports
+    pub aimed_arrival_time: Option<String>,
+    pub destination_name: Option<String>,
+    pub vehicle_id: Option<String>,
+    pub progress_status: Option<String>,
+    pub occupancy: Option<String>,
+    pub passenger_count: Option<u32>,
+    pub passenger_capacity: Option<u32>,
+    pub presentable_distance: Option<String>,
+    pub stops_away: Option<u32>,
+    pub distance_from_stop_meters: Option<f64>,
+    /// When the response this arrival was read from was fetched, if it is being served after
+    /// fetching a fresh one failed.
+    #[serde(skip)]
+    pub stale_since: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct VehiclePosition {
+    pub vehicle_id: String,
+    pub route_id: String,
+    pub route_label: String,
+    pub direction_id: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    /// Degrees clockwise from north.
+    pub bearing: Option<f64>,
+    pub destination_name: Option<String>,
+    pub next_stop_id: Option<String>,
+    pub progress_status: Option<String>,
+    pub recorded_at: Option<String>,
+}
+
+/// A detour, closure or other service change reported by a provider.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceAlert {
+    pub id: String,
+    pub summary: String,
+    pub description: Option<String>,
+    /// As reported upstream, e.g. "normal" or "severe".
+    pub severity: Option<String>,
+    /// RFC 3339 bounds of when the alert applies. A missing bound is open ended.
+    pub valid_from: Option<String>,
+    pub valid_until: Option<String>,
+    pub route_ids: Vec<String>,
+    pub stop_ids: Vec<String>,
+}
+
+impl ServiceAlert {
+    /// Bounds that can't be parsed are treated as open ended, so an alert is never hidden by a
+    /// malformed date.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let parse = |t: &Option<String>| {
+            t.as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        };
+
+        parse(&self.valid_from).map_or(true, |from| from <= now)
+            && parse(&self.valid_until).map_or(true, |until| now < until)
+    }
+}
+
+/// Which alerts to look up.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AlertFilter {
+    Stop(String),
+    Route(String),
+}
+
+/// Which vehicles to look up.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VehicleFilter {
+    Route(String),
+    Vehicle(String),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct StopArrivalsError {
+    pub stop_id: String,
+    pub reason: String,
+    /// No provider knows the stop.
+    pub not_found: bool,
+    /// How long the stop's provider is failing fast for, so that retrying sooner won't help.
+    pub unavailable: Option<Duration>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct MultipleStopArrivals {
+    pub arrivals: Vec<StopInformation>,
+    pub errors: Vec<StopArrivalsError>,
+}
+
+impl MultipleStopArrivals {
+    /// When every failed stop's provider is failing fast, how long until one of them lets a call
+    /// through.
+    pub fn unavailable_for(&self) -> Option<Duration> {
+        self.errors
+            .iter()
+            .map(|e| e.unavailable)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .min()
+    }
+}
+
+/// A stop within the searched radius, measured from the searched point.
+pub struct StopNearLocation {
+    pub id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub direction: Option<String>,
+    pub route_ids: Vec<String>,
+    pub distance_meters: f64,
+    pub bearing_degrees: f64,
 }
 
+/// Stops around a location that may have been given as a place ID.
+pub struct StopsNearLocation {
+    pub lat: f64,
+    pub lon: f64,
+    /// Nearest first.
+    pub stops: Vec<StopNearLocation>,
+}
+
+type ProvidedStops = Vec<(StopNearLocation, Arc<dyn TransitProvider>)>;
+
 pub struct GetGroupedStopsAtLocation {
     pub groups: Vec<GetStopsForRouteResultGroup>,
+    /// Nearest first.
+    pub stops: Vec<StopNearLocation>,
 }
 
 pub struct FindTransitRoutesResultRoute {
     pub id: String,
+    pub agency_id: String,
     pub name: String,
 }
 
@@ -56,6 +187,9 @@ pub struct FindTransitRoutesResult {
 pub struct GetStopsForRouteResultGroupStop {
     pub id: String,
     pub name: String,
+    /// Not every provider locates every stop.
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
 }
 
 pub struct GetStopsForRouteResultGroup {
@@ -64,15 +198,20 @@ pub struct GetStopsForRouteResultGroup {
     pub route_id: String,
     pub route_name: String,
     pub stops: Vec<GetStopsForRouteResultGroupStop>,
+    /// The path the group's vehicles follow as encoded polylines, possibly in several pieces.
+    pub polylines: Vec<String>,
 }
 
 pub struct GetStopsForRouteResult {
     pub groups: Vec<GetStopsForRouteResultGroup>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum TransitClientError {
     Internal(String),
     ResourceNotFound,
+    /// The named upstream's circuit breaker is open, for about this long.
+    Unavailable(String, Duration),
 }
 
 impl std::fmt::Display for TransitClientError {
@@ -80,89 +219,87 @@ impl std::fmt::Display for TransitClientError {
         match self {
             TransitClientError::Internal(e) => write!(f, "Internal error: {}", e),
             TransitClientError::ResourceNotFound => write!(f, "Resource not found"),
+            TransitClientError::Unavailable(upstream, _) => {
+                write!(f, "{} is unavailable", upstream)
+            }
         }
     }
 }
 
 impl TransitService {
     pub fn new(config: TransitServiceConfig) -> Self {
-        let request_client = reqwest::Client::new();
+        TransitService { config }
+    }
 
+    /// Returns a handle to the same service that reads the cache according to `policy`. Fresh
+    /// responses are still written back to the cache when it is bypassed.
+    pub fn with_cache_policy(&self, policy: CachePolicy) -> Self {
         TransitService {
-            config,
-            client: request_client,
+            config: TransitServiceConfig {
+                providers: self
+                    .config
+                    .providers
+                    .iter()
+                    .map(|p| p.with_cache_policy(policy))
+                    .collect(),
+                maps_service: self.config.maps_service.clone(),
+                stale_arrivals: self.config.stale_arrivals.clone(),
+            },
         }
     }
 
+    pub fn cache_stats(&self) -> CacheStats {
+        self.config
+            .providers
+            .iter()
+            .filter_map(|p| p.cache_stats())
+            .fold(
+                CacheStats {
+                    hits: 0,
+                    misses: 0,
+                    entries: 0,
+                },
+                |acc, s| CacheStats {
+                    hits: acc.hits + s.hits,
+                    misses: acc.misses + s.misses,
+                    entries: acc.entries + s.entries,
+                },
+            )
+    }
+
+    /// Stops within `radius_meters` of `loc`, nearest first and cut to `limit`, along with the
+    /// groupings of every route serving them.
     pub async fn get_stops_at_location(
         &self,
         loc: GetStopsAtLocationInput,
+        radius_meters: f64,
+        limit: Option<usize>,
     ) -> Result<GetGroupedStopsAtLocation, TransitClientError> {
-        let (lat, lon) = match loc {
-            GetStopsAtLocationInput::LatLong(lat, lon) => (lat, lon),
-            GetStopsAtLocationInput::GooglePlaceId(loc) => self
-                .config
-                .maps_service
-                .extract_coordinates_from_place_id(&loc)
-                .await
-                .map_err(|e| {
-                    TransitClientError::Internal(format!("Failed to extract coordinates: {}", e))
-                })?,
-        };
-
-        let url = &format!(
-            "{}/api/where/stops-for-location.json?lat={}&lon={}&latSpan=0.005&lonSpan=0.005&key={}",
-            self.config.host, lat, lon, self.config.api_key
-        );
-
-        let routes_for_location = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| {
-                TransitClientError::Internal(format!(
-                    "Failed to send stops for location API request: {}",
-                    e.to_string()
-                ))
-            })?
-            .json::<GetStopsAtLocationResponse>()
-            .await
-            .map_err(|e| {
-                TransitClientError::Internal(format!(
-                    "Failed to parse json for stops-for-location request: {}",
-                    e.to_string()
-                ))
-            })?;
-
-        let route_ids: HashSet<String> =
-            routes_for_location
-                .data
-                .stops
-                .iter()
-                .fold(HashSet::new(), |mut acc, stop| {
-                    stop.routes.iter().for_each(|r| {
-                        acc.insert(r.id.clone());
-                    });
-                    acc
-                });
-
-        let stop_ids: HashSet<String> =
-            routes_for_location
-                .data
-                .stops
-                .iter()
-                .fold(HashSet::new(), |mut acc, stop| {
-                    acc.insert(stop.id.clone());
-                    acc
-                });
+        let (_, _, stops) = self.find_stops_near(loc, radius_meters, limit).await?;
 
+        let stop_ids = stops
+            .iter()
+            .map(|(s, _)| s.id.clone())
+            .collect::<HashSet<String>>();
+        let mut seen_route_ids: HashSet<String> = HashSet::new();
         let mut fetches = Vec::new();
-        for route_id in route_ids {
-            fetches.push(self.get_stops_for_route(route_id));
+
+        for (stop, provider) in stops.iter() {
+            for route_id in stop.route_ids.iter() {
+                // routes are only fetched from the provider of the nearest stop they serve
+                if seen_route_ids.insert(route_id.clone()) {
+                    let provider = provider.clone();
+                    let route_id = route_id.clone();
+                    fetches
+                        .push(async move { provider.get_stops_for_route(&route_id, false).await });
+                }
+            }
         }
 
-        let mut result = GetGroupedStopsAtLocation { groups: Vec::new() };
+        let mut result = GetGroupedStopsAtLocation {
+            groups: Vec::new(),
+            stops: Vec::new(),
+        };
 
         try_join_all(fetches).await?.iter().for_each(|r| {
             r.groups.iter().for_each(|g| {
@@ -178,250 +315,526 @@ impl TransitService {
                         .map(|s| GetStopsForRouteResultGroupStop {
                             id: s.id.clone(),
                             name: s.name.clone(),
+                            lat: s.lat,
+                            lon: s.lon,
                         })
                         .collect(),
+                    polylines: g.polylines.clone(),
                 };
 
                 result.groups.push(group);
             });
         });
 
+        result.stops = stops.into_iter().map(|(s, _)| s).collect();
+
         Ok(result)
     }
 
-    pub async fn get_stops_for_route(
+    /// Stops within `radius_meters` of `loc`, nearest first and cut to `limit`.
+    pub async fn get_stops_near(
         &self,
-        route_id: String,
-    ) -> Result<GetStopsForRouteResult, TransitClientError> {
-        let res = self
-            .client
-            .get(&format!(
-                "{}/api/where/stops-for-route/{}.json?key={}&includePolylines=false&version=2",
-                self.config.host,
-                encode(&route_id),
-                self.config.api_key
-            ))
-            .send()
-            .await
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?;
+        loc: GetStopsAtLocationInput,
+        radius_meters: f64,
+        limit: Option<usize>,
+    ) -> Result<StopsNearLocation, TransitClientError> {
+        let (lat, lon, stops) = self.find_stops_near(loc, radius_meters, limit).await?;
+
+        Ok(StopsNearLocation {
+            lat,
+            lon,
+            stops: stops.into_iter().map(|(s, _)| s).collect(),
+        })
+    }
 
-        let json = match res.error_for_status() {
-            Ok(r) => r
-                .json::<GetStopsForRouteResponse>()
+    /// The nearby stops of the first provider with any, keeping which provider reported each stop.
+    /// Later providers are only asked when earlier ones have none or fail.
+    async fn find_stops_near(
+        &self,
+        loc: GetStopsAtLocationInput,
+        radius_meters: f64,
+        limit: Option<usize>,
+    ) -> Result<(f64, f64, ProvidedStops), TransitClientError> {
+        let (lat, lon) = match loc {
+            GetStopsAtLocationInput::LatLong(lat, lon) => (lat, lon),
+            GetStopsAtLocationInput::GooglePlaceId(loc) => self
+                .config
+                .maps_service
+                .extract_coordinates_from_place_id(&loc)
                 .await
-                .map_err(|e| TransitClientError::Internal(e.to_string()))?,
-            Err(e) => match e.status() {
-                Some(s) if s == 404 => {
-                    return Err(TransitClientError::ResourceNotFound);
+                .map_err(|e| match e {
+                    MapsServiceError::Unavailable(retry_after) => {
+                        TransitClientError::Unavailable("Google Maps".to_string(), retry_after)
+                    }
+                    _ => TransitClientError::Internal(format!(
+                        "Failed to extract coordinates: {}",
+                        e
+                    )),
+                })?,
+        };
+
+        let (lat, lon) = match (lat.parse::<f64>(), lon.parse::<f64>()) {
+            (Ok(lat), Ok(lon)) => (lat, lon),
+            _ => {
+                return Err(TransitClientError::Internal(format!(
+                    "Invalid coordinates: {},{}",
+                    lat, lon
+                )))
+            }
+        };
+
+        let mut stops: ProvidedStops = Vec::new();
+        let mut last_error = None;
+        let mut any_succeeded = false;
+
+        for provider in self.config.providers.iter() {
+            let nearby_stops = match provider.get_stops_near(lat, lon, radius_meters).await {
+                Ok(stops) => {
+                    any_succeeded = true;
+                    stops
                 }
-                _ => {
-                    return Err(TransitClientError::Internal(e.to_string()));
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch nearby stops from {}: {}",
+                        provider.name(),
+                        e
+                    );
+                    last_error = Some(e);
+                    continue;
                 }
-            },
-        };
+            };
 
-        let route_name = json
-            .data
-            .references
-            .routes
-            .iter()
-            .find(|r| r.id == route_id)
-            .map(|r| r.shortName.clone())
-            .unwrap_or("".to_string());
-
-        let stops_by_id = json
-            .data
-            .references
-            .stops
-            .iter()
-            .map(|s| (s.id.clone(), s))
-            .collect::<HashMap<String, &GetStopsForRouteResponseDataReferencesStop>>();
-
-        let mut result = GetStopsForRouteResult { groups: vec![] };
-
-        for (_, stop_group) in json.data.entry.stopGroupings.iter().enumerate() {
-            for (_, stop_group_nested) in stop_group.stopGroups.iter().enumerate() {
-                let GetStopsForRouteResponseDataEntryStopGroupingStopGroup {
-                    id: grouping_id,
-                    name: grouping_name,
-                    stopIds: stop_ids,
-                } = stop_group_nested;
-
-                let mut group_stops: Vec<GetStopsForRouteResultGroupStop> = vec![];
-
-                for (_, stop_id) in stop_ids.iter().enumerate() {
-                    let stop_info = match stops_by_id.get(stop_id) {
-                        Some(i) => i,
-                        None => continue,
-                    };
-
-                    group_stops.push(GetStopsForRouteResultGroupStop {
-                        id: stop_id.clone(),
-                        name: stop_info.name.clone(),
-                    });
+            let mut stop_ids: HashSet<String> = HashSet::new();
+            for stop in nearby_stops {
+                let distance_meters = distance_meters((lat, lon), (stop.lat, stop.lon));
+
+                // providers may search a box rather than a circle
+                if distance_meters > radius_meters || !stop_ids.insert(stop.id.clone()) {
+                    continue;
                 }
 
-                result.groups.push(GetStopsForRouteResultGroup {
-                    id: grouping_id.clone(),
-                    name: grouping_name.name.clone(),
-                    stops: group_stops,
-                    route_id: route_id.clone(),
-                    route_name: route_name.clone(),
-                });
+                stops.push((
+                    StopNearLocation {
+                        bearing_degrees: bearing_degrees((lat, lon), (stop.lat, stop.lon)),
+                        distance_meters,
+                        id: stop.id,
+                        name: stop.name,
+                        lat: stop.lat,
+                        lon: stop.lon,
+                        direction: stop.direction,
+                        route_ids: stop.route_ids,
+                    },
+                    provider.clone(),
+                ));
+            }
+
+            if !stops.is_empty() {
+                break;
             }
         }
 
-        Ok(result)
+        if let (false, Some(e)) = (any_succeeded, last_error) {
+            return Err(e);
+        }
+
+        stops.sort_by(|(a, _), (b, _)| a.distance_meters.total_cmp(&b.distance_meters));
+        if let Some(limit) = limit {
+            stops.truncate(limit);
+        }
+
+        Ok((lat, lon, stops))
+    }
+
+    pub async fn get_stops_for_route(
+        &self,
+        route_id: String,
+        include_polylines: bool,
+    ) -> Result<GetStopsForRouteResult, TransitClientError> {
+        for provider in self.config.providers.iter() {
+            match provider
+                .get_stops_for_route(&route_id, include_polylines)
+                .await
+            {
+                Err(TransitClientError::ResourceNotFound) => continue,
+                result => return result,
+            }
+        }
+
+        Err(TransitClientError::ResourceNotFound)
     }
 
+    /// Searches the providers in order, returning the routes of the first one with any. Later
+    /// providers are only searched when earlier ones find nothing or fail, and a failure is only
+    /// returned when every provider fails. Routes can be limited to a single `agency_id`.
     pub async fn get_routes(
         &self,
         search: &str,
+        agency_id: Option<&str>,
     ) -> Result<FindTransitRoutesResult, TransitClientError> {
-        let res = self
-            .client
-            .get(&format!(
-                "{}/api/where/routes-for-agency/MTA%20NYCT.json?key={}",
-                self.config.host, self.config.api_key
-            ))
-            .send()
-            .await
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?;
-
-        let mapped_routes = match res.error_for_status() {
-            Ok(r) => r
-                .json::<GetRoutesResponse>()
-                .await
-                .map_err(|e| TransitClientError::Internal(e.to_string()))?
-                .data
-                .list
-                .iter()
-                .filter(|d| d.shortName.to_lowercase().contains(&search.to_lowercase()))
-                .map(|d| FindTransitRoutesResultRoute {
-                    id: d.id.clone(),
-                    name: d.shortName.clone(),
-                })
-                .collect(),
-            Err(e) => return Err(TransitClientError::Internal(e.to_string())),
-        };
+        let mut last_error = None;
+        let mut any_succeeded = false;
+
+        for provider in self.config.providers.iter() {
+            match provider.get_routes(search, agency_id).await {
+                Ok(found) => {
+                    any_succeeded = true;
+
+                    let mut seen: HashSet<String> = HashSet::new();
+                    let routes = found
+                        .routes
+                        .into_iter()
+                        .filter(|r| seen.insert(r.id.clone()))
+                        .collect::<Vec<FindTransitRoutesResultRoute>>();
+
+                    if !routes.is_empty() {
+                        return Ok(FindTransitRoutesResult { routes });
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to fetch routes from {}: {}", provider.name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
 
-        Ok(FindTransitRoutesResult {
-            routes: mapped_routes,
-        })
+        match (any_succeeded, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(FindTransitRoutesResult { routes: Vec::new() }),
+        }
     }
 
+    /// Falls back to the stop's last good arrivals when fetching fails. While it keeps failing,
+    /// those are served at once and refreshed in the background instead.
     pub async fn fetch_stop_info(
         &self,
         stop_id: &str,
+        limit_per_route: usize,
     ) -> Result<Vec<StopInformation>, TransitClientError> {
-        let url = &format!(
-            "{}/api/siri/stop-monitoring.json?key={}&MonitoringRef={}",
-            self.config.host, self.config.api_key, stop_id
-        );
-
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?
-            .json::<GetStopInfoResponse>()
-            .await
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?;
+        let stale_arrivals = &self.config.stale_arrivals;
+
+        if stale_arrivals.is_failing(stop_id) {
+            if let Some(stale) = stale_arrivals.get(stop_id, limit_per_route).await {
+                if stale_arrivals.begin_refresh(stop_id) {
+                    let service = self.clone();
+                    let stop_id = stop_id.to_string();
+                    tokio::spawn(async move {
+                        let _ = service.refresh_stop_info(&stop_id, limit_per_route).await;
+                    });
+                }
+
+                return Ok(stale);
+            }
+        }
 
-        let stop_monitoring_delivery = response.Siri.ServiceDelivery.StopMonitoringDelivery.first();
+        match self.refresh_stop_info(stop_id, limit_per_route).await {
+            Err(e @ TransitClientError::ResourceNotFound) => Err(e),
+            Err(e) => match stale_arrivals.get(stop_id, limit_per_route).await {
+                Some(stale) => {
+                    warn!("Serving stale arrivals for stop {}: {}", stop_id, e);
+                    Ok(stale)
+                }
+                None => Err(e),
+            },
+            result => result,
+        }
+    }
 
-        let mut output = Vec::<StopInformation>::new();
+    async fn refresh_stop_info(
+        &self,
+        stop_id: &str,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        let result = self.fetch_fresh_stop_info(stop_id, limit_per_route).await;
 
-        let mut tracked_routes: HashSet<String> = HashSet::<String>::new();
+        match &result {
+            Ok(arrivals) => self.config.stale_arrivals.save(stop_id, arrivals),
+            Err(TransitClientError::ResourceNotFound) => self.config.stale_arrivals.forget(stop_id),
+            Err(_) => self.config.stale_arrivals.mark_failing(stop_id),
+        }
 
-        if let Some(delivery) = stop_monitoring_delivery {
-            for stop_visit in delivery.MonitoredStopVisit.iter() {
-                let hash_key = format!(
-                    "{}{}",
-                    stop_visit.MonitoredVehicleJourney.PublishedLineName,
-                    stop_visit.MonitoredVehicleJourney.DirectionRef
-                );
+        result
+    }
 
-                if tracked_routes.contains(&hash_key) {
-                    continue;
-                }
+    async fn fetch_fresh_stop_info(
+        &self,
+        stop_id: &str,
+        limit_per_route: usize,
+    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        for provider in self.config.providers.iter() {
+            match provider.fetch_stop_info(stop_id, limit_per_route).await {
+                Err(TransitClientError::ResourceNotFound) => continue,
+                result => return result,
+            }
+        }
 
-                let minutes_until_arrival = match &stop_visit
-                    .MonitoredVehicleJourney
-                    .MonitoredCall
-                    .ExpectedArrivalTime
-                {
-                    Some(s) => match DateTime::parse_from_rfc3339(&s) {
-                        Ok(d) => Some(d.signed_duration_since(Utc::now()).num_minutes()),
-                        Err(_) => Option::None,
-                    },
-                    None => None,
-                };
+        Err(TransitClientError::ResourceNotFound)
+    }
 
-                let expected_arrival_time = match &stop_visit
-                    .MonitoredVehicleJourney
-                    .MonitoredCall
-                    .ExpectedArrivalTime
-                {
-                    Some(s) => Some(s.clone()),
-                    None => None,
-                };
+    pub async fn fetch_vehicle_positions(
+        &self,
+        filter: &VehicleFilter,
+    ) -> Result<Vec<VehiclePosition>, TransitClientError> {
+        for provider in self.config.providers.iter() {
+            match provider.fetch_vehicle_positions(filter).await {
+                Err(TransitClientError::ResourceNotFound) => continue,
+                result => return result,
+            }
+        }
 
-                if let (Some(minutes_until_arrival), Some(expected_arrival_time)) =
-                    (minutes_until_arrival, expected_arrival_time)
-                {
-                    output.push(StopInformation {
-                        expected_arrival_time: expected_arrival_time,
-                        minutes_until_arrival,
-                        route_id: stop_visit.MonitoredVehicleJourney.LineRef.clone(),
-                        route_label: stop_visit.MonitoredVehicleJourney.PublishedLineName.clone(),
-                        stop_id: stop_id.to_string(),
-                    });
+        Err(TransitClientError::ResourceNotFound)
+    }
 
-                    tracked_routes.insert(hash_key);
+    /// Active alerts for any of `filters` from every provider, deduplicated by ID. A failing
+    /// provider is skipped unless every lookup fails.
+    pub async fn fetch_alerts(
+        &self,
+        filters: &[AlertFilter],
+    ) -> Result<Vec<ServiceAlert>, TransitClientError> {
+        let lookups = filters
+            .iter()
+            .flat_map(|f| self.config.providers.iter().map(move |p| (p, f)))
+            .collect::<Vec<_>>();
+        let results = join_all(lookups.iter().map(|(p, f)| p.fetch_alerts(f))).await;
+
+        let now = Utc::now();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut alerts = Vec::new();
+        let mut last_error = None;
+        let mut any_succeeded = false;
+
+        for ((provider, _), result) in lookups.iter().zip(results) {
+            match result {
+                Ok(found) => {
+                    any_succeeded = true;
+
+                    for alert in found {
+                        if alert.is_active(now) && seen.insert(alert.id.clone()) {
+                            alerts.push(alert);
+                        }
+                    }
+                }
+                // a provider that doesn't know a stop or route has no alerts for it
+                Err(TransitClientError::ResourceNotFound) => any_succeeded = true,
+                Err(e) => {
+                    warn!("Failed to fetch alerts from {}: {}", provider.name(), e);
+                    last_error = Some(e);
                 }
             }
+        }
 
-            return Ok(output);
-        } else {
-            return Ok(Vec::new());
+        match (any_succeeded, last_error) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(alerts),
         }
     }
 
+    /// Fetches arrivals for every stop independently. A stop that fails is reported in `errors`
+    /// without affecting the arrivals of the other stops.
     pub async fn fetch_multiple_stop_arrivals(
         &self,
         stop_ids: Vec<&str>,
-    ) -> Result<Vec<StopInformation>, TransitClientError> {
+        limit_per_route: usize,
+    ) -> MultipleStopArrivals {
         let mut fetches = Vec::new();
 
-        for stop_id in stop_ids {
-            fetches.push(self.fetch_stop_info(stop_id));
+        for stop_id in stop_ids.iter() {
+            fetches.push(self.fetch_stop_info(stop_id, limit_per_route));
         }
 
-        let mut output = Vec::<StopInformation>::new();
+        let mut output = MultipleStopArrivals {
+            arrivals: Vec::new(),
+            errors: Vec::new(),
+        };
 
-        try_join_all(fetches)
-            .await
-            .map_err(|e| TransitClientError::Internal(e.to_string()))?
-            .iter()
-            .for_each(|v| {
-                v.iter().for_each(|s| {
-                    output.push(StopInformation {
-                        expected_arrival_time: s.expected_arrival_time.clone(),
-                        minutes_until_arrival: s.minutes_until_arrival.clone(),
-                        route_id: s.route_id.clone(),
-                        route_label: s.route_label.clone(),
-                        stop_id: s.stop_id.clone(),
+        for (stop_id, result) in stop_ids.iter().zip(join_all(fetches).await) {
+            match result {
+                Ok(arrivals) => output.arrivals.extend(arrivals),
+                Err(e) => {
+                    warn!("Failed to fetch arrivals for stop {}: {}", stop_id, e);
+
+                    output.errors.push(StopArrivalsError {
+                        stop_id: stop_id.to_string(),
+                        reason: match e {
+                            TransitClientError::ResourceNotFound => "Stop not found".to_string(),
+                            TransitClientError::Internal(_) => {
+                                "Failed to fetch arrivals".to_string()
+                            }
+                            TransitClientError::Unavailable(..) => {
+                                "Arrivals are temporarily unavailable".to_string()
+                            }
+                        },
+                        not_found: e == TransitClientError::ResourceNotFound,
+                        unavailable: match e {
+                            TransitClientError::Unavailable(_, retry_after) => Some(retry_after),
+                            _ => None,
+                        },
                     });
-                });
-            });
+                }
+            }
+        }
 
         // sort by lowest minutes until arrival to highest
-        output.sort_by(|a, b| a.minutes_until_arrival.cmp(&b.minutes_until_arrival));
+        output.arrivals.sort_by_key(|a| a.minutes_until_arrival);
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        services::{
+            maps_client::maps_service::MapsServiceConfig,
+            transit_service::{
+                providers::fake_transit_provider::FakeTransitProvider, transit_provider::NearbyStop,
+            },
+        },
+        utils::upstream_client::UpstreamConfig,
+    };
+
+    use super::*;
+
+    fn service(providers: Vec<FakeTransitProvider>) -> TransitService {
+        TransitService::new(TransitServiceConfig {
+            providers: providers
+                .into_iter()
+                .map(|p| Arc::new(p) as Arc<dyn TransitProvider>)
+                .collect(),
+            maps_service: MapsService::new(MapsServiceConfig {
+                host: "http://localhost".to_string(),
+                api_key: "key".to_string(),
+                upstream: UpstreamConfig::default(),
+            }),
+            stale_arrivals: StaleArrivals::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn prefers_the_first_provider_with_routes() {
+        let service = service(vec![
+            FakeTransitProvider {
+                name: "first".to_string(),
+                routes: vec![
+                    ("MTA NYCT_B63".to_string(), "B63".to_string()),
+                    ("MTA NYCT_B61".to_string(), "B61".to_string()),
+                ],
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "down".to_string(),
+                failing: true,
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "second".to_string(),
+                routes: vec![
+                    ("MTA NYCT_B63".to_string(), "B63 duplicate".to_string()),
+                    ("MTABC_BM3".to_string(), "BM3".to_string()),
+                ],
+                ..Default::default()
+            },
+        ]);
+
+        let routes = service.get_routes("b", None).await.unwrap().routes;
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].name, "B63");
+        assert_eq!(routes[1].id, "MTA NYCT_B61");
+
+        // the failing provider is skipped on the way to one that has the route
+        let routes = service.get_routes("bm", None).await.unwrap().routes;
+
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].id, "MTABC_BM3");
+
+        let routes = service.get_routes("b", Some("MTABC")).await.unwrap().routes;
+
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].agency_id, "MTABC");
+
+        assert!(service
+            .get_routes("x", None)
+            .await
+            .unwrap()
+            .routes
+            .is_empty());
+
+        let all_down = self::service(vec![FakeTransitProvider {
+            failing: true,
+            ..Default::default()
+        }]);
+        assert!(all_down.get_routes("b", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn prefers_the_first_provider_with_nearby_stops() {
+        let stop = |id: &str| NearbyStop {
+            id: id.to_string(),
+            name: id.to_string(),
+            lat: 40.68,
+            lon: -73.98,
+            direction: None,
+            route_ids: Vec::new(),
+        };
+        let service = service(vec![
+            FakeTransitProvider {
+                name: "empty".to_string(),
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "down".to_string(),
+                failing: true,
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "first".to_string(),
+                stops: vec![stop("1")],
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "second".to_string(),
+                stops: vec![stop("2")],
+                ..Default::default()
+            },
+        ]);
+
+        let nearby = service
+            .get_stops_near(
+                GetStopsAtLocationInput::LatLong("40.68".to_string(), "-73.98".to_string()),
+                100.0,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(nearby.stops.len(), 1);
+        assert_eq!(nearby.stops[0].id, "1");
+    }
+
+    #[tokio::test]
+    async fn falls_through_to_provider_that_knows_the_stop() {
+        let service = service(vec![
+            FakeTransitProvider {
+                name: "first".to_string(),
+                arrivals: HashMap::from([("1".to_string(), vec![("B63".to_string(), 4)])]),
+                ..Default::default()
+            },
+            FakeTransitProvider {
+                name: "second".to_string(),
+                arrivals: HashMap::from([("2".to_string(), vec![("BM3".to_string(), 7)])]),
+                ..Default::default()
+            },
+        ]);
+
+        let arrivals = service.fetch_stop_info("2", 1).await.unwrap();
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(arrivals[0].route_id, "BM3");
 
-        Ok(output)
+        assert!(matches!(
+            service.fetch_stop_info("3", 1).await,
+            Err(TransitClientError::ResourceNotFound)
+        ));
     }
 }
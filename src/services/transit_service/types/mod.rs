@@ -1,5 +1,8 @@
+pub mod mta_get_agencies_with_coverage_response;
 pub mod mta_get_location_routes_response;
 pub mod mta_get_routes_response;
 pub mod mta_get_stop_response;
 pub mod mta_get_stops_at_location_response;
 pub mod mta_get_stops_for_route_response;
+pub mod mta_get_vehicle_monitoring_response;
+pub mod mta_situation_exchange_delivery;
@@ -0,0 +1,17 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize)]
+pub struct GetAgenciesWithCoverageResponseAgency {
+    pub agencyId: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetAgenciesWithCoverageResponseData {
+    pub list: Vec<GetAgenciesWithCoverageResponseAgency>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetAgenciesWithCoverageResponse {
+    pub data: GetAgenciesWithCoverageResponseData,
+}
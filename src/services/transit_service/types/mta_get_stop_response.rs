@@ -1,18 +1,55 @@
 #![allow(non_snake_case)]
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+use super::mta_situation_exchange_delivery::SituationExchangeDelivery;
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Distances {
+    /// e.g. "approaching", "1 stop away" or "2.3 miles away".
+    pub PresentableDistance: Option<String>,
+    pub DistanceFromCall: Option<f64>,
+    pub StopsFromCall: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Capacities {
+    pub EstimatedPassengerCount: Option<u32>,
+    pub EstimatedPassengerCapacity: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct MonitoredCallExtensions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Distances: Option<Distances>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Capacities: Option<Capacities>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
 pub struct MonitoredCall {
-    // pub AimedArrivalTime: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub AimedArrivalTime: Option<String>,
     pub ExpectedArrivalTime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Extensions: Option<MonitoredCallExtensions>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct MonitoredVehicleJourney {
     pub MonitoredCall: MonitoredCall,
     pub LineRef: String,
     pub DirectionRef: String,
     pub PublishedLineName: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub DestinationName: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub VehicleRef: Option<String>,
+    /// e.g. "layover" or "prevTrip" when the bus has not started this trip yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ProgressStatus: Option<String>,
+    /// "seatsAvailable", "standingAvailable" or "full".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Occupancy: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -20,9 +57,17 @@ pub struct MonitoredStopVisit {
     pub MonitoredVehicleJourney: MonitoredVehicleJourney,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct ErrorCondition {
+    pub Description: Option<String>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct StopMonitoringDelivery {
+    #[serde(default)]
     pub MonitoredStopVisit: Vec<MonitoredStopVisit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ErrorCondition: Option<ErrorCondition>,
     // pub ValidUntil: String,
 }
 
@@ -30,6 +75,8 @@ pub struct StopMonitoringDelivery {
 pub struct ServiceDelivery {
     // pub ResponseTimestamp: String,
     pub StopMonitoringDelivery: Vec<StopMonitoringDelivery>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub SituationExchangeDelivery: Vec<SituationExchangeDelivery>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -10,6 +10,13 @@ pub struct StopAtLocationRoute {
 #[derive(Deserialize, Serialize)]
 pub struct StopAtLocation {
     pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// e.g. "N" or "SW", empty when unknown.
+    #[serde(default)]
+    pub direction: Option<String>,
     pub routes: Vec<StopAtLocationRoute>,
 }
 
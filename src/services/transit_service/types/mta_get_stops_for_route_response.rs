@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 pub struct GetStopsForRouteResponseDataReferencesStop {
     pub id: String,
     pub name: String,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -24,11 +26,19 @@ pub struct GetStopsForRouteResponseDataEntryStopGroupingStopGroupName {
     pub name: String,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct GetStopsForRouteResponsePolyline {
+    pub points: String,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct GetStopsForRouteResponseDataEntryStopGroupingStopGroup {
     pub id: String,
     pub name: GetStopsForRouteResponseDataEntryStopGroupingStopGroupName,
     pub stopIds: Vec<String>,
+    /// Only sent with `includePolylines=true`.
+    #[serde(default)]
+    pub polylines: Vec<GetStopsForRouteResponsePolyline>,
 }
 
 #[derive(Deserialize, Serialize)]
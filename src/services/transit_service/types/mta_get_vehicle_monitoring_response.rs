@@ -0,0 +1,67 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+use super::{
+    mta_get_stop_response::ErrorCondition,
+    mta_situation_exchange_delivery::SituationExchangeDelivery,
+};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct VehicleLocation {
+    pub Longitude: f64,
+    pub Latitude: f64,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct VehicleMonitoredCall {
+    pub StopPointRef: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct VehicleJourney {
+    pub LineRef: String,
+    pub DirectionRef: Option<String>,
+    pub PublishedLineName: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub DestinationName: Option<String>,
+    pub VehicleRef: String,
+    pub VehicleLocation: VehicleLocation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Bearing: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ProgressStatus: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub MonitoredCall: Option<VehicleMonitoredCall>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VehicleActivity {
+    pub MonitoredVehicleJourney: VehicleJourney,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub RecordedAtTime: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VehicleMonitoringDelivery {
+    #[serde(default)]
+    pub VehicleActivity: Vec<VehicleActivity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ErrorCondition: Option<ErrorCondition>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VehicleServiceDelivery {
+    pub VehicleMonitoringDelivery: Vec<VehicleMonitoringDelivery>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub SituationExchangeDelivery: Vec<SituationExchangeDelivery>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VehicleSiri {
+    pub ServiceDelivery: VehicleServiceDelivery,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetVehicleMonitoringResponse {
+    pub Siri: VehicleSiri,
+}
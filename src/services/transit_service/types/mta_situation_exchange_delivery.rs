@@ -0,0 +1,72 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct PublicationWindow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub StartTime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub EndTime: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct AffectedVehicleJourney {
+    pub LineRef: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub DirectionRef: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct AffectedVehicleJourneys {
+    #[serde(default)]
+    pub AffectedVehicleJourney: Vec<AffectedVehicleJourney>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct AffectedStopPoint {
+    pub StopPointRef: String,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct AffectedStopPoints {
+    #[serde(default)]
+    pub AffectedStopPoint: Vec<AffectedStopPoint>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Affects {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub VehicleJourneys: Option<AffectedVehicleJourneys>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub StopPoints: Option<AffectedStopPoints>,
+}
+
+/// A detour, closure or other service change.
+#[derive(Deserialize, Serialize, Default)]
+pub struct PtSituationElement {
+    pub SituationNumber: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Description: Option<String>,
+    /// e.g. "undefined", "normal" or "severe".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Severity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub PublicationWindow: Option<PublicationWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub Affects: Option<Affects>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Situations {
+    #[serde(default)]
+    pub PtSituationElement: Vec<PtSituationElement>,
+}
+
+/// Sent alongside stop and vehicle monitoring deliveries with the situations affecting them.
+#[derive(Deserialize, Serialize, Default)]
+pub struct SituationExchangeDelivery {
+    #[serde(default)]
+    pub Situations: Situations,
+}
@@ -0,0 +1,2 @@
+pub mod sqlite_user_data_store;
+pub mod user_data_store;
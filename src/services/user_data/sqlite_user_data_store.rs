@@ -0,0 +1,509 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::user_data_store::{
+    CommuteProfile, Favorite, FavoriteKind, NotificationRule, UserDataError, UserDataStore,
+    MAX_COMMUTES, MAX_FAVORITES, MAX_NOTIFICATION_RULES,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS favorites (
+    client_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    PRIMARY KEY (client_id, name)
+);
+CREATE TABLE IF NOT EXISTS commutes (
+    client_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    stop_ids TEXT NOT NULL,
+    route_ids TEXT NOT NULL,
+    limit_per_route INTEGER,
+    PRIMARY KEY (client_id, name)
+);
+CREATE TABLE IF NOT EXISTS notification_rules (
+    client_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    stop_id TEXT NOT NULL,
+    route_id TEXT NOT NULL,
+    minutes_before INTEGER NOT NULL,
+    webhook_url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY (client_id, name)
+);
+";
+
+const NOTIFICATION_RULE_COLUMNS: &str =
+    "client_id, name, stop_id, route_id, minutes_before, webhook_url, secret, expires_at";
+
+type CommuteRow = (String, String, String, Option<i64>);
+
+fn notification_rule_from_row(row: &rusqlite::Row) -> rusqlite::Result<(String, NotificationRule)> {
+    Ok((
+        row.get(0)?,
+        NotificationRule {
+            name: row.get(1)?,
+            stop_id: row.get(2)?,
+            route_id: row.get(3)?,
+            minutes_before: row.get(4)?,
+            webhook_url: row.get(5)?,
+            secret: row.get(6)?,
+            // stored as unix seconds so that expiry is a plain comparison
+            expires_at: DateTime::from_timestamp(row.get(7)?, 0).unwrap_or_default(),
+        },
+    ))
+}
+
+/// Keeps user data in a single SQLite database. Queries run on the blocking pool since rusqlite
+/// is synchronous.
+#[derive(Clone)]
+pub struct SqliteUserDataStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteUserDataStore {
+    /// Opens the database at `path`, creating it and its tables when missing.
+    pub fn open(path: &Path) -> Result<Self, UserDataError> {
+        Self::init(Connection::open(path))
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, UserDataError> {
+        Self::init(Connection::open_in_memory())
+    }
+
+    fn init(connection: rusqlite::Result<Connection>) -> Result<Self, UserDataError> {
+        let connection = connection.map_err(|e| UserDataError::Internal(e.to_string()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| UserDataError::Internal(e.to_string()))?;
+
+        Ok(SqliteUserDataStore {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<T, F>(&self, query: F) -> Result<T, UserDataError>
+    where
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || query(&connection.lock().unwrap()))
+            .await
+            .map_err(|e| UserDataError::Internal(e.to_string()))?
+            .map_err(|e| UserDataError::Internal(e.to_string()))
+    }
+}
+
+fn commute_from_row(
+    (name, stop_ids, route_ids, limit_per_route): CommuteRow,
+) -> Result<CommuteProfile, UserDataError> {
+    let parse = |ids: &str| {
+        serde_json::from_str::<Vec<String>>(ids).map_err(|e| UserDataError::Internal(e.to_string()))
+    };
+
+    Ok(CommuteProfile {
+        name,
+        stop_ids: parse(&stop_ids)?,
+        route_ids: parse(&route_ids)?,
+        limit_per_route: limit_per_route.map(|l| l as usize),
+    })
+}
+
+/// Whether the client has room in `table` for another row named `name`. Replacing a row under
+/// the same name always fits.
+fn has_room(
+    c: &Connection,
+    table: &str,
+    client_id: &str,
+    name: &str,
+    max: usize,
+) -> rusqlite::Result<bool> {
+    let others: i64 = c.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM {} WHERE client_id = ?1 AND name != ?2",
+            table
+        ),
+        params![client_id, name],
+        |row| row.get(0),
+    )?;
+
+    Ok((others as usize) < max)
+}
+
+fn saved(saved: bool, max: usize) -> Result<(), UserDataError> {
+    match saved {
+        true => Ok(()),
+        false => Err(UserDataError::LimitReached(max)),
+    }
+}
+
+fn deleted(rows: usize) -> Result<(), UserDataError> {
+    match rows {
+        0 => Err(UserDataError::NotFound),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl UserDataStore for SqliteUserDataStore {
+    async fn list_favorites(&self, client_id: &str) -> Result<Vec<Favorite>, UserDataError> {
+        let client_id = client_id.to_string();
+
+        let rows = self
+            .run(move |c| {
+                c.prepare(
+                    "SELECT name, kind, target_id FROM favorites WHERE client_id = ?1 ORDER BY name",
+                )?
+                .query_map(params![client_id], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect::<rusqlite::Result<Vec<(String, String, String)>>>()
+            })
+            .await?;
+
+        // rows of a kind this build does not know about are skipped rather than failing the list
+        Ok(rows
+            .into_iter()
+            .filter_map(|(name, kind, id)| {
+                FavoriteKind::parse(&kind).map(|kind| Favorite { name, kind, id })
+            })
+            .collect())
+    }
+
+    async fn save_favorite(
+        &self,
+        client_id: &str,
+        favorite: Favorite,
+    ) -> Result<(), UserDataError> {
+        let client_id = client_id.to_string();
+
+        saved(
+            self.run(move |c| {
+                if !has_room(c, "favorites", &client_id, &favorite.name, MAX_FAVORITES)? {
+                    return Ok(false);
+                }
+
+                c.execute(
+                    "INSERT OR REPLACE INTO favorites (client_id, name, kind, target_id) VALUES (?1, ?2, ?3, ?4)",
+                    params![client_id, favorite.name, favorite.kind.as_str(), favorite.id],
+                )
+                .map(|_| true)
+            })
+            .await?,
+            MAX_FAVORITES,
+        )
+    }
+
+    async fn delete_favorite(&self, client_id: &str, name: &str) -> Result<(), UserDataError> {
+        let (client_id, name) = (client_id.to_string(), name.to_string());
+
+        deleted(
+            self.run(move |c| {
+                c.execute(
+                    "DELETE FROM favorites WHERE client_id = ?1 AND name = ?2",
+                    params![client_id, name],
+                )
+            })
+            .await?,
+        )
+    }
+
+    async fn list_commutes(&self, client_id: &str) -> Result<Vec<CommuteProfile>, UserDataError> {
+        let client_id = client_id.to_string();
+
+        self.run(move |c| {
+            c.prepare(
+                "SELECT name, stop_ids, route_ids, limit_per_route FROM commutes WHERE client_id = ?1 ORDER BY name",
+            )?
+            .query_map(params![client_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<Vec<CommuteRow>>>()
+        })
+        .await?
+        .into_iter()
+        .map(commute_from_row)
+        .collect()
+    }
+
+    async fn get_commute(
+        &self,
+        client_id: &str,
+        name: &str,
+    ) -> Result<CommuteProfile, UserDataError> {
+        let (client_id, name) = (client_id.to_string(), name.to_string());
+
+        let row = self
+            .run(move |c| {
+                c.query_row(
+                    "SELECT name, stop_ids, route_ids, limit_per_route FROM commutes WHERE client_id = ?1 AND name = ?2",
+                    params![client_id, name],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .optional()
+            })
+            .await?;
+
+        commute_from_row(row.ok_or(UserDataError::NotFound)?)
+    }
+
+    async fn save_commute(
+        &self,
+        client_id: &str,
+        commute: CommuteProfile,
+    ) -> Result<(), UserDataError> {
+        let client_id = client_id.to_string();
+        let stop_ids = serde_json::to_string(&commute.stop_ids).unwrap();
+        let route_ids = serde_json::to_string(&commute.route_ids).unwrap();
+
+        saved(
+            self.run(move |c| {
+                if !has_room(c, "commutes", &client_id, &commute.name, MAX_COMMUTES)? {
+                    return Ok(false);
+                }
+
+                c.execute(
+                    "INSERT OR REPLACE INTO commutes (client_id, name, stop_ids, route_ids, limit_per_route) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        client_id,
+                        commute.name,
+                        stop_ids,
+                        route_ids,
+                        commute.limit_per_route.map(|l| l as i64)
+                    ],
+                )
+                .map(|_| true)
+            })
+            .await?,
+            MAX_COMMUTES,
+        )
+    }
+
+    async fn delete_commute(&self, client_id: &str, name: &str) -> Result<(), UserDataError> {
+        let (client_id, name) = (client_id.to_string(), name.to_string());
+
+        deleted(
+            self.run(move |c| {
+                c.execute(
+                    "DELETE FROM commutes WHERE client_id = ?1 AND name = ?2",
+                    params![client_id, name],
+                )
+            })
+            .await?,
+        )
+    }
+
+    async fn list_notification_rules(
+        &self,
+        client_id: &str,
+    ) -> Result<Vec<NotificationRule>, UserDataError> {
+        let client_id = client_id.to_string();
+
+        let rows = self
+            .run(move |c| {
+                c.prepare(&format!(
+                    "SELECT {} FROM notification_rules WHERE client_id = ?1 ORDER BY name",
+                    NOTIFICATION_RULE_COLUMNS
+                ))?
+                .query_map(params![client_id], notification_rule_from_row)?
+                .collect::<rusqlite::Result<Vec<(String, NotificationRule)>>>()
+            })
+            .await?;
+
+        Ok(rows.into_iter().map(|(_, rule)| rule).collect())
+    }
+
+    async fn save_notification_rule(
+        &self,
+        client_id: &str,
+        rule: NotificationRule,
+    ) -> Result<(), UserDataError> {
+        let client_id = client_id.to_string();
+
+        saved(
+            self.run(move |c| {
+                if !has_room(
+                    c,
+                    "notification_rules",
+                    &client_id,
+                    &rule.name,
+                    MAX_NOTIFICATION_RULES,
+                )? {
+                    return Ok(false);
+                }
+
+                c.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO notification_rules ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        NOTIFICATION_RULE_COLUMNS
+                    ),
+                    params![
+                        client_id,
+                        rule.name,
+                        rule.stop_id,
+                        rule.route_id,
+                        rule.minutes_before,
+                        rule.webhook_url,
+                        rule.secret,
+                        rule.expires_at.timestamp()
+                    ],
+                )
+                .map(|_| true)
+            })
+            .await?,
+            MAX_NOTIFICATION_RULES,
+        )
+    }
+
+    async fn delete_notification_rule(
+        &self,
+        client_id: &str,
+        name: &str,
+    ) -> Result<(), UserDataError> {
+        let (client_id, name) = (client_id.to_string(), name.to_string());
+
+        deleted(
+            self.run(move |c| {
+                c.execute(
+                    "DELETE FROM notification_rules WHERE client_id = ?1 AND name = ?2",
+                    params![client_id, name],
+                )
+            })
+            .await?,
+        )
+    }
+
+    async fn all_notification_rules(
+        &self,
+    ) -> Result<Vec<(String, NotificationRule)>, UserDataError> {
+        self.run(move |c| {
+            c.prepare(&format!(
+                "SELECT {} FROM notification_rules",
+                NOTIFICATION_RULE_COLUMNS
+            ))?
+            .query_map([], notification_rule_from_row)?
+            .collect()
+        })
+        .await
+    }
+
+    async fn delete_expired_notification_rules(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<usize, UserDataError> {
+        self.run(move |c| {
+            c.execute(
+                "DELETE FROM notification_rules WHERE expires_at <= ?1",
+                params![now.timestamp()],
+            )
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_data_per_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("user_data.db");
+        let store = SqliteUserDataStore::open(&path).unwrap();
+
+        let morning = CommuteProfile {
+            name: "Morning".to_string(),
+            stop_ids: vec!["MTA_308209".to_string(), "MTA_308210".to_string()],
+            route_ids: vec!["MTA NYCT_B63".to_string()],
+            limit_per_route: Some(2),
+        };
+
+        store.save_commute("alice", morning.clone()).await.unwrap();
+        store
+            .save_favorite(
+                "alice",
+                Favorite {
+                    name: "Home".to_string(),
+                    kind: FavoriteKind::Stop,
+                    id: "MTA_308209".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_commute("alice", "Morning").await.unwrap(),
+            morning
+        );
+        assert!(matches!(
+            store.get_commute("bob", "Morning").await,
+            Err(UserDataError::NotFound)
+        ));
+        assert!(store.list_favorites("bob").await.unwrap().is_empty());
+
+        // saving under the same name replaces the profile
+        let evening = CommuteProfile {
+            route_ids: Vec::new(),
+            limit_per_route: None,
+            ..morning.clone()
+        };
+        store.save_commute("alice", evening.clone()).await.unwrap();
+        assert_eq!(store.list_commutes("alice").await.unwrap(), [evening]);
+
+        // and everything survives reopening the database
+        let store = SqliteUserDataStore::open(&path).unwrap();
+        assert_eq!(store.list_favorites("alice").await.unwrap().len(), 1);
+
+        store.delete_favorite("alice", "Home").await.unwrap();
+        assert!(matches!(
+            store.delete_favorite("alice", "Home").await,
+            Err(UserDataError::NotFound)
+        ));
+        store.delete_commute("alice", "Morning").await.unwrap();
+        assert!(store.list_commutes("alice").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn limits_rows_per_client() {
+        let store = SqliteUserDataStore::open_in_memory().unwrap();
+        let favorite = |name: String| Favorite {
+            name,
+            kind: FavoriteKind::Route,
+            id: "MTA NYCT_B63".to_string(),
+        };
+
+        for i in 0..MAX_FAVORITES {
+            store
+                .save_favorite("alice", favorite(i.to_string()))
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            store
+                .save_favorite("alice", favorite("new".to_string()))
+                .await,
+            Err(UserDataError::LimitReached(MAX_FAVORITES))
+        ));
+        // replacing one still fits, and other clients have their own room
+        store
+            .save_favorite("alice", favorite("0".to_string()))
+            .await
+            .unwrap();
+        store
+            .save_favorite("bob", favorite("new".to_string()))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The longest name a favorite or commute profile can be saved under.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// How many of each a client can save.
+pub const MAX_FAVORITES: usize = 100;
+pub const MAX_COMMUTES: usize = 20;
+pub const MAX_NOTIFICATION_RULES: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FavoriteKind {
+    Stop,
+    Route,
+}
+
+impl FavoriteKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FavoriteKind::Stop => "stop",
+            FavoriteKind::Route => "route",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "stop" => Some(FavoriteKind::Stop),
+            "route" => Some(FavoriteKind::Route),
+            _ => None,
+        }
+    }
+}
+
+/// A stop or route a client saved under a name of its choosing.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Favorite {
+    pub name: String,
+    pub kind: FavoriteKind,
+    pub id: String,
+}
+
+/// A named set of stops whose arrivals a client checks together, e.g. "Morning" for the stops
+/// near home filtered to the route taken to work.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommuteProfile {
+    pub name: String,
+    pub stop_ids: Vec<String>,
+    /// Empty to keep every route.
+    pub route_ids: Vec<String>,
+    pub limit_per_route: Option<usize>,
+}
+
+/// Asks for a webhook call when a bus of `route_id` is at most `minutes_before` minutes from
+/// `stop_id`, until `expires_at`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NotificationRule {
+    pub name: String,
+    pub stop_id: String,
+    pub route_id: String,
+    pub minutes_before: i64,
+    pub webhook_url: String,
+    /// Signs each call so that the receiver can tell it came from us.
+    pub secret: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum UserDataError {
+    NotFound,
+    /// The client already saved as many as it can under other names.
+    LimitReached(usize),
+    Internal(String),
+}
+
+impl std::fmt::Display for UserDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UserDataError::NotFound => write!(f, "Not found"),
+            UserDataError::LimitReached(max) => write!(f, "At most {} can be saved", max),
+            UserDataError::Internal(e) => write!(f, "Internal error: {}", e),
+        }
+    }
+}
+
+/// Favorites, commute profiles and notification rules, kept apart per client. Saving under an existing name
+/// replaces what was there, saving under a new one fails past the `MAX_*` limits.
+#[async_trait]
+pub trait UserDataStore: Send + Sync {
+    async fn list_favorites(&self, client_id: &str) -> Result<Vec<Favorite>, UserDataError>;
+
+    async fn save_favorite(&self, client_id: &str, favorite: Favorite)
+        -> Result<(), UserDataError>;
+
+    async fn delete_favorite(&self, client_id: &str, name: &str) -> Result<(), UserDataError>;
+
+    async fn list_commutes(&self, client_id: &str) -> Result<Vec<CommuteProfile>, UserDataError>;
+
+    async fn get_commute(
+        &self,
+        client_id: &str,
+        name: &str,
+    ) -> Result<CommuteProfile, UserDataError>;
+
+    async fn save_commute(
+        &self,
+        client_id: &str,
+        commute: CommuteProfile,
+    ) -> Result<(), UserDataError>;
+
+    async fn delete_commute(&self, client_id: &str, name: &str) -> Result<(), UserDataError>;
+
+    async fn list_notification_rules(
+        &self,
+        client_id: &str,
+    ) -> Result<Vec<NotificationRule>, UserDataError>;
+
+    async fn save_notification_rule(
+        &self,
+        client_id: &str,
+        rule: NotificationRule,
+    ) -> Result<(), UserDataError>;
+
+    async fn delete_notification_rule(
+        &self,
+        client_id: &str,
+        name: &str,
+    ) -> Result<(), UserDataError>;
+
+    /// Every client's rules paired with the client they belong to.
+    async fn all_notification_rules(
+        &self,
+    ) -> Result<Vec<(String, NotificationRule)>, UserDataError>;
+
+    /// Removes the rules that expired by `now`, returning how many there were.
+    async fn delete_expired_notification_rules(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<usize, UserDataError>;
+}
@@ -1,10 +1,23 @@
 use crate::services::{
-    maps_client::maps_service::MapsService, transit_service::transit_service::TransitService,
+    announcements::announcer::Announcer, api_keys::api_key_service::ApiKeyService,
+    departure_advice::departure_advisor::DepartureAdvisor,
+    gtfs_static::gtfs_static_store::GtfsStaticStore, jwt::jwt_verifier::JwtVerifier,
+    live_updates::live_poller::LivePoller, maps_client::maps_service::MapsService,
+    transit_service::transit_service::TransitService, user_data::user_data_store::UserDataStore,
 };
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub transit_service: TransitService,
+    pub live_poller: LivePoller,
+    pub departure_advisor: DepartureAdvisor,
+    /// Unset when no complete set of clips was recorded.
+    pub announcer: Option<Announcer>,
     pub maps_service: MapsService,
+    pub gtfs_static: GtfsStaticStore,
+    pub user_data: Arc<dyn UserDataStore>,
+    pub api_keys: ApiKeyService,
     pub auth_key: Option<String>,
+    pub jwt: Option<JwtVerifier>,
 }
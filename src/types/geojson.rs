@@ -0,0 +1,64 @@
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Whether an endpoint that returns locations answers with its own JSON or with GeoJSON, chosen
+/// with `format=json|geojson`.
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    GeoJson,
+}
+
+/// Positions are `[lon, lat]` as GeoJSON requires.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point { coordinates: [f64; 2] },
+    LineString { coordinates: Vec<[f64; 2]> },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename = "Feature")]
+pub struct Feature {
+    pub geometry: Geometry,
+    pub properties: Value,
+}
+
+impl Feature {
+    pub fn point((lat, lon): (f64, f64), properties: Value) -> Self {
+        Feature {
+            geometry: Geometry::Point {
+                coordinates: [lon, lat],
+            },
+            properties,
+        }
+    }
+
+    pub fn line_string(points: &[(f64, f64)], properties: Value) -> Self {
+        Feature {
+            geometry: Geometry::LineString {
+                coordinates: points.iter().map(|&(lat, lon)| [lon, lat]).collect(),
+            },
+            properties,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+}
+
+impl IntoResponse for FeatureCollection {
+    fn into_response(self) -> Response {
+        ([(CONTENT_TYPE, "application/geo+json")], Json(self)).into_response()
+    }
+}
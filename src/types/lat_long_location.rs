@@ -5,3 +5,29 @@ pub enum GetStopsAtLocationInput {
     LatLong(String, String),
     GooglePlaceId(String),
 }
+
+impl GetStopsAtLocationInput {
+    /// Reads a location from either `coordinates=lat,lon` or `place_id`, but not both.
+    pub fn from_query(
+        coordinates: Option<String>,
+        place_id: Option<String>,
+    ) -> Result<Self, &'static str> {
+        match (coordinates, place_id) {
+            (Some(coordinates), None) => {
+                let coordinates: Vec<&str> = coordinates.split(',').collect();
+
+                match (coordinates.first(), coordinates.get(1)) {
+                    (Some(lat), Some(lon)) => Ok(GetStopsAtLocationInput::LatLong(
+                        lat.to_string(),
+                        lon.to_string(),
+                    )),
+                    _ => Err("Invalid coordinates. Must be in the form of coordinates=lat,lon"),
+                }
+            }
+            (None, Some(place_id)) => Ok(GetStopsAtLocationInput::GooglePlaceId(place_id)),
+            _ => Err(
+                "Must provide either coordinates in the form of coordinates=lat,lon or place_id",
+            ),
+        }
+    }
+}
@@ -1,3 +1,4 @@
 pub mod app_state;
+pub mod geojson;
 pub mod lat_long_location;
 pub mod tomtom_search_response;
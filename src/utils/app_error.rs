@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use axum::{
     body::Body,
-    http::{Response, StatusCode},
+    http::{header::RETRY_AFTER, HeaderValue, Response, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -10,17 +12,33 @@ use serde::Serialize;
 pub struct AppError {
     pub code: StatusCode,
     pub message: String,
+    /// Sent as `Retry-After`.
+    pub retry_after: Option<Duration>,
 }
 
 impl AppError {
     pub fn new(code: StatusCode, message: &str) -> Self {
         AppError {
-            code: code,
+            code,
             message: message.to_string(),
+            retry_after: None,
+        }
+    }
+
+    /// An upstream is failing fast, for about `retry_after`.
+    pub fn unavailable(retry_after: Duration) -> Self {
+        AppError {
+            retry_after: Some(retry_after),
+            ..AppError::new(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable")
         }
     }
 }
 
+/// Whole seconds, rounded up so that retrying after them succeeds.
+pub fn header_seconds(duration: Duration) -> HeaderValue {
+    HeaderValue::from(duration.as_secs() + u64::from(duration.subsec_nanos() > 0))
+}
+
 #[derive(Serialize)]
 struct ResponseJson {
     message: String,
@@ -28,12 +46,20 @@ struct ResponseJson {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response<Body> {
-        (
+        let mut response = (
             self.code,
             Json(ResponseJson {
                 message: self.message,
             }),
         )
-            .into_response()
+            .into_response();
+
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, header_seconds(retry_after));
+        }
+
+        response
     }
 }
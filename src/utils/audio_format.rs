@@ -0,0 +1,160 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header::ACCEPT, request::Parts, HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+
+use super::app_error::AppError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Flac => "audio/flac",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    fn media_types(&self) -> &'static [&'static str] {
+        match self {
+            AudioFormat::Wav => &["audio/wav", "audio/wave", "audio/x-wav", "audio/vnd.wave"],
+            AudioFormat::Flac => &["audio/flac", "audio/x-flac"],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AudioFormatQuery {
+    format: Option<AudioFormat>,
+}
+
+/// The audio format a request asked for, from a `format=wav|flac` query parameter or else the
+/// `Accept` header. WAV is the default and wins ties.
+pub struct AcceptAudio(pub AudioFormat);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AcceptAudio
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<AudioFormatQuery>::try_from_uri(&parts.uri).map_err(|_| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                "Invalid query: format must be wav or flac",
+            )
+        })?;
+
+        if let Some(format) = query.format {
+            return Ok(AcceptAudio(format));
+        }
+
+        negotiate(&parts.headers)
+            .map(AcceptAudio)
+            .ok_or(AppError::new(
+                StatusCode::NOT_ACCEPTABLE,
+                "Only audio/wav and audio/flac are available",
+            ))
+    }
+}
+
+fn negotiate(headers: &HeaderMap) -> Option<AudioFormat> {
+    let ranges = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(parse_media_range)
+        .collect::<Vec<(String, f32)>>();
+
+    if ranges.is_empty() {
+        return Some(AudioFormat::Wav);
+    }
+
+    [AudioFormat::Wav, AudioFormat::Flac]
+        .into_iter()
+        .map(|format| (format, quality(&ranges, format)))
+        .filter(|(_, q)| *q > 0.0)
+        // the highest quality, keeping the first of equals so WAV wins ties
+        .min_by(|(_, a), (_, b)| b.total_cmp(a))
+        .map(|(format, _)| format)
+}
+
+/// The quality of the most specific range that matches `format`.
+fn quality(ranges: &[(String, f32)], format: AudioFormat) -> f32 {
+    let matching = |pattern: &dyn Fn(&str) -> bool| {
+        ranges
+            .iter()
+            .find(|(range, _)| pattern(range))
+            .map(|(_, q)| *q)
+    };
+
+    matching(&|range| format.media_types().contains(&range))
+        .or_else(|| matching(&|range| range == "audio/*"))
+        .or_else(|| matching(&|range| range == "*/*"))
+        .unwrap_or(0.0)
+}
+
+fn parse_media_range(range: &str) -> Option<(String, f32)> {
+    let mut parts = range.split(';');
+    let media_type = parts.next()?.trim().to_ascii_lowercase();
+
+    if media_type.is_empty() {
+        return None;
+    }
+
+    let q = parts
+        .filter_map(|p| p.trim().strip_prefix("q="))
+        .find_map(|q| q.parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+    Some((media_type, q))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn negotiate_accept(accept: &str) -> Option<AudioFormat> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(accept).unwrap());
+        negotiate(&headers)
+    }
+
+    #[test]
+    fn negotiates_accept() {
+        assert_eq!(negotiate(&HeaderMap::new()), Some(AudioFormat::Wav));
+        assert_eq!(negotiate_accept("audio/flac"), Some(AudioFormat::Flac));
+        assert_eq!(
+            negotiate_accept("audio/wav;q=0.5, audio/x-flac"),
+            Some(AudioFormat::Flac)
+        );
+        assert_eq!(
+            negotiate_accept("audio/webm,audio/ogg,audio/*;q=0.9,*/*;q=0.5"),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(
+            negotiate_accept("audio/*, audio/wav;q=0"),
+            Some(AudioFormat::Flac)
+        );
+        assert_eq!(negotiate_accept("application/json"), None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::convert::Infallible;
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CachePolicy {
+    Use,
+    Bypass,
+    /// Answers from the cache alone, and never calls the upstream.
+    Only,
+}
+
+impl CachePolicy {
+    /// The policy for lookups made alongside the one a request is for. Bypassing the cache
+    /// refreshes what was asked for, not everything attached to it.
+    pub fn alongside(self) -> CachePolicy {
+        match self {
+            CachePolicy::Bypass => CachePolicy::Only,
+            policy => policy,
+        }
+    }
+}
+
+/// Reads the request's `Cache-Control` header. `no-cache` and `no-store` skip cached upstream
+/// responses for that request.
+pub struct CacheControl(pub CachePolicy);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CacheControl
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let bypass = parts
+            .headers
+            .get_all("cache-control")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|d| {
+                let d = d.trim();
+                d.eq_ignore_ascii_case("no-cache") || d.eq_ignore_ascii_case("no-store")
+            });
+
+        Ok(CacheControl(match bypass {
+            true => CachePolicy::Bypass,
+            false => CachePolicy::Use,
+        }))
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use sha2::{Digest, Sha256};
+
+use crate::services::api_keys::api_key_store::Scope;
+
+use super::app_error::AppError;
+
+/// Who is making the request, as established by the auth middleware. Data saved per client is
+/// keyed by this, so it must stay stable for a given credential.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ClientIdentity(pub String);
+
+impl ClientIdentity {
+    /// Identifies the holders of a key shared between clients, such as `AUTH_KEY`, by its hash so
+    /// that the key is never written to storage.
+    pub fn from_key(key: &[u8]) -> Self {
+        ClientIdentity(format!("shared:{:x}", Sha256::digest(key)))
+    }
+
+    /// Identifies a client by the ID of its stored key, which survives rotating the key.
+    pub fn from_key_id(id: &str) -> Self {
+        ClientIdentity(format!("key:{}", id))
+    }
+
+    /// Identifies a client by the subject of its bearer token.
+    pub fn from_jwt_subject(sub: &str) -> Self {
+        ClientIdentity(format!("jwt:{}", sub))
+    }
+
+    pub fn anonymous() -> Self {
+        ClientIdentity("anonymous".to_string())
+    }
+
+    /// Whether the credential belongs to a single client, so that what is saved under it is
+    /// that client's alone.
+    pub fn is_per_client(&self) -> bool {
+        self.0.starts_with("key:") || self.0.starts_with("jwt:")
+    }
+}
+
+/// What the request's credential is allowed to do, set alongside `ClientIdentity`.
+#[derive(Clone, Debug)]
+pub struct GrantedScopes(pub Vec<Scope>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIdentity
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    /// Only extracts identities that are per client, since handlers key saved data by them.
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ClientIdentity>()
+            .filter(|identity| identity.is_per_client())
+            .cloned()
+            .ok_or(AppError::new(
+                StatusCode::UNAUTHORIZED,
+                "Requires an API key or bearer token of your own",
+            ))
+    }
+}
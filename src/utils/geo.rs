@@ -0,0 +1,51 @@
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// The great-circle distance between two points.
+pub fn distance_meters((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+/// The initial bearing from the first point towards the second, in degrees clockwise from north.
+pub fn bearing_degrees((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let y = d_lambda.sin() * phi2.cos();
+    let x = phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * d_lambda.cos();
+
+    (y.atan2(x).to_degrees() + 360.0) % 360.0
+}
+
+/// The nearest of the eight compass points, e.g. "NE".
+pub fn compass_point(bearing_degrees: f64) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+    POINTS[((bearing_degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_distance_and_bearing() {
+        let atlantic_terminal = (40.6842, -73.9772);
+        let barclays_center = (40.6826, -73.9754);
+
+        let distance = distance_meters(atlantic_terminal, barclays_center);
+        assert!((distance - 230.0).abs() < 10.0, "{}", distance);
+
+        let bearing = bearing_degrees(atlantic_terminal, barclays_center);
+        assert_eq!(compass_point(bearing), "SE");
+
+        assert_eq!(compass_point(0.0), "N");
+        assert_eq!(compass_point(350.0), "N");
+        assert_eq!(compass_point(270.0), "W");
+    }
+}
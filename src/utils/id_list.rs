@@ -0,0 +1,37 @@
+use urlencoding::decode;
+use validator::ValidationError;
+
+/// The IDs of a comma separated list, each percent-decoded. IDs that don't decode to UTF-8 are
+/// left out, `validate_id_list` rejects them in queries.
+pub fn split_ids(ids: &str) -> Vec<String> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| decode(s).ok())
+        .map(|s| s.into_owned())
+        .collect()
+}
+
+pub fn validate_id_list(ids: &str) -> Result<(), ValidationError> {
+    match ids.split(',').all(|s| decode(s.trim()).is_ok()) {
+        true => Ok(()),
+        false => Err(ValidationError::new("id_list")
+            .with_message("Must be comma separated, percent-encoded UTF-8 IDs".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_and_decodes() {
+        assert_eq!(
+            split_ids("B63, MTA%20NYCT_B41,,"),
+            vec!["B63", "MTA NYCT_B41"]
+        );
+        assert_eq!(split_ids("%FF,B63"), vec!["B63"]);
+        assert!(validate_id_list("B63,MTA%20NYCT_B41").is_ok());
+        assert!(validate_id_list("%FF").is_err());
+    }
+}
@@ -1,2 +1,15 @@
 pub mod app_error;
+pub mod audio_format;
+pub mod cache_control;
+pub mod client_identity;
+pub mod geo;
+pub mod id_list;
+pub mod polyline;
+pub mod ranged_response;
+pub mod rate_limiter;
+pub mod shared_poll;
+pub mod single_flight;
+pub mod ttl_cache;
+pub mod upstream_client;
+pub mod validated_json;
 pub mod validated_query;
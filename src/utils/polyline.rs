@@ -0,0 +1,99 @@
+/// Encodes `(lat, lon)` points with Google's encoded polyline algorithm at 5 decimal places, the
+/// same format OneBusAway returns.
+pub fn encode_polyline(points: &[(f64, f64)]) -> String {
+    let mut encoded = String::new();
+    let mut previous = (0, 0);
+
+    for &(lat, lon) in points {
+        let point = ((lat * 1e5).round() as i64, (lon * 1e5).round() as i64);
+
+        encode_value(point.0 - previous.0, &mut encoded);
+        encode_value(point.1 - previous.1, &mut encoded);
+
+        previous = point;
+    }
+
+    encoded
+}
+
+/// The `(lat, lon)` points of an encoded polyline, or `None` when it is malformed.
+pub fn decode_polyline(encoded: &str) -> Option<Vec<(f64, f64)>> {
+    let mut bytes = encoded.bytes();
+    let mut points = Vec::new();
+    let (mut lat, mut lon) = (0i64, 0i64);
+
+    while let Some(d_lat) = decode_value(&mut bytes) {
+        let d_lat = d_lat.ok()?;
+        let d_lon = decode_value(&mut bytes)?.ok()?;
+
+        lat += d_lat;
+        lon += d_lon;
+        points.push((lat as f64 / 1e5, lon as f64 / 1e5));
+    }
+
+    Some(points)
+}
+
+fn encode_value(value: i64, encoded: &mut String) {
+    let mut value = match value < 0 {
+        true => !(value << 1),
+        false => value << 1,
+    };
+
+    while value >= 0x20 {
+        encoded.push((((value & 0x1f) | 0x20) as u8 + 63) as char);
+        value >>= 5;
+    }
+
+    encoded.push((value as u8 + 63) as char);
+}
+
+/// `None` at the end of the input, `Some(Err)` when a value is cut short or out of range.
+fn decode_value(bytes: &mut impl Iterator<Item = u8>) -> Option<Result<i64, ()>> {
+    let mut value = 0i64;
+    let mut shift = 0;
+
+    let mut byte = bytes.next()?;
+
+    loop {
+        if !(63..=126).contains(&byte) || shift > 60 {
+            return Some(Err(()));
+        }
+
+        let chunk = (byte - 63) as i64;
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+
+        if chunk < 0x20 {
+            break;
+        }
+
+        byte = match bytes.next() {
+            Some(byte) => byte,
+            None => return Some(Err(())),
+        };
+    }
+
+    Some(Ok(match value & 1 {
+        1 => !(value >> 1),
+        _ => value >> 1,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_polylines() {
+        // the example from Google's documentation
+        let points = [(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)];
+        let encoded = "_p~iF~ps|U_ulLnnqC_mqNvxq`@";
+
+        assert_eq!(encode_polyline(&points), encoded);
+        assert_eq!(decode_polyline(encoded).unwrap(), points);
+        assert_eq!(decode_polyline("").unwrap(), []);
+        assert_eq!(decode_polyline("_p~iF"), None);
+        assert_eq!(decode_polyline("_p~iF~ps|"), None);
+    }
+}
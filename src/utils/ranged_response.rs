@@ -0,0 +1,180 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE, VARY,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+
+/// An in-memory body served with `ETag`/`Last-Modified` validators and single `Range` requests,
+/// which media players such as iOS Safari rely on to seek.
+pub struct RangedResponse {
+    pub bytes: Bytes,
+    pub content_type: &'static str,
+    pub filename: String,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl RangedResponse {
+    pub fn respond(self, request_headers: &HeaderMap) -> Response {
+        let etag = format!("\"{:016x}\"", {
+            let mut hasher = DefaultHasher::new();
+            self.bytes.hash(&mut hasher);
+            hasher.finish()
+        });
+        let last_modified = self.last_modified.map(httpdate::fmt_http_date);
+        let len = self.bytes.len();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(self.content_type));
+        headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        headers.insert(VARY, HeaderValue::from_static("accept"));
+        headers.insert(ETAG, HeaderValue::from_str(&etag).unwrap());
+        if let Some(last_modified) = &last_modified {
+            headers.insert(LAST_MODIFIED, HeaderValue::from_str(last_modified).unwrap());
+        }
+        if let Ok(disposition) =
+            HeaderValue::from_str(&format!("inline; filename=\"{}\"", self.filename))
+        {
+            headers.insert(CONTENT_DISPOSITION, disposition);
+        }
+
+        if self.not_modified(request_headers, &etag) {
+            return (StatusCode::NOT_MODIFIED, headers).into_response();
+        }
+
+        let range = request_headers
+            .get(RANGE)
+            .and_then(|v| v.to_str().ok())
+            .filter(|_| if_range_matches(request_headers, &etag, last_modified.as_deref()));
+
+        match range.and_then(|r| parse_range(r, len)) {
+            None => (headers, Body::from(self.bytes)).into_response(),
+            Some(Ok((start, end))) => {
+                headers.insert(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)).unwrap(),
+                );
+
+                (
+                    StatusCode::PARTIAL_CONTENT,
+                    headers,
+                    Body::from(self.bytes.slice(start..=end)),
+                )
+                    .into_response()
+            }
+            Some(Err(())) => {
+                headers.remove(CONTENT_TYPE);
+                headers.insert(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", len)).unwrap(),
+                );
+
+                (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response()
+            }
+        }
+    }
+
+    /// `If-None-Match` takes precedence over `If-Modified-Since` when both are sent.
+    fn not_modified(&self, request_headers: &HeaderMap, etag: &str) -> bool {
+        if let Some(if_none_match) = request_headers.get(IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|v| {
+                v.split(',')
+                    .map(|tag| tag.trim())
+                    .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+            });
+        }
+
+        match (self.last_modified, request_headers.get(IF_MODIFIED_SINCE)) {
+            (Some(last_modified), Some(since)) => since
+                .to_str()
+                .ok()
+                .and_then(|s| httpdate::parse_http_date(s).ok())
+                .is_some_and(|since| seconds(last_modified) <= seconds(since)),
+            _ => false,
+        }
+    }
+}
+
+/// A `Range` only applies when any `If-Range` still matches, otherwise the whole body is sent.
+fn if_range_matches(request_headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    match request_headers.get(IF_RANGE).map(|v| v.to_str()) {
+        None => true,
+        Some(Ok(validator)) if validator.starts_with('"') => validator == etag,
+        Some(Ok(validator)) => Some(validator) == last_modified,
+        Some(Err(_)) => false,
+    }
+}
+
+/// The inclusive byte range of a single `bytes=` range, or `Err` when it can't be satisfied.
+/// Malformed and multiple ranges are ignored so the whole body is sent.
+fn parse_range(range: &str, len: usize) -> Option<Result<(usize, usize), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.is_empty(), end.is_empty()) {
+        // the last `end` bytes
+        (true, false) => {
+            let suffix = end.parse::<usize>().ok()?;
+            match suffix {
+                0 => return Some(Err(())),
+                _ => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            }
+        }
+        (false, true) => (start.parse().ok()?, len.saturating_sub(1)),
+        (false, false) => {
+            let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.min(len.saturating_sub(1)))
+        }
+        (true, true) => return None,
+    };
+
+    match range.0 < len {
+        true => Some(Ok(range)),
+        false => Some(Err(())),
+    }
+}
+
+/// HTTP dates only have whole seconds.
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-1", 10), Some(Ok((0, 1))));
+        assert_eq!(parse_range("bytes=4-", 10), Some(Ok((4, 9))));
+        assert_eq!(parse_range("bytes=-3", 10), Some(Ok((7, 9))));
+        assert_eq!(parse_range("bytes=-30", 10), Some(Ok((0, 9))));
+        assert_eq!(parse_range("bytes=5-100", 10), Some(Ok((5, 9))));
+        assert_eq!(parse_range("bytes=10-", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=-0", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-0", 0), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+        assert_eq!(parse_range("bytes=5-1", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Past this many buckets, those left idle for `IDLE_BUCKET_TTL` are dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Longer than any bucket takes to refill, so only quota counts are lost by dropping one.
+const IDLE_BUCKET_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RateLimit {
+    /// How many requests can be made at once after a quiet period.
+    pub burst: u32,
+    /// How fast the burst refills. Must not be zero.
+    pub per_minute: u32,
+    /// Requests allowed per UTC day.
+    pub daily_quota: Option<u64>,
+}
+
+impl RateLimit {
+    /// Parses `<per minute>:<burst>[:<daily quota>]`, e.g. `10:5:500`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.trim().split(':');
+        let per_minute = parts.next()?.parse().ok().filter(|n| *n > 0)?;
+        let burst = parts.next()?.parse().ok()?;
+        let daily_quota = match parts.next() {
+            Some(quota) => Some(quota.parse().ok()?),
+            None => None,
+        };
+
+        Some(RateLimit {
+            burst,
+            per_minute,
+            daily_quota,
+        })
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum RateLimitOutcome {
+    Allowed {
+        remaining: u32,
+        /// Until the burst is full again.
+        reset_after: Duration,
+        quota_remaining: Option<u64>,
+    },
+    Limited {
+        retry_after: Duration,
+    },
+    QuotaExceeded {
+        /// Until the next UTC midnight.
+        retry_after: Duration,
+    },
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    day: NaiveDate,
+    used_today: u64,
+}
+
+/// Token buckets with daily counters, kept in memory by key.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from `key`'s bucket when one is left and the daily quota allows it.
+    pub fn check(&self, key: &str, limit: RateLimit) -> RateLimitOutcome {
+        self.check_at(key, limit, Instant::now(), Utc::now())
+    }
+
+    fn check_at(
+        &self,
+        key: &str,
+        limit: RateLimit,
+        now: Instant,
+        utc_now: DateTime<Utc>,
+    ) -> RateLimitOutcome {
+        let per_second = limit.per_minute as f64 / 60.0;
+        let burst = limit.burst as f64;
+        let today = utc_now.date_naive();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                now.saturating_duration_since(bucket.updated_at) < IDLE_BUCKET_TTL
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+            day: today,
+            used_today: 0,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * per_second).min(burst);
+        bucket.updated_at = now;
+        if bucket.day != today {
+            bucket.day = today;
+            bucket.used_today = 0;
+        }
+
+        if limit
+            .daily_quota
+            .is_some_and(|quota| bucket.used_today >= quota)
+        {
+            let midnight = (today + chrono::Duration::days(1))
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_utc();
+
+            return RateLimitOutcome::QuotaExceeded {
+                retry_after: (midnight - utc_now).to_std().unwrap_or_default(),
+            };
+        }
+
+        if bucket.tokens < 1.0 {
+            return RateLimitOutcome::Limited {
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / per_second),
+            };
+        }
+
+        bucket.tokens -= 1.0;
+        bucket.used_today += 1;
+
+        RateLimitOutcome::Allowed {
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((burst - bucket.tokens) / per_second),
+            quota_remaining: limit
+                .daily_quota
+                .map(|quota| quota.saturating_sub(bucket.used_today)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refills_and_enforces_quotas() {
+        let limiter = RateLimiter::default();
+        let limit = RateLimit {
+            burst: 2,
+            per_minute: 60,
+            daily_quota: Some(3),
+        };
+        let start = Instant::now();
+        let noon = "2024-07-23T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        let check = |key: &str, secs: f64| {
+            limiter.check_at(key, limit, start + Duration::from_secs_f64(secs), noon)
+        };
+
+        assert!(matches!(
+            check("a", 0.0),
+            RateLimitOutcome::Allowed { remaining: 1, .. }
+        ));
+        assert!(matches!(
+            check("a", 0.0),
+            RateLimitOutcome::Allowed { remaining: 0, .. }
+        ));
+        assert_eq!(
+            check("a", 0.5),
+            RateLimitOutcome::Limited {
+                retry_after: Duration::from_millis(500)
+            }
+        );
+        // other keys have their own bucket
+        assert!(matches!(check("b", 0.5), RateLimitOutcome::Allowed { .. }));
+
+        assert!(matches!(
+            check("a", 1.0),
+            RateLimitOutcome::Allowed {
+                quota_remaining: Some(0),
+                ..
+            }
+        ));
+        assert_eq!(
+            check("a", 10.0),
+            RateLimitOutcome::QuotaExceeded {
+                retry_after: Duration::from_secs(12 * 60 * 60)
+            }
+        );
+
+        // the quota starts over the next day
+        let tomorrow = noon + chrono::Duration::days(1);
+        assert!(matches!(
+            limiter.check_at("a", limit, start + Duration::from_secs(20), tomorrow),
+            RateLimitOutcome::Allowed { .. }
+        ));
+
+        assert_eq!(
+            RateLimit::parse("10:5:500"),
+            Some(RateLimit {
+                burst: 5,
+                per_minute: 10,
+                daily_quota: Some(500)
+            })
+        );
+        assert_eq!(RateLimit::parse("0:5"), None);
+    }
+
+    #[test]
+    fn drops_idle_buckets() {
+        let limiter = RateLimiter::default();
+        let limit = RateLimit {
+            burst: 1,
+            per_minute: 1,
+            daily_quota: None,
+        };
+        let start = Instant::now();
+        let now = Utc::now();
+
+        for i in 0..=PRUNE_THRESHOLD {
+            limiter.check_at(&i.to_string(), limit, start, now);
+        }
+        limiter.check_at("recent", limit, start + IDLE_BUCKET_TTL / 2, now);
+        limiter.check_at("new", limit, start + IDLE_BUCKET_TTL, now);
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert!(buckets.contains_key("recent"));
+    }
+}
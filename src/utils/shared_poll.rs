@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::watch, time::MissedTickBehavior};
+
+/// The latest polled value, or `None` until the first poll completes.
+pub type PollUpdate<T> = Option<Arc<T>>;
+
+type Polls<T> = Arc<Mutex<HashMap<String, Arc<watch::Sender<PollUpdate<T>>>>>>;
+
+/// Runs one polling loop per key, however many subscribers are watching it. Subscribers are only
+/// woken when the polled value changes, and a loop stops once its last subscriber goes away.
+///
+/// Clones share the same set of polls.
+pub struct SharedPoll<T> {
+    interval: Duration,
+    polls: Polls<T>,
+}
+
+impl<T> Clone for SharedPoll<T> {
+    fn clone(&self) -> Self {
+        SharedPoll {
+            interval: self.interval,
+            polls: self.polls.clone(),
+        }
+    }
+}
+
+impl<T> SharedPoll<T>
+where
+    T: PartialEq + Send + Sync + 'static,
+{
+    pub fn new(interval: Duration) -> Self {
+        SharedPoll {
+            interval,
+            polls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Subscribes to the poll for `key`, starting it with `fetch` unless it is already running.
+    pub fn subscribe<F, Fut>(&self, key: &str, fetch: F) -> watch::Receiver<PollUpdate<T>>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let mut polls = self.polls.lock().unwrap();
+
+        if let Some(sender) = polls.get(key) {
+            return sender.subscribe();
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        let sender = Arc::new(sender);
+        polls.insert(key.to_string(), sender.clone());
+
+        tokio::spawn(run(
+            self.polls.clone(),
+            key.to_string(),
+            sender,
+            self.interval,
+            fetch,
+        ));
+
+        receiver
+    }
+
+    #[cfg(test)]
+    pub fn active_polls(&self) -> usize {
+        self.polls.lock().unwrap().len()
+    }
+}
+
+async fn run<T, F, Fut>(
+    polls: Polls<T>,
+    key: String,
+    sender: Arc<watch::Sender<PollUpdate<T>>>,
+    interval: Duration,
+    fetch: F,
+) where
+    T: PartialEq,
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = sender.closed() => {
+                let mut polls = polls.lock().unwrap();
+
+                // someone may have subscribed between the last receiver closing and the lock
+                if sender.receiver_count() == 0 {
+                    polls.remove(&key);
+                    return;
+                }
+
+                continue;
+            }
+        }
+
+        let value = fetch().await;
+
+        sender.send_if_modified(|current| match current {
+            Some(current) if **current == value => false,
+            _ => {
+                *current = Some(Arc::new(value));
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn shares_polls_until_unsubscribed() {
+        let shared_poll = SharedPoll::<usize>::new(Duration::from_millis(10));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let fetch = move || {
+            let calls = calls.clone();
+            async move { calls.fetch_add(1, Ordering::SeqCst) / 3 }
+        };
+
+        let mut first = shared_poll.subscribe("key", fetch.clone());
+        let mut second = shared_poll.subscribe("key", fetch.clone());
+        let other = shared_poll.subscribe("other", fetch);
+
+        assert_eq!(shared_poll.active_polls(), 2);
+
+        first.changed().await.unwrap();
+        second.changed().await.unwrap();
+        assert_eq!(*first.borrow_and_update().clone().unwrap(), 0);
+
+        // the value only changes every third poll
+        first.changed().await.unwrap();
+        assert!(*first.borrow().clone().unwrap() > 0);
+
+        drop(first);
+        drop(second);
+        drop(other);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(shared_poll.active_polls(), 0);
+    }
+}
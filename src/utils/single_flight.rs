@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+
+/// Coalesces concurrent calls that share a key into a single execution of the underlying future.
+/// Every caller waiting on the same key receives a clone of the same output, errors included.
+///
+/// Clones share the same set of in-flight calls.
+#[derive(Clone)]
+pub struct SingleFlight<T: Clone> {
+    in_flight: Arc<Mutex<HashMap<String, Shared<BoxFuture<'static, T>>>>>,
+}
+
+impl<T> Default for SingleFlight<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<T> SingleFlight<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Runs `fut` unless a call for `key` is already in flight, in which case that call's result
+    /// is awaited instead and `fut` is dropped without being polled.
+    pub async fn run<F>(&self, key: &str, fut: F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+
+            match in_flight.get(key) {
+                Some(existing) => existing.clone(),
+                None => {
+                    let map = self.in_flight.clone();
+                    let owned_key = key.to_string();
+
+                    let shared = async move {
+                        let output = fut.await;
+                        map.lock().unwrap().remove(&owned_key);
+                        output
+                    }
+                    .boxed()
+                    .shared();
+
+                    in_flight.insert(key.to_string(), shared.clone());
+                    shared
+                }
+            }
+        };
+
+        shared.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures::future::join_all;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn coalesces_concurrent_calls() {
+        let single_flight = SingleFlight::<Result<u32, String>>::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let results = join_all((0..5).map(|_| {
+            let calls = calls.clone();
+
+            single_flight.run("key", async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Err("failed".to_string())
+            })
+        }))
+        .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|r| r == &Err("failed".to_string())));
+
+        // the finished call is no longer in flight, so the next one runs again
+        single_flight.run("key", async { Ok(1) }).await.unwrap();
+        assert!(single_flight.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+struct TtlCacheEntry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// A bounded in-memory cache where every entry carries its own time-to-live.
+///
+/// Clones share the same underlying storage and counters.
+#[derive(Clone)]
+pub struct TtlCache<V> {
+    entries: Arc<Mutex<HashMap<String, TtlCacheEntry<V>>>>,
+    max_entries: usize,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(max_entries: usize) -> Self {
+        TtlCache {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            max_entries,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let value = match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        value
+    }
+
+    pub fn insert(&self, key: String, value: V, ttl: Duration) {
+        if self.max_entries == 0 || ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, e| e.expires_at > now);
+        }
+
+        // still full after dropping expired entries, so evict whatever expires soonest
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let soonest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.expires_at)
+                .map(|(k, _)| k.clone());
+
+            if let Some(soonest) = soonest {
+                entries.remove(&soonest);
+            }
+        }
+
+        entries.insert(
+            key,
+            TtlCacheEntry {
+                value,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_entries() {
+        let cache = TtlCache::<u32>::new(10);
+
+        cache.insert("a".to_string(), 1, Duration::from_secs(60));
+        cache.insert("b".to_string(), 2, Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("b"), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.entries, 1);
+    }
+
+    #[test]
+    fn evicts_when_full() {
+        let cache = TtlCache::<u32>::new(2);
+
+        cache.insert("a".to_string(), 1, Duration::from_secs(10));
+        cache.insert("b".to_string(), 2, Duration::from_secs(60));
+        cache.insert("c".to_string(), 3, Duration::from_secs(60));
+
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(2));
+        assert_eq!(cache.get("c"), Some(3));
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
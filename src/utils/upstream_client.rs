@@ -0,0 +1,296 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::body::Bytes;
+use rand::Rng;
+use reqwest::StatusCode;
+use tracing::warn;
+
+#[derive(Clone)]
+pub struct UpstreamConfig {
+    pub connect_timeout: Duration,
+    /// How long to wait on each read of the response.
+    pub read_timeout: Duration,
+    /// How long each attempt may take in total, from connecting to reading the whole body.
+    pub timeout: Duration,
+    /// How long a call may take across every attempt and the delays between them.
+    pub deadline: Duration,
+    pub max_attempts: u32,
+    /// Doubled after every failed attempt, with jitter.
+    pub retry_delay: Duration,
+    /// Consecutive failed calls that open the circuit breaker.
+    pub failure_threshold: u32,
+    /// How long an open breaker fails calls before letting one through to try the upstream.
+    pub open_duration: Duration,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        UpstreamConfig {
+            connect_timeout: Duration::from_secs(3),
+            read_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(15),
+            deadline: Duration::from_secs(30),
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(200),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum UpstreamError {
+    /// The circuit breaker is open, so the upstream was not called. It lets a call through
+    /// after this long.
+    Unavailable(Duration),
+    /// The upstream answered with a status that retrying will not change.
+    Status(StatusCode),
+    /// Every attempt failed, the last one for this reason.
+    Failed(String),
+}
+
+impl std::fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UpstreamError::Unavailable(_) => write!(f, "Circuit breaker is open"),
+            UpstreamError::Status(status) => write!(f, "Upstream responded with {}", status),
+            UpstreamError::Failed(e) => write!(f, "Upstream request failed: {}", e),
+        }
+    }
+}
+
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single call is trying the upstream. Another is let through if it never finishes.
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// A `reqwest::Client` for one upstream, with timeouts, retries of idempotent GETs and a circuit
+/// breaker shared by every clone.
+#[derive(Clone)]
+pub struct UpstreamClient {
+    name: String,
+    client: reqwest::Client,
+    config: UpstreamConfig,
+    breaker: Arc<Mutex<BreakerState>>,
+}
+
+impl UpstreamClient {
+    pub fn new(name: &str, config: UpstreamConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .timeout(config.timeout)
+            .build()
+            .expect("HTTP client should build");
+
+        UpstreamClient {
+            name: name.to_string(),
+            client,
+            config,
+            breaker: Arc::new(Mutex::new(BreakerState::Closed { failures: 0 })),
+        }
+    }
+
+    /// GETs `url`, retrying connection failures, timeouts and 5xxs until the deadline.
+    pub async fn get(&self, url: &str) -> Result<Bytes, UpstreamError> {
+        self.acquire()?;
+
+        let result = tokio::time::timeout(self.config.deadline, self.get_with_retries(url))
+            .await
+            .unwrap_or_else(|_| {
+                Err(UpstreamError::Failed(format!(
+                    "no response within {:?}",
+                    self.config.deadline
+                )))
+            });
+        match &result {
+            Ok(_) | Err(UpstreamError::Status(_)) => self.record_success(),
+            Err(_) => self.record_failure(),
+        }
+
+        result
+    }
+
+    async fn get_with_retries(&self, url: &str) -> Result<Bytes, UpstreamError> {
+        let mut delay = self.config.retry_delay;
+        let mut attempt = 1;
+
+        loop {
+            let reason = match self.client.get(url).send().await {
+                Ok(res) if res.status().is_success() => match res.bytes().await {
+                    Ok(body) => return Ok(body),
+                    Err(e) => e.to_string(),
+                },
+                Ok(res) if !res.status().is_server_error() => {
+                    return Err(UpstreamError::Status(res.status()));
+                }
+                Ok(res) => res.status().to_string(),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= self.config.max_attempts {
+                return Err(UpstreamError::Failed(reason));
+            }
+
+            warn!(
+                "{} request attempt {} failed with {}, retrying",
+                self.name, attempt, reason
+            );
+            // jitter keeps clients that failed together from retrying together
+            let jittered = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+            tokio::time::sleep(jittered).await;
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    fn acquire(&self) -> Result<(), UpstreamError> {
+        let mut breaker = self.breaker.lock().unwrap();
+        let now = Instant::now();
+
+        match *breaker {
+            BreakerState::Closed { .. } => Ok(()),
+            BreakerState::Open { until } if now < until => {
+                Err(UpstreamError::Unavailable(until - now))
+            }
+            BreakerState::HalfOpen { since } if now < since + self.config.open_duration => Err(
+                UpstreamError::Unavailable(since + self.config.open_duration - now),
+            ),
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                *breaker = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        *self.breaker.lock().unwrap() = BreakerState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        let failures = match *breaker {
+            BreakerState::Closed { failures } => failures + 1,
+            _ => self.config.failure_threshold,
+        };
+
+        *breaker = match failures >= self.config.failure_threshold {
+            true => {
+                warn!(
+                    "Opening circuit breaker for {} for {:?}",
+                    self.name, self.config.open_duration
+                );
+                BreakerState::Open {
+                    until: Instant::now() + self.config.open_duration,
+                }
+            }
+            false => BreakerState::Closed { failures },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{extract::State, http::StatusCode, routing::get, Router};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn retries_and_breaks_the_circuit() {
+        // fails the first call of every three
+        async fn flaky(State(calls): State<Arc<AtomicUsize>>) -> (StatusCode, &'static str) {
+            match calls.fetch_add(1, Ordering::SeqCst) % 3 {
+                0 => (StatusCode::SERVICE_UNAVAILABLE, "down"),
+                _ => (StatusCode::OK, "up"),
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .route("/flaky", get(flaky))
+            .route("/missing", get(|| async { StatusCode::NOT_FOUND }))
+            .route("/down", get(|| async { StatusCode::BAD_GATEWAY }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "late"
+                }),
+            )
+            .with_state(calls.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let client = UpstreamClient::new(
+            "test",
+            UpstreamConfig {
+                max_attempts: 2,
+                retry_delay: Duration::from_millis(1),
+                failure_threshold: 2,
+                open_duration: Duration::from_millis(200),
+                ..Default::default()
+            },
+        );
+
+        let body = client.get(&format!("{}/flaky", host)).await.unwrap();
+        assert_eq!(body, "up");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        assert!(matches!(
+            client.get(&format!("{}/missing", host)).await,
+            Err(UpstreamError::Status(StatusCode::NOT_FOUND))
+        ));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                client.get(&format!("{}/down", host)).await,
+                Err(UpstreamError::Failed(_))
+            ));
+        }
+
+        // open: healthy URLs on the same upstream fail fast too
+        assert!(matches!(
+            client.get(&format!("{}/flaky", host)).await,
+            Err(UpstreamError::Unavailable(retry_after)) if retry_after <= Duration::from_millis(200)
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // after a while a call is let through, and closes the breaker again
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(client.get(&format!("{}/flaky", host)).await.is_ok());
+        assert!(client.get(&format!("{}/flaky", host)).await.is_ok());
+
+        // slow attempts time out, and retrying them gives up at the deadline
+        let client = UpstreamClient::new(
+            "test",
+            UpstreamConfig {
+                timeout: Duration::from_millis(100),
+                deadline: Duration::from_millis(250),
+                max_attempts: 10,
+                retry_delay: Duration::from_millis(1),
+                ..Default::default()
+            },
+        );
+        let start = Instant::now();
+        assert!(matches!(
+            client.get(&format!("{}/slow", host)).await,
+            Err(UpstreamError::Failed(_))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
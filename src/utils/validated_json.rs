@@ -0,0 +1,52 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use super::app_error::AppError;
+
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(data) = match Json::<T>::from_request(req, state).await {
+            Ok(data) => data,
+            Err(e) => match e.source() {
+                Some(source) => {
+                    return Err(AppError::new(
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid body: {}", source).as_str(),
+                    ));
+                }
+                None => {
+                    return Err(AppError::new(
+                        StatusCode::BAD_REQUEST,
+                        e.body_text().as_str(),
+                    ));
+                }
+            },
+        };
+
+        data.validate().map_err(|e| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid body: {}", e).as_str(),
+            )
+        })?;
+
+        Ok(ValidatedJson(data))
+    }
+}
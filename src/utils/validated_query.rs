@@ -27,7 +27,7 @@ where
                 Some(source) => {
                     return Err(AppError::new(
                         StatusCode::BAD_REQUEST,
-                        format!("Invalid query: {}", source.to_string()).as_str(),
+                        format!("Invalid query: {}", source).as_str(),
                     ));
                 }
                 None => {
@@ -42,7 +42,7 @@ where
         let data = match data.validate().map(|_| ValidatedQuery(data)).map_err(|e| {
             AppError::new(
                 StatusCode::BAD_REQUEST,
-                format!("Invalid query: {}", e.to_string()).as_str(),
+                format!("Invalid query: {}", e).as_str(),
             )
         }) {
             Ok(data) => data,